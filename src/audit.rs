@@ -0,0 +1,377 @@
+use std::net::SocketAddr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::firewall::Reachability;
+use crate::scanner::{PortEntry, SocketState};
+
+// Exposure checks over listening sockets: services bound to every interface
+// instead of loopback, databases and admin APIs reachable from the network,
+// root-owned listeners and processes whose executable has been deleted.
+// Exposure findings for ports the host firewall blocks are kept, downgraded
+// to info.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 4] = [
+        Severity::Info,
+        Severity::Low,
+        Severity::Medium,
+        Severity::High,
+    ];
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|severity| severity.to_string().eq_ignore_ascii_case(text))
+            .ok_or_else(|| {
+                format!(
+                    "unknown severity '{}' (expected info, low, medium or high)",
+                    text
+                )
+            })
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Low => write!(f, "low"),
+            Severity::Medium => write!(f, "medium"),
+            Severity::High => write!(f, "high"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Finding {
+    pub severity: Severity,
+    // Stable identifier for scripts, e.g. "wildcard-bind"
+    pub rule: String,
+    pub message: String,
+}
+
+// Ports of databases, caches, brokers and admin APIs that should not be
+// reachable from other hosts
+const SENSITIVE_PORTS: &[(u16, &str)] = &[
+    (1433, "SQL Server"),
+    (1521, "Oracle"),
+    (2375, "Docker API"),
+    (2376, "Docker API"),
+    (2379, "etcd"),
+    (3306, "MySQL"),
+    (5432, "PostgreSQL"),
+    (5672, "RabbitMQ"),
+    (5984, "CouchDB"),
+    (6379, "Redis"),
+    (8500, "Consul"),
+    (9200, "Elasticsearch"),
+    (9300, "Elasticsearch"),
+    (10250, "kubelet"),
+    (11211, "memcached"),
+    (15672, "RabbitMQ management"),
+    (27017, "MongoDB"),
+];
+
+// The same services recognized by process, for when they run on another port
+const SENSITIVE_PROCESSES: &[(&str, &str)] = &[
+    ("mariadbd", "MariaDB"),
+    ("memcached", "memcached"),
+    ("mongod", "MongoDB"),
+    ("mysqld", "MySQL"),
+    ("postgres", "PostgreSQL"),
+    ("redis-server", "Redis"),
+];
+
+pub struct Audit;
+
+impl Audit {
+    // Findings about who can connect, which the firewall can make moot
+    const EXPOSURE_RULES: &'static [&'static str] =
+        &["wildcard-bind", "network-bind", "exposed-service"];
+
+    // Findings for one socket, most severe first. Only listeners are audited.
+    pub fn entry(entry: &PortEntry) -> Vec<Finding> {
+        if entry.state != SocketState::Listen {
+            return Vec::new();
+        }
+
+        let mut findings = Vec::new();
+        let exposure = Exposure::of(&entry.local_addr);
+
+        match exposure {
+            Exposure::Wildcard => findings.push(Finding {
+                severity: Severity::Medium,
+                rule: "wildcard-bind".to_string(),
+                message: format!(
+                    "Listening on all interfaces ({}); bind to 127.0.0.1 if only local clients need it",
+                    entry.local_addr
+                ),
+            }),
+            Exposure::Network => findings.push(Finding {
+                severity: Severity::Low,
+                rule: "network-bind".to_string(),
+                message: format!("Listening on a network address ({})", entry.local_addr),
+            }),
+            Exposure::Loopback => {}
+        }
+
+        if exposure != Exposure::Loopback {
+            if let Some(service) = Self::sensitive_service(entry) {
+                findings.push(Finding {
+                    severity: Severity::High,
+                    rule: "exposed-service".to_string(),
+                    message: format!("{} is reachable from other hosts", service),
+                });
+            }
+        }
+
+        if entry.euid == Some(0) && entry.pid.is_some() {
+            findings.push(Finding {
+                severity: if exposure == Exposure::Loopback {
+                    Severity::Info
+                } else {
+                    Severity::Low
+                },
+                rule: "root-listener".to_string(),
+                message: format!("{} runs as root", Self::process_label(entry)),
+            });
+        }
+
+        if let Some(pid) = entry.pid {
+            if Self::executable_deleted(pid) {
+                findings.push(Finding {
+                    severity: Severity::High,
+                    rule: "deleted-executable".to_string(),
+                    message: format!(
+                        "{}'s executable was deleted or replaced since it started",
+                        Self::process_label(entry)
+                    ),
+                });
+            }
+        }
+
+        if let Some(verdict) = &entry.firewall {
+            for finding in &mut findings {
+                if !Self::EXPOSURE_RULES.contains(&finding.rule.as_str()) {
+                    continue;
+                }
+                match verdict.reachability {
+                    Reachability::Blocked => {
+                        finding.severity = Severity::Info;
+                        finding.message = format!(
+                            "{}; blocked by the firewall ({})",
+                            finding.message, verdict.rule
+                        );
+                    }
+                    Reachability::Reachable => {
+                        finding.message = format!(
+                            "{}; allowed by the firewall ({})",
+                            finding.message, verdict.rule
+                        );
+                    }
+                    Reachability::Unknown => {}
+                }
+            }
+        }
+
+        findings.sort_by_key(|finding| std::cmp::Reverse(finding.severity));
+        findings
+    }
+
+    pub fn max_severity(findings: &[Finding]) -> Option<Severity> {
+        findings.iter().map(|finding| finding.severity).max()
+    }
+
+    fn sensitive_service(entry: &PortEntry) -> Option<&'static str> {
+        let by_port = SENSITIVE_PORTS
+            .iter()
+            .find(|(port, _)| *port == entry.port)
+            .map(|(_, service)| *service);
+
+        by_port.or_else(|| {
+            SENSITIVE_PROCESSES
+                .iter()
+                .find(|(name, _)| *name == entry.process_name)
+                .map(|(_, service)| *service)
+        })
+    }
+
+    // Upgraded packages leave old binaries running; so do some kinds of malware
+    fn executable_deleted(pid: u32) -> bool {
+        std::fs::read_link(format!("/proc/{}/exe", pid))
+            .is_ok_and(|exe| exe.to_string_lossy().ends_with(" (deleted)"))
+    }
+
+    fn process_label(entry: &PortEntry) -> String {
+        match entry.pid {
+            Some(pid) => format!("{} (PID {})", entry.process_name, pid),
+            None => "The process".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Exposure {
+    Loopback,
+    Wildcard,
+    Network,
+}
+
+impl Exposure {
+    fn of(local_addr: &str) -> Self {
+        match local_addr.parse::<SocketAddr>() {
+            Ok(addr) if addr.ip().is_loopback() => Exposure::Loopback,
+            Ok(addr) if addr.ip().is_unspecified() => Exposure::Wildcard,
+            Ok(_) => Exposure::Network,
+            // lsof prints the wildcard address as "*:8080"
+            Err(_) if local_addr.starts_with('*') => Exposure::Wildcard,
+            Err(_) => Exposure::Network,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::firewall::FirewallVerdict;
+
+    fn rules(entry: &PortEntry) -> Vec<(Severity, String)> {
+        Audit::entry(entry)
+            .into_iter()
+            .map(|finding| (finding.severity, finding.rule))
+            .collect()
+    }
+
+    fn bound(addr: &str, port: u16, process: &str) -> PortEntry {
+        PortEntry {
+            local_addr: addr.to_string(),
+            ..PortEntry::listener(port, process)
+        }
+    }
+
+    #[test]
+    fn exposure_by_bind_address() {
+        assert!(rules(&bound("127.0.0.1:3000", 3000, "node")).is_empty());
+        assert!(rules(&bound("[::1]:3000", 3000, "node")).is_empty());
+        for addr in ["0.0.0.0:3000", "[::]:3000", "*:3000"] {
+            assert_eq!(
+                rules(&bound(addr, 3000, "node")),
+                [(Severity::Medium, "wildcard-bind".to_string())],
+                "{}",
+                addr
+            );
+        }
+        assert_eq!(
+            rules(&bound("192.168.1.5:3000", 3000, "node")),
+            [(Severity::Low, "network-bind".to_string())]
+        );
+
+        // Only listeners are audited
+        let connection = PortEntry {
+            state: SocketState::Established,
+            remote_addr: "10.0.0.2:50000".to_string(),
+            ..PortEntry::listener(3000, "node")
+        };
+        assert!(rules(&connection).is_empty());
+    }
+
+    #[test]
+    fn sensitive_services_by_port_or_process() {
+        assert_eq!(
+            rules(&PortEntry::listener(5432, "postgres")),
+            [
+                (Severity::High, "exposed-service".to_string()),
+                (Severity::Medium, "wildcard-bind".to_string())
+            ]
+        );
+        assert_eq!(
+            rules(&bound("10.0.0.1:7000", 7000, "redis-server"))[0],
+            (Severity::High, "exposed-service".to_string())
+        );
+        assert!(rules(&bound("127.0.0.1:5432", 5432, "postgres")).is_empty());
+    }
+
+    #[test]
+    fn root_listeners() {
+        let root = |addr| PortEntry {
+            pid: Some(u32::MAX),
+            euid: Some(0),
+            ..bound(addr, 8080, "nginx")
+        };
+        assert_eq!(
+            rules(&root("127.0.0.1:8080")),
+            [(Severity::Info, "root-listener".to_string())]
+        );
+        assert_eq!(
+            rules(&root("0.0.0.0:8080")),
+            [
+                (Severity::Medium, "wildcard-bind".to_string()),
+                (Severity::Low, "root-listener".to_string())
+            ]
+        );
+        // Without a process there is nothing to attribute it to
+        let unknown = PortEntry {
+            pid: None,
+            ..root("127.0.0.1:8080")
+        };
+        assert!(rules(&unknown).is_empty());
+    }
+
+    #[test]
+    fn firewall_verdict_adjusts_exposure_findings() {
+        let behind = |reachability| PortEntry {
+            pid: Some(u32::MAX),
+            euid: Some(0),
+            firewall: Some(FirewallVerdict {
+                reachability,
+                rule: "inet filter input: policy drop".to_string(),
+            }),
+            ..PortEntry::listener(5432, "postgres")
+        };
+
+        let findings = Audit::entry(&behind(Reachability::Blocked));
+        assert_eq!(
+            findings
+                .iter()
+                .map(|finding| (finding.severity, finding.rule.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                // Who runs it still matters
+                (Severity::Low, "root-listener"),
+                (Severity::Info, "wildcard-bind"),
+                (Severity::Info, "exposed-service"),
+            ]
+        );
+        assert!(findings[2]
+            .message
+            .ends_with("; blocked by the firewall (inet filter input: policy drop)"));
+
+        let findings = Audit::entry(&behind(Reachability::Reachable));
+        assert_eq!(findings[0].severity, Severity::High);
+        assert!(findings[0].message.contains("; allowed by the firewall"));
+
+        let findings = Audit::entry(&behind(Reachability::Unknown));
+        assert_eq!(findings[0].severity, Severity::High);
+        assert!(!findings[0].message.contains("firewall"));
+    }
+
+    #[test]
+    fn severities() {
+        let findings = Audit::entry(&PortEntry::listener(5432, "postgres"));
+        assert_eq!(Audit::max_severity(&findings), Some(Severity::High));
+        assert_eq!(Audit::max_severity(&[]), None);
+
+        assert_eq!(Severity::parse("MEDIUM"), Ok(Severity::Medium));
+        assert!(Severity::parse("critical").is_err());
+        assert!(Severity::Info < Severity::Low && Severity::Medium < Severity::High);
+    }
+}
@@ -0,0 +1,120 @@
+use portkill::config::Config;
+use portkill::query::Query;
+
+pub use portkill::config::parse_duration;
+
+// Minimal argument reader shared by the subcommands. Options may be given as
+// `--name value` or `--name=value`; anything left over after a command has
+// taken what it understands is reported as an error.
+pub struct Args {
+    args: Vec<String>,
+}
+
+impl Args {
+    pub fn new(args: Vec<String>) -> Self {
+        Self { args }
+    }
+
+    // Everything after a literal `--`, e.g. a command to run. Call first so the
+    // command's own options aren't taken as ours.
+    pub fn trailing(&mut self) -> Vec<String> {
+        match self.args.iter().position(|arg| arg == "--") {
+            Some(index) => {
+                let trailing = self.args.split_off(index + 1);
+                self.args.pop();
+                trailing
+            }
+            None => Vec::new(),
+        }
+    }
+
+    pub fn flag(&mut self, name: &str) -> bool {
+        match self.args.iter().position(|arg| arg == name) {
+            Some(index) => {
+                self.args.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn value(&mut self, name: &str) -> Result<Option<String>, String> {
+        let prefix = format!("{}=", name);
+
+        for index in 0..self.args.len() {
+            if self.args[index] == name {
+                if index + 1 >= self.args.len() {
+                    return Err(format!("{} requires a value", name));
+                }
+                let value = self.args.remove(index + 1);
+                self.args.remove(index);
+                return Ok(Some(value));
+            }
+
+            if let Some(value) = self.args[index].strip_prefix(&prefix) {
+                let value = value.to_string();
+                self.args.remove(index);
+                return Ok(Some(value));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // `--filter`, falling back to the configured default filter
+    pub fn filter(&mut self) -> Result<Query, String> {
+        match self.value("--filter")? {
+            Some(filter) => Query::parse(&filter),
+            None => match &Config::global().default_filter {
+                Some(filter) => Query::parse(filter)
+                    .map_err(|e| format!("invalid default filter in config: {}", e)),
+                None => Ok(Query::default()),
+            },
+        }
+    }
+
+    // `--format`, falling back to the configured default format
+    pub fn format(&mut self) -> Result<OutputFormat, String> {
+        match self.value("--format")? {
+            Some(format) => OutputFormat::parse(&format),
+            None => match &Config::global().default_format {
+                Some(format) => OutputFormat::parse(format)
+                    .map_err(|e| format!("invalid default format in config: {}", e)),
+                None => Ok(OutputFormat::Human),
+            },
+        }
+    }
+
+    // Call after all options have been taken, so option values aren't mistaken
+    // for positionals
+    pub fn positional(&mut self) -> Option<String> {
+        let index = self.args.iter().position(|arg| !arg.starts_with("--"))?;
+        Some(self.args.remove(index))
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        match self.args.first() {
+            Some(arg) => Err(format!("unexpected argument '{}'", arg)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Human,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "human" | "text" => Ok(OutputFormat::Human),
+            "json" | "ndjson" => Ok(OutputFormat::Json),
+            _ => Err(format!(
+                "unknown output format '{}' (expected human or json)",
+                text
+            )),
+        }
+    }
+}
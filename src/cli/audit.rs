@@ -0,0 +1,139 @@
+use portkill::audit::{Audit, Finding, Severity};
+use portkill::scanner::PortEntry;
+
+use super::args::{Args, OutputFormat};
+use super::{EXIT_FAILURE, EXIT_FINDINGS, EXIT_OK};
+
+const USAGE: &str = "\
+Usage: portkill audit [OPTIONS]
+
+Checks listening sockets for exposure problems:
+
+  wildcard-bind        medium  listening on 0.0.0.0 or :: instead of loopback
+  network-bind         low     listening on a non-loopback address
+  exposed-service      high    database, cache or admin API reachable from other
+                               hosts
+  root-listener        low     listener owned by root (info when loopback only)
+  deleted-executable   high    the process's executable was deleted or replaced
+
+Bind and exposure findings for ports the host firewall (nftables or iptables)
+blocks are reported as info. Reading the ruleset needs root.
+
+Options:
+  --min-severity <LEVEL>  Only report findings at or above LEVEL: info, low,
+                          medium or high (default low)
+  --filter <QUERY>        Only audit sockets matching a filter expression
+  --format <human|json>   Output format
+
+Exit codes: 0 nothing reported, 1 scan error, 2 usage error, 3 findings reported
+";
+
+pub fn run(mut args: Args) -> Result<i32, String> {
+    if args.flag("--help") {
+        print!("{}", USAGE);
+        return Ok(EXIT_OK);
+    }
+
+    let min_severity = match args.value("--min-severity")? {
+        Some(severity) => Severity::parse(&severity)?,
+        None => Severity::Low,
+    };
+    let query = args.filter()?;
+    let format = args.format()?;
+    args.finish()?;
+
+    let entries = match super::scan_with_firewall() {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("portkill audit: failed to scan ports: {}", e);
+            return Ok(EXIT_FAILURE);
+        }
+    };
+
+    let mut findings: Vec<(&PortEntry, Finding)> = entries
+        .iter()
+        .filter(|entry| query.matches(entry))
+        .flat_map(|entry| {
+            Audit::entry(entry)
+                .into_iter()
+                .map(move |finding| (entry, finding))
+        })
+        .filter(|(_, finding)| finding.severity >= min_severity)
+        .collect();
+    findings.sort_by(|(a, a_finding), (b, b_finding)| {
+        b_finding
+            .severity
+            .cmp(&a_finding.severity)
+            .then_with(|| a.port.cmp(&b.port))
+    });
+
+    match format {
+        OutputFormat::Human => print_report(&findings),
+        OutputFormat::Json => {
+            let report: Vec<serde_json::Value> = findings
+                .iter()
+                .map(|(entry, finding)| {
+                    serde_json::json!({
+                        "severity": finding.severity,
+                        "rule": finding.rule,
+                        "message": finding.message,
+                        "entry": entry,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::Value::Array(report));
+        }
+    }
+
+    if findings.is_empty() {
+        Ok(EXIT_OK)
+    } else {
+        Ok(EXIT_FINDINGS)
+    }
+}
+
+fn print_report(findings: &[(&PortEntry, Finding)]) {
+    if findings.is_empty() {
+        println!("No findings");
+        return;
+    }
+
+    println!(
+        "{:<8} {:<6} {:<24} {:<20} {:<20} MESSAGE",
+        "SEVERITY", "PORT", "ADDRESS", "PROCESS", "RULE"
+    );
+
+    for (entry, finding) in findings {
+        let process = match entry.pid {
+            Some(pid) => format!("{} ({})", entry.process_name, pid),
+            None => "-".to_string(),
+        };
+
+        println!(
+            "{:<8} {:<6} {:<24} {:<20} {:<20} {}",
+            finding.severity.to_string().to_uppercase(),
+            entry.port,
+            entry.local_addr,
+            process,
+            finding.rule,
+            finding.message
+        );
+    }
+
+    let counts: Vec<String> = Severity::ALL
+        .iter()
+        .rev()
+        .map(|severity| {
+            let count = findings
+                .iter()
+                .filter(|(_, finding)| finding.severity == *severity)
+                .count();
+            (severity, count)
+        })
+        .filter(|(_, count)| *count > 0)
+        .map(|(severity, count)| format!("{} {}", count, severity))
+        .collect();
+
+    println!();
+    println!("{} findings ({})", findings.len(), counts.join(", "));
+}
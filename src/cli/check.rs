@@ -0,0 +1,298 @@
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use portkill::declared::{Declarations, DeclaredPort};
+use portkill::rpc::Client;
+use portkill::scanner::{Killer, PortEntry, Project, Scanner, SocketState};
+
+use super::args::{Args, OutputFormat};
+use super::{EXIT_CONFLICT, EXIT_FAILURE, EXIT_OK};
+
+const USAGE: &str = "\
+Usage: portkill check [DIR] [OPTIONS]
+
+Finds ports declared in DIR (default: the current directory) that something is
+already listening on:
+
+  docker-compose.yml, compose.yaml   published `ports:` of each service
+  .env, .env.*                       PORT= and *_PORT= variables
+  package.json                       --port / PORT= in scripts
+  Procfile, Procfile.*               --port / PORT= in process commands
+
+When run in a terminal, offers to free each taken port by signalling the process
+holding it.
+
+Options:
+  --kill                  Free taken ports without asking (protected processes
+                          are skipped)
+  --no-kill               Only report, never offer to free ports
+  --format <human|json>   Output format
+
+Exit codes: 0 all declared ports free, 1 scan error, 2 usage error,
+3 ports still taken
+";
+
+// How long to wait for signalled processes to release their ports
+const RELEASE_TIMEOUT: Duration = Duration::from_secs(3);
+
+struct Conflict<'a> {
+    declared: &'a DeclaredPort,
+    holders: Vec<&'a PortEntry>,
+}
+
+pub fn run(mut args: Args) -> Result<i32, String> {
+    if args.flag("--help") {
+        print!("{}", USAGE);
+        return Ok(EXIT_OK);
+    }
+
+    let kill = args.flag("--kill");
+    let no_kill = args.flag("--no-kill");
+    let format = args.format()?;
+    let dir = PathBuf::from(args.positional().unwrap_or_else(|| ".".to_string()));
+    args.finish()?;
+
+    if kill && no_kill {
+        return Err("--kill and --no-kill can't be combined".to_string());
+    }
+    if !dir.is_dir() {
+        return Err(format!("'{}' is not a directory", dir.display()));
+    }
+
+    let declarations = Declarations::scan(&dir);
+    for warning in &declarations.warnings {
+        eprintln!("portkill check: skipping {}", warning);
+    }
+
+    let entries = match super::scan_all() {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("portkill check: failed to scan ports: {}", e);
+            return Ok(EXIT_FAILURE);
+        }
+    };
+
+    let conflicts: Vec<Conflict> = declarations
+        .ports
+        .iter()
+        .map(|declared| Conflict {
+            declared,
+            holders: entries
+                .iter()
+                .filter(|entry| entry.port == declared.port && entry.state == SocketState::Listen)
+                .collect(),
+        })
+        .collect();
+    let taken: Vec<u16> = conflicts
+        .iter()
+        .filter(|conflict| !conflict.holders.is_empty())
+        .map(|conflict| conflict.declared.port)
+        .collect();
+
+    if format == OutputFormat::Json {
+        print_json(&conflicts);
+    } else {
+        print_report(&dir, &conflicts);
+    }
+
+    if taken.is_empty() {
+        return Ok(EXIT_OK);
+    }
+
+    let interactive = format == OutputFormat::Human
+        && std::io::stdin().is_terminal()
+        && std::io::stderr().is_terminal();
+    if no_kill || !(kill || interactive) {
+        return Ok(EXIT_CONFLICT);
+    }
+
+    let mut signalled = false;
+    let mut daemon = Client::connect();
+    let mut terminations = Vec::new();
+    for (pid, process_name, ports) in holders_by_pid(&conflicts) {
+        let ports_label = ports
+            .iter()
+            .map(|port| port.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        if !Killer::can_kill(pid) {
+            eprintln!(
+                "Not freeing port {}: {} (PID {}) is protected",
+                ports_label, process_name, pid
+            );
+            continue;
+        }
+
+        if !kill
+            && !confirm(&format!(
+                "Free port {} by stopping {} (PID {})?",
+                ports_label, process_name, pid
+            ))
+        {
+            continue;
+        }
+
+        // Through the daemon when one runs, so its kill policy applies and it
+        // sends the SIGKILL after the grace period
+        let result = match &mut daemon {
+            Some(client) => client.kill(pid, None),
+            None => Killer::terminate(pid).map(|termination| {
+                let message = termination.message.clone();
+                terminations.push(termination);
+                message
+            }),
+        };
+        match result {
+            Ok(message) => {
+                eprintln!("{}", message);
+                signalled = true;
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    // This process would exit before a pending SIGKILL is sent otherwise
+    for termination in terminations {
+        match termination.wait() {
+            Some(Ok(message)) => eprintln!("Grace period expired: {}", message),
+            Some(Err(e)) => eprintln!("Grace period expired: {}", e),
+            None => {}
+        }
+    }
+
+    if signalled && wait_for_release(&taken) {
+        eprintln!("All declared ports are free now");
+        return Ok(EXIT_OK);
+    }
+
+    Ok(EXIT_CONFLICT)
+}
+
+fn print_report(dir: &std::path::Path, conflicts: &[Conflict]) {
+    if conflicts.is_empty() {
+        println!("No ports declared in {}", dir.display());
+        return;
+    }
+
+    // Listeners started from this checkout are most likely an earlier run of it
+    let this_project = std::fs::canonicalize(dir)
+        .ok()
+        .and_then(|dir| Project::for_dir(&dir));
+
+    for conflict in conflicts {
+        let location = format!("{}:{}", conflict.declared.file, conflict.declared.line);
+        let status = if conflict.holders.is_empty() {
+            "free".to_string()
+        } else {
+            let holders: Vec<String> = conflict
+                .holders
+                .iter()
+                .map(|entry| describe_holder(entry, this_project.as_ref()))
+                .collect();
+            format!("in use by {}", holders.join(", "))
+        };
+
+        println!(
+            "{:<6} {:<24} {:<24} {}",
+            conflict.declared.port, location, conflict.declared.source, status
+        );
+    }
+
+    let taken = conflicts
+        .iter()
+        .filter(|conflict| !conflict.holders.is_empty())
+        .count();
+    println!();
+    if taken == 0 {
+        println!("All {} declared ports are free", conflicts.len());
+    } else {
+        println!("{} of {} declared ports are taken", taken, conflicts.len());
+    }
+}
+
+fn describe_holder(entry: &PortEntry, this_project: Option<&Project>) -> String {
+    let process = match entry.pid {
+        Some(pid) => format!("{} (PID {}, user {})", entry.process_name, pid, entry.user),
+        // Owned by another user's process we can't inspect, or a kernel socket
+        None => format!("an unknown process (user {})", entry.user),
+    };
+
+    match &entry.project {
+        Some(project) if Some(project) == this_project => format!("{} from this project", process),
+        Some(project) => format!("{} from project {}", process, project.name),
+        None => process,
+    }
+}
+
+fn print_json(conflicts: &[Conflict]) {
+    let report: Vec<serde_json::Value> = conflicts
+        .iter()
+        .map(|conflict| {
+            serde_json::json!({
+                "port": conflict.declared.port,
+                "file": conflict.declared.file,
+                "line": conflict.declared.line,
+                "source": conflict.declared.source,
+                "in_use": !conflict.holders.is_empty(),
+                "holders": conflict.holders,
+            })
+        })
+        .collect();
+
+    println!("{}", serde_json::Value::Array(report));
+}
+
+// Each process once, with every declared port it holds
+fn holders_by_pid(conflicts: &[Conflict]) -> Vec<(u32, String, Vec<u16>)> {
+    let mut holders: Vec<(u32, String, Vec<u16>)> = Vec::new();
+
+    for conflict in conflicts {
+        for entry in &conflict.holders {
+            let Some(pid) = entry.pid else {
+                continue;
+            };
+
+            match holders.iter_mut().find(|(holder, _, _)| *holder == pid) {
+                Some((_, _, ports)) => {
+                    if !ports.contains(&entry.port) {
+                        ports.push(entry.port);
+                    }
+                }
+                None => holders.push((pid, entry.process_name.clone(), vec![entry.port])),
+            }
+        }
+    }
+
+    holders
+}
+
+fn confirm(question: &str) -> bool {
+    eprint!("{} [y/N] ", question);
+    let _ = std::io::stderr().flush();
+
+    let mut answer = String::new();
+    if std::io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+fn wait_for_release(ports: &[u16]) -> bool {
+    let started = Instant::now();
+
+    while started.elapsed() < RELEASE_TIMEOUT {
+        if let Ok(entries) = Scanner::scan_sockets() {
+            let still_taken = entries
+                .iter()
+                .any(|entry| ports.contains(&entry.port) && entry.state == SocketState::Listen);
+            if !still_taken {
+                return true;
+            }
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+
+    false
+}
@@ -0,0 +1,186 @@
+use std::time::Duration;
+
+use portkill::config::Config;
+use portkill::daemon::Daemon;
+use portkill::history::{History, Recorder};
+use portkill::metrics::MetricsServer;
+use portkill::rpc::{self, Client};
+use portkill::rules::{Outcome, RuleEngine};
+
+use super::args::{parse_duration, Args, OutputFormat};
+use super::{EXIT_FAILURE, EXIT_OK};
+
+const USAGE: &str = "\
+Usage: portkill daemon [OPTIONS]
+       portkill daemon status [--format <human|json>]
+
+Keeps an up-to-date scan, the history recorder and the [[rules]] from the
+config running, and serves them over a UNIX socket. While it runs, the GUI
+and `list`, `stats`, `audit`, `check` and `watch` read its scans instead of
+scanning by themselves; set PORTKILL_NO_DAEMON=1 to make them scan locally
+anyway.
+
+Options:
+  --socket <PATH>         Socket to listen on (default [daemon] socket, or
+                          $XDG_RUNTIME_DIR/portkill.sock)
+  --interval <DURATION>   Scan interval (default [daemon] scan_interval, 2s)
+  --no-history            Don't append listeners to the history file
+  --no-rules              Don't run the configured rules
+  --metrics <ADDR>        Serve Prometheus metrics at http://ADDR/metrics
+                          (default [daemon] metrics, off when unset)
+  --dry-run               Log what the rules would do without doing it
+
+The API is JSON-RPC 2.0, one object per line: status, list, subscribe and
+kill. Only the user running the daemon can connect to the socket. Kills are
+refused for protected processes and for processes that hold no socket.
+";
+
+pub fn run(mut args: Args) -> Result<i32, String> {
+    if args.flag("--help") {
+        print!("{}", USAGE);
+        return Ok(EXIT_OK);
+    }
+
+    let socket = args.value("--socket")?.map(std::path::PathBuf::from);
+    let interval = args.value("--interval")?;
+    let no_history = args.flag("--no-history");
+    let no_rules = args.flag("--no-rules");
+    let dry_run = args.flag("--dry-run");
+    let metrics = args.value("--metrics")?;
+    let format = args.format()?;
+    let status = match args.positional() {
+        Some(command) if command == "status" => true,
+        Some(command) => return Err(format!("unknown daemon command '{}'", command)),
+        None => false,
+    };
+    args.finish()?;
+
+    let Some(socket) = socket.or_else(rpc::socket_path) else {
+        eprintln!("portkill daemon: cannot locate the daemon socket; pass --socket or set [daemon] socket");
+        return Ok(EXIT_FAILURE);
+    };
+
+    if status {
+        if interval.is_some() || no_history || no_rules || dry_run || metrics.is_some() {
+            return Err("status takes no options besides --socket and --format".to_string());
+        }
+        return print_status(&socket, format);
+    }
+
+    let interval = match interval {
+        Some(interval) => parse_duration(&interval)?,
+        None => Config::global().daemon_scan_interval,
+    };
+    if interval == Duration::ZERO {
+        return Err("--interval must be longer than zero".to_string());
+    }
+
+    let mut daemon = match Daemon::bind(&socket) {
+        Ok(daemon) => daemon,
+        Err(e) => {
+            eprintln!("portkill daemon: {}", e);
+            return Ok(EXIT_FAILURE);
+        }
+    };
+    if let Some(address) = metrics.or_else(|| Config::global().daemon_metrics.clone()) {
+        match MetricsServer::bind(&address) {
+            Ok(server) => {
+                eprintln!("Serving metrics at http://{}/metrics", server.local_addr());
+                daemon = daemon.with_metrics(server);
+            }
+            Err(e) => {
+                eprintln!("portkill daemon: metrics: {}", e);
+                return Ok(EXIT_FAILURE);
+            }
+        }
+    }
+
+    let recorder = if no_history {
+        None
+    } else {
+        let recorder = History::path()
+            .ok_or_else(|| "cannot locate the history file; set [history] path".to_string())
+            .and_then(|path| Recorder::open(&path));
+        match recorder {
+            Ok(recorder) => Some(recorder),
+            Err(e) => {
+                eprintln!("portkill daemon: not recording history: {}", e);
+                None
+            }
+        }
+    };
+
+    let rules = if no_rules {
+        RuleEngine::new(Vec::new(), dry_run, None)
+    } else {
+        RuleEngine::from_config(dry_run)
+    };
+    if !rules.is_empty() {
+        eprintln!(
+            "Running {} rule(s){}",
+            rules.rules().len(),
+            if dry_run || Config::global().actions_dry_run {
+                " in dry-run mode"
+            } else {
+                ""
+            }
+        );
+    }
+
+    eprintln!(
+        "Serving on {} and scanning every {:?} (Ctrl+C to stop)",
+        socket.display(),
+        interval
+    );
+
+    match daemon.run(interval, recorder, rules, print_outcome) {
+        Ok(()) => Ok(EXIT_OK),
+        Err(e) => {
+            eprintln!("portkill daemon: {}", e);
+            Ok(EXIT_FAILURE)
+        }
+    }
+}
+
+fn print_outcome(outcome: &Outcome) {
+    eprintln!("{}", outcome);
+}
+
+fn print_status(socket: &std::path::Path, format: OutputFormat) -> Result<i32, String> {
+    let status = Client::connect_to(socket).and_then(|mut client| client.status());
+
+    match (format, status) {
+        (OutputFormat::Human, Ok(status)) => {
+            println!("Running on {}", socket.display());
+            println!("  PID          {}", status.pid);
+            println!("  Version      {} (API v{})", status.version, status.api);
+            println!(
+                "  Last scan    {}",
+                match status.scanned_at {
+                    Some(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    None => "pending".to_string(),
+                }
+            );
+            println!("  Sockets      {}", status.sockets);
+            println!("  Subscribers  {}", status.subscribers);
+            println!("  Rules        {}", status.rules);
+            println!(
+                "  History      {}",
+                if status.recording {
+                    "recording"
+                } else {
+                    "not recorded"
+                }
+            );
+            Ok(EXIT_OK)
+        }
+        (OutputFormat::Json, Ok(status)) => {
+            println!("{}", serde_json::json!(status));
+            Ok(EXIT_OK)
+        }
+        (_, Err(e)) => {
+            eprintln!("portkill daemon: not available ({})", e);
+            Ok(EXIT_FAILURE)
+        }
+    }
+}
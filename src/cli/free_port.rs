@@ -0,0 +1,165 @@
+use std::net::TcpListener;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::CommandExt;
+
+use portkill::ports::{self, FreePortOptions, PortRange};
+use portkill::scanner::Scanner;
+
+use super::args::{Args, OutputFormat};
+use super::{EXIT_FAILURE, EXIT_OK};
+
+const USAGE: &str = "\
+Usage: portkill free-port [OPTIONS] [--exec -- <COMMAND>...]
+
+Prints unused TCP ports, one per line. By default ports are picked from the
+unprivileged range below the kernel's ephemeral range
+(/proc/sys/net/ipv4/ip_local_port_range), skipping well-known service ports and
+ports with sockets still in TIME_WAIT.
+
+Options:
+  --count <N>             Number of ports to find (default 1)
+  --range <START-END>     Range to search
+  --allow-well-known      Don't skip well-known and reserved ports
+  --allow-time-wait       Don't skip ports with TIME_WAIT sockets
+  --format <human|json>   Output format
+  --exec -- <COMMAND>...  Keep the ports bound and exec COMMAND with them, so no
+                          other process can take them in between. The bound
+                          sockets are passed systemd-style from fd 3
+                          (LISTEN_FDS, LISTEN_PID) and the port numbers in
+                          PORT (first) and PORTS (comma-separated).
+";
+
+pub fn run(mut args: Args) -> Result<i32, String> {
+    let command = args.trailing();
+
+    if args.flag("--help") {
+        print!("{}", USAGE);
+        return Ok(EXIT_OK);
+    }
+
+    let options = FreePortOptions {
+        range: args
+            .value("--range")?
+            .map(|range| PortRange::parse(&range))
+            .transpose()?,
+        count: match args.value("--count")? {
+            Some(count) => match count.parse::<usize>() {
+                Ok(count) if count > 0 => count,
+                _ => return Err(format!("invalid count '{}'", count)),
+            },
+            None => 1,
+        },
+        avoid_well_known: !args.flag("--allow-well-known"),
+        avoid_time_wait: !args.flag("--allow-time-wait"),
+    };
+    let format = args.format()?;
+    let exec = args.flag("--exec");
+    args.finish()?;
+
+    if exec && command.is_empty() {
+        return Err("--exec requires a command after `--`".to_string());
+    }
+
+    let entries = match Scanner::scan_sockets() {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("portkill free-port: failed to scan ports: {}", e);
+            return Ok(EXIT_FAILURE);
+        }
+    };
+
+    if exec {
+        return match ports::reserve_free_ports(&entries, &options) {
+            Ok(listeners) => Ok(exec_with_listeners(listeners, command)),
+            Err(e) => {
+                eprintln!("portkill free-port: {}", e);
+                Ok(EXIT_FAILURE)
+            }
+        };
+    }
+
+    let found = ports::find_free_ports(&entries, &options);
+    if found.len() < options.count {
+        eprintln!(
+            "portkill free-port: only {} of {} requested ports are free in {}",
+            found.len(),
+            options.count,
+            options.effective_range()
+        );
+        return Ok(EXIT_FAILURE);
+    }
+
+    match format {
+        OutputFormat::Human => {
+            for port in found {
+                println!("{}", port);
+            }
+        }
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                serde_json::json!({
+                    "ports": found,
+                    "range": options.effective_range().to_string(),
+                })
+            );
+        }
+    }
+
+    Ok(EXIT_OK)
+}
+
+// Replaces this process with `command`, so LISTEN_PID (our PID) stays valid
+// for sd_listen_fds()-style consumers. Only returns on failure.
+fn exec_with_listeners(listeners: Vec<TcpListener>, command: Vec<String>) -> i32 {
+    const FIRST_FD: i32 = 3;
+
+    let ports: Vec<String> = listeners
+        .iter()
+        .filter_map(|listener| listener.local_addr().ok())
+        .map(|addr| addr.port().to_string())
+        .collect();
+
+    if let Err(e) = move_listeners_to(&listeners, FIRST_FD) {
+        eprintln!("portkill free-port: failed to pass sockets: {}", e);
+        return EXIT_FAILURE;
+    }
+
+    let error = std::process::Command::new(&command[0])
+        .args(&command[1..])
+        .env("LISTEN_FDS", listeners.len().to_string())
+        .env("LISTEN_PID", std::process::id().to_string())
+        .env("PORT", &ports[0])
+        .env("PORTS", ports.join(","))
+        .exec();
+
+    eprintln!(
+        "portkill free-port: failed to run {}: {}",
+        command[0], error
+    );
+    EXIT_FAILURE
+}
+
+// Places the listeners at consecutive descriptors starting at `first`, without
+// close-on-exec. They are first moved above the target range so that a
+// listener already sitting on a target descriptor isn't overwritten.
+fn move_listeners_to(listeners: &[TcpListener], first: i32) -> nix::Result<()> {
+    use nix::fcntl::{fcntl, FcntlArg};
+
+    let above = first + listeners.len() as i32;
+    let mut moved = Vec::with_capacity(listeners.len());
+
+    for listener in listeners {
+        moved.push(fcntl(
+            listener.as_raw_fd(),
+            FcntlArg::F_DUPFD_CLOEXEC(above),
+        )?);
+    }
+
+    for (index, fd) in moved.into_iter().enumerate() {
+        nix::unistd::dup2(fd, first + index as i32)?;
+        nix::unistd::close(fd)?;
+    }
+
+    Ok(())
+}
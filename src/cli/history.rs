@@ -0,0 +1,235 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use portkill::history::{parse_time, History, Interval, Record, Recorder};
+use portkill::ports::PortRange;
+use portkill::scanner::{PortEntry, Scanner};
+
+use super::args::{parse_duration, Args, OutputFormat};
+use super::{EXIT_FAILURE, EXIT_OK};
+
+const USAGE: &str = "\
+Usage: portkill history [OPTIONS]
+       portkill history record [--interval <DURATION>]
+
+Shows what listened on which ports over time, from the history file that
+`portkill history record` (or the GUI with [history] record = true) appends to.
+
+Options:
+  --port <PORT|START-END> Only these local ports
+  --filter <QUERY>        Only listeners matching a filter expression
+  --since <TIME>          Start of the range (default 24h); a duration ago
+                          such as 2h or 30m, 03:00, '2026-10-18 03:00' or
+                          an RFC 3339 timestamp
+  --until <TIME>          End of the range (default now)
+  --at <TIME>             Only what was listening at that moment
+  --format <human|json>   Output format
+
+Recording:
+  --interval <DURATION>   Scan interval (default 5s)
+
+The file is ~/.local/state/portkill/history.ndjson unless the config sets
+[history] path; events older than [history] retention (default 7d) are
+compacted away.
+";
+
+const DEFAULT_SINCE: &str = "24h";
+const DEFAULT_RECORD_INTERVAL: Duration = Duration::from_secs(5);
+
+pub fn run(mut args: Args) -> Result<i32, String> {
+    if args.flag("--help") {
+        print!("{}", USAGE);
+        return Ok(EXIT_OK);
+    }
+
+    let interval = args.value("--interval")?;
+    let ports = args
+        .value("--port")?
+        .map(|ports| PortRange::parse(&ports))
+        .transpose()?;
+    let query = args.filter()?;
+    let now = Local::now();
+    let since = args.value("--since")?;
+    let until = args.value("--until")?;
+    let at = args
+        .value("--at")?
+        .map(|at| parse_time(&at, now))
+        .transpose()?;
+    let format = args.format()?;
+    let record = match args.positional() {
+        Some(command) if command == "record" => true,
+        Some(command) => return Err(format!("unknown history command '{}'", command)),
+        None => false,
+    };
+    args.finish()?;
+
+    let Some(path) = History::path() else {
+        eprintln!("portkill history: cannot locate the history file; set [history] path");
+        return Ok(EXIT_FAILURE);
+    };
+
+    if record {
+        let interval = match interval {
+            Some(interval) => parse_duration(&interval)?,
+            None => DEFAULT_RECORD_INTERVAL,
+        };
+        if interval.is_zero() {
+            return Err("--interval must be longer than zero".to_string());
+        }
+        return record_forever(&path, interval);
+    }
+    if interval.is_some() {
+        return Err("--interval only applies to `history record`".to_string());
+    }
+    if at.is_some() && (since.is_some() || until.is_some()) {
+        return Err("--at can't be combined with --since or --until".to_string());
+    }
+
+    let history = match History::load(&path) {
+        Ok(history) => history,
+        Err(e) => {
+            eprintln!("portkill history: {}", e);
+            return Ok(EXIT_FAILURE);
+        }
+    };
+    if history.records.is_empty() {
+        eprintln!(
+            "portkill history: nothing recorded in {} yet; run `portkill history record` \
+             or set [history] record = true",
+            path.display()
+        );
+    }
+
+    let matches = |entry: &PortEntry| {
+        ports.is_none_or(|range| range.contains(entry.port)) && query.matches(entry)
+    };
+
+    if let Some(at) = at {
+        let listening: Vec<Interval> = history
+            .intervals()
+            .into_iter()
+            .filter(|interval| interval.is_active_at(at) && matches(&interval.entry))
+            .collect();
+
+        match format {
+            OutputFormat::Human => print_listening(&listening, at),
+            OutputFormat::Json => println!(
+                "{}",
+                serde_json::json!({ "at": at.to_rfc3339(), "listening": listening })
+            ),
+        }
+        return Ok(EXIT_OK);
+    }
+
+    let since = parse_time(since.as_deref().unwrap_or(DEFAULT_SINCE), now)?;
+    let until = match until {
+        Some(until) => parse_time(&until, now)?,
+        None => now,
+    };
+    if since > until {
+        return Err("--since is after --until".to_string());
+    }
+
+    let listening: Vec<Interval> = history
+        .intervals()
+        .into_iter()
+        .filter(|interval| interval.is_active_at(since) && matches(&interval.entry))
+        .collect();
+    let events: Vec<&Record> = history
+        .events_between(since, until)
+        .filter(|record| matches(&record.entry))
+        .collect();
+
+    match format {
+        OutputFormat::Human => {
+            print_listening(&listening, since);
+            println!();
+            print_events(&events);
+        }
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::json!({
+                "since": since.to_rfc3339(),
+                "until": until.to_rfc3339(),
+                "listening": listening,
+                "events": events,
+            })
+        ),
+    }
+
+    Ok(EXIT_OK)
+}
+
+fn record_forever(path: &std::path::Path, interval: Duration) -> Result<i32, String> {
+    let mut recorder = match Recorder::open(path) {
+        Ok(recorder) => recorder,
+        Err(e) => {
+            eprintln!("portkill history: {}", e);
+            return Ok(EXIT_FAILURE);
+        }
+    };
+    eprintln!(
+        "Recording listeners to {} every {:?} (Ctrl+C to stop)",
+        path.display(),
+        interval
+    );
+
+    loop {
+        match Scanner::scan_all() {
+            Ok(entries) => {
+                if let Err(e) = recorder.record(&entries) {
+                    eprintln!("portkill history: {}", e);
+                    return Ok(EXIT_FAILURE);
+                }
+            }
+            Err(e) => log::warn!("Failed to scan ports: {}", e),
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+fn print_listening(listening: &[Interval], at: DateTime<Local>) {
+    println!("Listening at {}", format_time(at));
+
+    if listening.is_empty() {
+        println!("  nothing");
+    }
+    for interval in listening {
+        println!(
+            "  {:<6} {:<4} {:<24} {:<32} since {}",
+            interval.entry.port,
+            interval.entry.protocol.to_string(),
+            interval.entry.local_addr,
+            owner(&interval.entry),
+            format_time(interval.start)
+        );
+    }
+}
+
+fn print_events(events: &[&Record]) {
+    if events.is_empty() {
+        println!("No changes");
+    }
+    for record in events {
+        println!(
+            "{} {:<6} {:<6} {:<4} {:<24} {}",
+            format_time(record.time),
+            record.event.to_string().to_uppercase(),
+            record.entry.port,
+            record.entry.protocol.to_string(),
+            record.entry.local_addr,
+            owner(&record.entry)
+        );
+    }
+}
+
+fn owner(entry: &PortEntry) -> String {
+    match entry.pid {
+        Some(pid) => format!("{} (PID {}, {})", entry.process_name, pid, entry.user),
+        None => "-".to_string(),
+    }
+}
+
+fn format_time(time: DateTime<Local>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S").to_string()
+}
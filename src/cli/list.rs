@@ -0,0 +1,225 @@
+use std::time::Duration;
+
+use portkill::connections::ListenerConnections;
+use portkill::query::Field;
+use portkill::resolver::Resolver;
+use portkill::scanner::{PortEntry, SocketState, TcpTimer};
+
+use super::args::{Args, OutputFormat};
+use super::{EXIT_FAILURE, EXIT_OK};
+
+const USAGE: &str = "\
+Usage: portkill list [OPTIONS]
+
+Prints listening sockets and their processes.
+
+Options:
+  --filter <QUERY>        Filter expression, as in the GUI search box, e.g.
+                          'port:3000-3999 proc:~node !user:root'
+  --all                   Include every socket state, not just LISTEN
+                          (implied when the filter has a state: term)
+  --connections           Under each listener matching the filter, count its
+                          inbound connections per remote peer
+  --resolve               With --connections, name peers from /etc/hosts and
+                          reverse DNS (waits up to 3s for answers)
+  --metrics               Add the kernel's queue, timer and retransmit columns
+                          and flag sockets that look stuck
+  --format <human|json>   Output format
+";
+
+// How long --resolve waits for reverse DNS before printing addresses
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub fn run(mut args: Args) -> Result<i32, String> {
+    if args.flag("--help") {
+        print!("{}", USAGE);
+        return Ok(EXIT_OK);
+    }
+
+    let query = args.filter()?;
+    let all_states = args.flag("--all") || query.constrains(Field::State);
+    let connections = args.flag("--connections");
+    let resolve = args.flag("--resolve");
+    let metrics = args.flag("--metrics");
+    let format = args.format()?;
+    args.finish()?;
+
+    if resolve && !connections {
+        return Err("--resolve only applies to --connections".to_string());
+    }
+
+    let mut entries: Vec<PortEntry> = match super::scan_all() {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("portkill list: failed to scan ports: {}", e);
+            return Ok(EXIT_FAILURE);
+        }
+    };
+
+    entries.sort_by(|a, b| {
+        a.port
+            .cmp(&b.port)
+            .then_with(|| a.local_addr.cmp(&b.local_addr))
+    });
+
+    if connections {
+        let groups: Vec<ListenerConnections> = ListenerConnections::group(&entries)
+            .into_iter()
+            .filter(|group| query.matches(group.listener))
+            .collect();
+        let resolver = resolve.then(|| {
+            let resolver = Resolver::spawn();
+            let peers = groups.iter().flat_map(|group| group.peers());
+            resolver.prefetch(
+                peers.filter_map(|peer| peer.name.parse().ok()),
+                RESOLVE_TIMEOUT,
+            );
+            resolver
+        });
+        let host_name = |peer: &str| {
+            let resolver = resolver.as_ref()?;
+            resolver.name(peer.parse().ok()?)
+        };
+
+        match format {
+            OutputFormat::Human => print_connections(&groups, host_name),
+            OutputFormat::Json => print_connections_json(&groups, host_name),
+        }
+        return Ok(EXIT_OK);
+    }
+
+    entries
+        .retain(|entry| (all_states || entry.state == SocketState::Listen) && query.matches(entry));
+
+    match format {
+        OutputFormat::Human => print_table(&entries, metrics),
+        OutputFormat::Json => println!("{}", serde_json::json!(entries)),
+    }
+
+    Ok(EXIT_OK)
+}
+
+fn print_table(entries: &[PortEntry], metrics: bool) {
+    let metric_header = if metrics {
+        format!(
+            "{:>8} {:>8} {:<24} {:>7} {:>6} ",
+            "SEND-Q", "RECV-Q", "TIMER", "RETRANS", "UID"
+        )
+    } else {
+        String::new()
+    };
+    println!(
+        "{:<6} {:<5} {:<12} {:<8} {:<20} {:<12} {:<16} {}ADDRESS",
+        "PORT", "PROTO", "STATE", "PID", "PROCESS", "USER", "PROJECT", metric_header
+    );
+
+    for entry in entries {
+        let pid = entry
+            .pid
+            .map(|pid| pid.to_string())
+            .unwrap_or_else(|| "-".to_string());
+
+        let project = entry
+            .project
+            .as_ref()
+            .map(|project| project.name.as_str())
+            .unwrap_or("-");
+
+        let metric_cells = if metrics {
+            metric_cells(entry)
+        } else {
+            String::new()
+        };
+
+        println!(
+            "{:<6} {:<5} {:<12} {:<8} {:<20} {:<12} {:<16} {}{}",
+            entry.port,
+            entry.protocol.to_string(),
+            entry.state.to_string(),
+            pid,
+            entry.process_name,
+            entry.user,
+            project,
+            metric_cells,
+            entry.local_addr
+        );
+
+        if metrics {
+            for issue in entry.health_issues() {
+                println!("       ! {}", issue);
+            }
+        }
+    }
+}
+
+fn metric_cells(entry: &PortEntry) -> String {
+    let Some(metrics) = &entry.metrics else {
+        return format!("{:>8} {:>8} {:<24} {:>7} {:>6} ", "-", "-", "-", "-", "-");
+    };
+    let timer = match metrics.timer {
+        TcpTimer::None => "-".to_string(),
+        timer => format!("{} {}ms", timer, metrics.timer_expires_ms),
+    };
+
+    format!(
+        "{:>8} {:>8} {:<24} {:>7} {:>6} ",
+        metrics.tx_queue, metrics.rx_queue, timer, metrics.retransmits, metrics.uid
+    )
+}
+
+fn print_connections<F>(groups: &[ListenerConnections], host_name: F)
+where
+    F: Fn(&str) -> Option<String>,
+{
+    for group in groups {
+        let process = match group.listener.pid {
+            Some(pid) => format!("{} ({})", group.listener.process_name, pid),
+            None => "-".to_string(),
+        };
+        println!(
+            "{:<6} {:<24} {:<20} {} connections",
+            group.listener.port,
+            group.listener.local_addr,
+            process,
+            group.connections.len()
+        );
+
+        for peer in group.peers() {
+            let name = match host_name(&peer.name) {
+                Some(host) => format!("{} ({})", host, peer.name),
+                None => peer.name,
+            };
+            println!("         <- {:<40} {}", name, peer.count);
+        }
+    }
+}
+
+fn print_connections_json<F>(groups: &[ListenerConnections], host_name: F)
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let report: Vec<serde_json::Value> = groups
+        .iter()
+        .map(|group| {
+            let peers: Vec<serde_json::Value> = group
+                .peers()
+                .iter()
+                .map(|peer| {
+                    serde_json::json!({
+                        "name": peer.name,
+                        "count": peer.count,
+                        "host_name": host_name(&peer.name),
+                    })
+                })
+                .collect();
+
+            serde_json::json!({
+                "listener": group.listener,
+                "connections": group.connections,
+                "peers": peers,
+            })
+        })
+        .collect();
+
+    println!("{}", serde_json::Value::Array(report));
+}
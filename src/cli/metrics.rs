@@ -0,0 +1,149 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use portkill::metrics::{write_textfile, MetricsServer, Scrape};
+use portkill::scanner::{PortEntry, Scanner};
+
+use super::args::{parse_duration, Args};
+use super::{EXIT_FAILURE, EXIT_OK};
+
+const USAGE: &str = "\
+Usage: portkill metrics [OPTIONS]
+
+Prints socket counts in the Prometheus text format: sockets by protocol and
+state, listeners by process and user, TIME_WAIT sockets, kills and scan
+duration.
+
+Options:
+  --listen <ADDR>         Serve http://ADDR/metrics, scanning on every scrape
+                          (e.g. 127.0.0.1:9469)
+  --textfile <PATH>       Write the metrics to PATH (a .prom file for
+                          node_exporter's textfile collector) after every scan
+  --interval <DURATION>   Scan interval with --textfile (default 15s)
+  --once                  With --textfile, write once and exit (for cron)
+
+Kills count only signals sent by this process; `portkill daemon --metrics`
+also counts the kills it performs for clients and rules.
+";
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(15);
+
+// What the scans so far add up to
+#[derive(Default)]
+struct Scans {
+    entries: Vec<PortEntry>,
+    scanned_at: Option<chrono::DateTime<Local>>,
+    duration: Option<Duration>,
+    count: u64,
+    errors: u64,
+}
+
+impl Scans {
+    fn scan(&mut self) -> Result<(), String> {
+        let started = Instant::now();
+        match Scanner::scan_all() {
+            Ok(entries) => {
+                self.duration = Some(started.elapsed());
+                self.entries = entries;
+                self.scanned_at = Some(Local::now());
+                self.count += 1;
+                Ok(())
+            }
+            Err(e) => {
+                self.errors += 1;
+                Err(format!("failed to scan ports: {}", e))
+            }
+        }
+    }
+
+    fn render(&self) -> String {
+        Scrape {
+            entries: &self.entries,
+            scanned_at: self.scanned_at,
+            scan_duration: self.duration,
+            scans: self.count,
+            scan_errors: self.errors,
+        }
+        .render()
+    }
+}
+
+pub fn run(mut args: Args) -> Result<i32, String> {
+    if args.flag("--help") {
+        print!("{}", USAGE);
+        return Ok(EXIT_OK);
+    }
+
+    let listen = args.value("--listen")?;
+    let textfile = args.value("--textfile")?.map(PathBuf::from);
+    let interval = args.value("--interval")?;
+    let once = args.flag("--once");
+    args.finish()?;
+
+    if listen.is_some() && textfile.is_some() {
+        return Err("--listen and --textfile can't be combined".to_string());
+    }
+    if (interval.is_some() || once) && textfile.is_none() {
+        return Err("--interval and --once only apply to --textfile".to_string());
+    }
+
+    let mut scans = Scans::default();
+
+    if let Some(address) = listen {
+        let server = match MetricsServer::bind(&address) {
+            Ok(server) => server,
+            Err(e) => {
+                eprintln!("portkill metrics: {}", e);
+                return Ok(EXIT_FAILURE);
+            }
+        };
+        eprintln!(
+            "Serving metrics at http://{}/metrics (Ctrl+C to stop)",
+            server.local_addr()
+        );
+
+        let scans = std::sync::Mutex::new(scans);
+        server.serve(|| {
+            let mut scans = scans.lock().unwrap_or_else(|e| e.into_inner());
+            if let Err(e) = scans.scan() {
+                log::warn!("{}", e);
+            }
+            scans.render()
+        });
+        return Ok(EXIT_OK);
+    }
+
+    let Some(path) = textfile else {
+        if let Err(e) = scans.scan() {
+            eprintln!("portkill metrics: {}", e);
+            return Ok(EXIT_FAILURE);
+        }
+        print!("{}", scans.render());
+        return Ok(EXIT_OK);
+    };
+
+    let interval = match interval {
+        Some(interval) => parse_duration(&interval)?,
+        None => DEFAULT_INTERVAL,
+    };
+    if interval.is_zero() {
+        return Err("--interval must be longer than zero".to_string());
+    }
+    loop {
+        if let Err(e) = scans.scan() {
+            eprintln!("portkill metrics: {}", e);
+            if once {
+                return Ok(EXIT_FAILURE);
+            }
+        }
+        if let Err(e) = write_textfile(&path, &scans.render()) {
+            eprintln!("portkill metrics: {}", e);
+            return Ok(EXIT_FAILURE);
+        }
+        if once {
+            return Ok(EXIT_OK);
+        }
+        std::thread::sleep(interval);
+    }
+}
@@ -0,0 +1,113 @@
+mod args;
+mod audit;
+mod check;
+mod daemon;
+mod free_port;
+mod history;
+mod list;
+mod metrics;
+mod rules;
+mod stats;
+mod wait;
+mod watch;
+
+use args::Args;
+use portkill::config::Config;
+use portkill::firewall::Firewall;
+use portkill::rpc::Client;
+use portkill::scanner::{PortEntry, Scanner};
+
+// Exit codes shared by all subcommands, chosen so commands chain with `&&`
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+// `check` found declared ports that are taken
+pub const EXIT_CONFLICT: i32 = 3;
+// `audit` reported findings
+pub const EXIT_FINDINGS: i32 = 3;
+// `stats` found ephemeral port usage over the threshold
+pub const EXIT_EXHAUSTED: i32 = 3;
+// Same as timeout(1)
+pub const EXIT_TIMEOUT: i32 = 124;
+
+const USAGE: &str = "\
+Usage: portkill [COMMAND] [OPTIONS]
+
+Without a command the GUI is started.
+
+Commands:
+  list         Print listening sockets and their processes
+  watch        Stream port open/close/state-change events
+  wait-listen  Block until ports are listening
+  wait-free    Block until ports are no longer in use
+  free-port    Find unused ports, optionally keeping them bound for a command
+  check        Report ports declared in project files that are already taken
+  audit        Flag listeners exposed to the network, owned by root, etc.
+  stats        Count sockets per state, remote and process; check ephemeral ports
+  history      Show what listened on which ports over time, or record it
+  daemon       Keep scanning in the background and serve other portkill instances
+  rules        Show the configured rules and what they would do now
+  metrics      Export socket counts for Prometheus
+  help         Show this message
+";
+
+// Runs a subcommand if one was given. Returns None when the GUI should start.
+pub fn run(args: Vec<String>) -> Option<i32> {
+    let mut args = args.into_iter();
+    let command = args.next()?;
+    let args = Args::new(args.collect());
+
+    for warning in &Config::global().warnings {
+        eprintln!("portkill: config: {}", warning);
+    }
+
+    let result = match command.as_str() {
+        "list" => list::run(args),
+        "watch" => watch::run(args),
+        "wait-listen" => wait::run_listen(args),
+        "wait-free" => wait::run_free(args),
+        "free-port" => free_port::run(args),
+        "check" => check::run(args),
+        "audit" => audit::run(args),
+        "stats" => stats::run(args),
+        "history" => history::run(args),
+        "daemon" => daemon::run(args),
+        "rules" => rules::run(args),
+        "metrics" => metrics::run(args),
+        "help" | "-h" | "--help" => {
+            print!("{}", USAGE);
+            Ok(EXIT_OK)
+        }
+        _ => Err(format!("unknown command '{}'", command)),
+    };
+
+    // Subcommands report runtime failures themselves and return EXIT_FAILURE;
+    // an Err is a usage error
+    Some(match result {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("portkill {}: {}", command, message);
+            eprintln!("Run `portkill help` for usage.");
+            EXIT_USAGE
+        }
+    })
+}
+
+// The running daemon's latest scan if there is one, otherwise a scan of our own
+pub fn scan_all() -> Result<Vec<PortEntry>, String> {
+    if let Some(mut client) = Client::connect() {
+        match client.list() {
+            Ok(entries) => return Ok(entries),
+            Err(e) => log::debug!("Scanning locally: {}", e),
+        }
+    }
+    Scanner::scan_all().map_err(|e| e.to_string())
+}
+
+// `scan_all` with firewall verdicts attached to listeners. Reading the ruleset
+// runs nft or iptables-save, so only commands that report verdicts use this.
+pub fn scan_with_firewall() -> Result<Vec<PortEntry>, String> {
+    let mut entries = scan_all()?;
+    Firewall::current().annotate(&mut entries);
+    Ok(entries)
+}
@@ -0,0 +1,93 @@
+use portkill::config::Config;
+use portkill::rules::{Outcome, Rule, RuleEngine};
+
+use super::args::{Args, OutputFormat};
+use super::{EXIT_FAILURE, EXIT_OK};
+
+const USAGE: &str = "\
+Usage: portkill rules [OPTIONS]
+
+Lists the [[rules]] from the config and what they would do against the
+current sockets, without doing it or writing the action log. `portkill daemon`
+and `portkill watch --rules` carry them out.
+
+Options:
+  --format <human|json>   Output format
+
+Sockets already open are taken to be as old as their process, as when the
+daemon starts.
+";
+
+pub fn run(mut args: Args) -> Result<i32, String> {
+    if args.flag("--help") {
+        print!("{}", USAGE);
+        return Ok(EXIT_OK);
+    }
+
+    let format = args.format()?;
+    args.finish()?;
+
+    let config = Config::global();
+    let entries = match super::scan_all() {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("portkill rules: failed to scan ports: {}", e);
+            return Ok(EXIT_FAILURE);
+        }
+    };
+    let outcomes = RuleEngine::new(config.rules.clone(), true, None).evaluate(&entries);
+
+    match format {
+        OutputFormat::Human => print_human(&config.rules, &outcomes),
+        OutputFormat::Json => {
+            let rules: Vec<serde_json::Value> = config
+                .rules
+                .iter()
+                .map(|rule| {
+                    serde_json::json!({
+                        "name": rule.name,
+                        "conditions": rule.conditions(),
+                        "action": rule.action.to_string(),
+                        "dry_run": rule.dry_run || config.actions_dry_run,
+                    })
+                })
+                .collect();
+            println!(
+                "{}",
+                serde_json::json!({ "rules": rules, "would_fire": outcomes })
+            );
+        }
+    }
+
+    Ok(EXIT_OK)
+}
+
+fn print_human(rules: &[Rule], outcomes: &[Outcome]) {
+    if rules.is_empty() {
+        println!("No [[rules]] in the config");
+        return;
+    }
+
+    let dry_run = Config::global().actions_dry_run;
+    for rule in rules {
+        println!(
+            "{}{}\n  when  {}\n  then  {}",
+            rule.name,
+            if rule.dry_run || dry_run {
+                " (dry run)"
+            } else {
+                ""
+            },
+            rule.conditions(),
+            rule.action
+        );
+    }
+
+    println!();
+    if outcomes.is_empty() {
+        println!("Nothing would fire now");
+    }
+    for outcome in outcomes {
+        println!("{}", outcome);
+    }
+}
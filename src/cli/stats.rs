@@ -0,0 +1,149 @@
+use portkill::config::Config;
+use portkill::stats::{Count, SocketStats};
+
+use super::args::{Args, OutputFormat};
+use super::{EXIT_EXHAUSTED, EXIT_FAILURE, EXIT_OK};
+
+const USAGE: &str = "\
+Usage: portkill stats [OPTIONS]
+
+Counts sockets per state, remote endpoint and process, compares outgoing
+connections against the ephemeral port range (ip_local_port_range) and shows
+listeners whose accept queue is filling up.
+
+Options:
+  --filter <QUERY>        Only count sockets matching a filter expression
+  --top <N>               Rows per table (default 10)
+  --warn-percent <P>      Warn when connections to one remote endpoint use P%
+                          of the ephemeral range (default from the config, 80)
+  --backlog-percent <P>   Warn when a listener's accept queue holds P% of its
+                          backlog (default from the config, 90)
+  --format <human|json>   Output format
+
+Exit codes: 0 below the threshold, 1 scan error, 2 usage error,
+3 ephemeral port usage or an accept queue at or above its threshold
+";
+
+const DEFAULT_TOP: usize = 10;
+
+pub fn run(mut args: Args) -> Result<i32, String> {
+    if args.flag("--help") {
+        print!("{}", USAGE);
+        return Ok(EXIT_OK);
+    }
+
+    let query = args.filter()?;
+    let top = match args.value("--top")? {
+        Some(top) => top
+            .parse::<usize>()
+            .map_err(|_| format!("invalid --top '{}'", top))?,
+        None => DEFAULT_TOP,
+    };
+    let warn_percent = percent_option(&mut args, "--warn-percent")?
+        .unwrap_or(Config::global().ephemeral_warn_percent);
+    let backlog_percent = percent_option(&mut args, "--backlog-percent")?
+        .unwrap_or(Config::global().backlog_warn_percent);
+    let format = args.format()?;
+    args.finish()?;
+
+    let mut entries = match super::scan_all() {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("portkill stats: failed to scan ports: {}", e);
+            return Ok(EXIT_FAILURE);
+        }
+    };
+    entries.retain(|entry| query.matches(entry));
+
+    let stats = SocketStats::collect(&entries);
+    let warning = stats.warning(warn_percent);
+    let backlog_warnings = stats.backlog_warnings(backlog_percent);
+
+    match format {
+        OutputFormat::Human => print_report(&stats, top),
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::json!({
+                "stats": stats,
+                "warning": warning,
+                "backlog_warnings": backlog_warnings,
+            })
+        ),
+    }
+
+    let warnings: Vec<&String> = warning.iter().chain(&backlog_warnings).collect();
+    for warning in &warnings {
+        eprintln!("portkill stats: warning: {}", warning);
+    }
+
+    if warnings.is_empty() {
+        Ok(EXIT_OK)
+    } else {
+        Ok(EXIT_EXHAUSTED)
+    }
+}
+
+fn percent_option(args: &mut Args, name: &str) -> Result<Option<f64>, String> {
+    match args.value(name)? {
+        Some(percent) => match percent.parse::<f64>() {
+            Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(Some(percent)),
+            _ => Err(format!("invalid {} '{}'", name, percent)),
+        },
+        None => Ok(None),
+    }
+}
+
+fn print_report(stats: &SocketStats, top: usize) {
+    let ephemeral = &stats.ephemeral;
+
+    println!("{} sockets", stats.total);
+    println!();
+    println!(
+        "Ephemeral ports ({}, {} ports)",
+        ephemeral.range, ephemeral.capacity
+    );
+    println!("  local ports in use    {}", ephemeral.ports_in_use);
+    println!("  in TIME_WAIT          {}", ephemeral.time_wait);
+    match &ephemeral.busiest_remote {
+        Some(remote) => println!(
+            "  busiest remote        {} ({} connections, {:.1}% of the range)",
+            remote.name, remote.count, ephemeral.percent
+        ),
+        None => println!("  busiest remote        -"),
+    }
+
+    if !stats.backlogs.is_empty() {
+        println!();
+        println!(
+            "{:<40} {:>8} {:>8} {:>6}",
+            "ACCEPT QUEUE", "QUEUED", "BACKLOG", "FILL"
+        );
+        for listener in stats.backlogs.iter().take(top) {
+            println!(
+                "{:<40} {:>8} {:>8} {:>5.0}%",
+                format!("{} on {}", listener.process, listener.local_addr),
+                listener.backlog.queued,
+                listener.backlog.limit,
+                listener.backlog.percent
+            );
+        }
+    }
+
+    print_counts("STATE", &stats.by_state, top);
+    print_counts("REMOTE", &stats.by_remote, top);
+    print_counts("PROCESS", &stats.by_process, top);
+}
+
+fn print_counts(heading: &str, counts: &[Count], top: usize) {
+    println!();
+    println!("{:<40} COUNT", heading);
+
+    for count in counts.iter().take(top) {
+        println!("{:<40} {}", count.name, count.count);
+    }
+
+    if counts.len() > top {
+        let rest: usize = counts[top..].iter().map(|count| count.count).sum();
+        println!("{:<40} {}", format!("({} more)", counts.len() - top), rest);
+    }
+}
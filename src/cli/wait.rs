@@ -0,0 +1,189 @@
+use std::time::{Duration, Instant};
+
+use portkill::scanner::{PortEntry, Scanner, SocketState};
+
+use super::args::{parse_duration, Args};
+use super::{EXIT_FAILURE, EXIT_OK, EXIT_TIMEOUT};
+
+const LISTEN_USAGE: &str = "\
+Usage: portkill wait-listen <PORT>... [OPTIONS]
+
+Blocks until every given port has a LISTEN socket.
+
+Options:
+  --timeout <DURATION>    Give up after this long (default: wait forever)
+  --interval <DURATION>   Poll interval (default 200ms)
+  --process <NAME>        Require the listener's process name to contain NAME
+  --quiet                 Print nothing; rely on the exit code
+
+Exit codes: 0 ready, 1 scan error, 2 usage error, 124 timed out
+";
+
+const FREE_USAGE: &str = "\
+Usage: portkill wait-free <PORT>... [OPTIONS]
+
+Blocks until no socket holds any of the given ports. TIME_WAIT sockets are
+ignored unless --strict is given, since servers using SO_REUSEADDR can bind
+over them.
+
+Options:
+  --timeout <DURATION>    Give up after this long (default: wait forever)
+  --interval <DURATION>   Poll interval (default 200ms)
+  --strict                Also wait for TIME_WAIT sockets to expire
+  --quiet                 Print nothing; rely on the exit code
+
+Exit codes: 0 free, 1 scan error, 2 usage error, 124 timed out
+";
+
+struct WaitOptions {
+    ports: Vec<u16>,
+    timeout: Option<Duration>,
+    interval: Duration,
+    quiet: bool,
+}
+
+impl WaitOptions {
+    fn parse(args: &mut Args) -> Result<Self, String> {
+        let timeout = args
+            .value("--timeout")?
+            .map(|timeout| parse_duration(&timeout))
+            .transpose()?;
+        let interval = match args.value("--interval")? {
+            Some(interval) => parse_duration(&interval)?,
+            None => Duration::from_millis(200),
+        };
+        if interval.is_zero() {
+            return Err("--interval must be longer than zero".to_string());
+        }
+        let quiet = args.flag("--quiet");
+
+        Ok(Self {
+            ports: Vec::new(),
+            timeout,
+            interval,
+            quiet,
+        })
+    }
+
+    fn take_ports(&mut self, mut args: Args) -> Result<(), String> {
+        while let Some(port) = args.positional() {
+            self.ports.push(
+                port.parse::<u16>()
+                    .map_err(|_| format!("invalid port '{}'", port))?,
+            );
+        }
+        args.finish()?;
+
+        if self.ports.is_empty() {
+            return Err("at least one port is required".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+pub fn run_listen(mut args: Args) -> Result<i32, String> {
+    if args.flag("--help") {
+        print!("{}", LISTEN_USAGE);
+        return Ok(EXIT_OK);
+    }
+
+    let mut options = WaitOptions::parse(&mut args)?;
+    let process = args.value("--process")?.map(|name| name.to_lowercase());
+    options.take_ports(args)?;
+
+    let is_ready = |entries: &[PortEntry]| {
+        options.ports.iter().all(|port| {
+            entries.iter().any(|entry| {
+                entry.port == *port
+                    && entry.state == SocketState::Listen
+                    && process
+                        .as_ref()
+                        .is_none_or(|name| entry.process_name.to_lowercase().contains(name))
+            })
+        })
+    };
+
+    wait_until(&options, process.is_some(), is_ready, "listening")
+}
+
+pub fn run_free(mut args: Args) -> Result<i32, String> {
+    if args.flag("--help") {
+        print!("{}", FREE_USAGE);
+        return Ok(EXIT_OK);
+    }
+
+    let mut options = WaitOptions::parse(&mut args)?;
+    let strict = args.flag("--strict");
+    options.take_ports(args)?;
+
+    let is_free = |entries: &[PortEntry]| {
+        !entries.iter().any(|entry| {
+            options.ports.contains(&entry.port) && (strict || entry.state != SocketState::TimeWait)
+        })
+    };
+
+    wait_until(&options, false, is_free, "free")
+}
+
+// Polls until `condition` holds for a scan. Process information is only
+// collected when the condition needs it, keeping each poll cheap.
+fn wait_until<F>(
+    options: &WaitOptions,
+    with_processes: bool,
+    condition: F,
+    what: &str,
+) -> Result<i32, String>
+where
+    F: Fn(&[PortEntry]) -> bool,
+{
+    let started = Instant::now();
+    let ports: Vec<String> = options.ports.iter().map(|port| port.to_string()).collect();
+    let ports = ports.join(", ");
+
+    loop {
+        let scan = if with_processes {
+            Scanner::scan_all()
+        } else {
+            Scanner::scan_sockets()
+        };
+
+        let entries = match scan {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("portkill: failed to scan ports: {}", e);
+                return Ok(EXIT_FAILURE);
+            }
+        };
+
+        if condition(&entries) {
+            if !options.quiet {
+                println!(
+                    "Port {} {} after {:.1}s",
+                    ports,
+                    what,
+                    started.elapsed().as_secs_f32()
+                );
+            }
+            return Ok(EXIT_OK);
+        }
+
+        // Check one last time right at the deadline rather than a whole interval past it
+        let mut pause = options.interval;
+        if let Some(timeout) = options.timeout {
+            let remaining = timeout.saturating_sub(started.elapsed());
+            if remaining.is_zero() {
+                if !options.quiet {
+                    eprintln!(
+                        "Timed out after {:?} waiting for port {} to be {}",
+                        timeout, ports, what
+                    );
+                }
+                return Ok(EXIT_TIMEOUT);
+            }
+            pause = pause.min(remaining);
+        }
+
+        std::thread::sleep(pause);
+    }
+}
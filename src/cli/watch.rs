@@ -0,0 +1,302 @@
+use std::io::Write;
+use std::time::Duration;
+
+use portkill::history::HistoryEvent;
+use portkill::ports::PortRange;
+use portkill::query::{Field, Query};
+use portkill::rpc::{Client, Event};
+use portkill::rules::RuleEngine;
+use portkill::scanner::{ChangeKind, EntryChange, PortEntry, Scanner, SnapshotDiff, SocketState};
+
+use super::args::{parse_duration, Args, OutputFormat};
+use super::{EXIT_FAILURE, EXIT_OK};
+
+const USAGE: &str = "\
+Usage: portkill watch [OPTIONS]
+
+Polls the socket table and prints an event whenever a socket opens, closes or
+changes state. Runs until interrupted.
+
+Options:
+  --interval <DURATION>   Poll interval (default 1s; e.g. 500ms, 2s)
+  --port <PORT|START-END> Only report these local ports
+  --process <NAME>        Only report processes whose name contains NAME
+  --filter <QUERY>        Only report sockets matching a filter expression,
+                          e.g. 'port:3000-3999 proc:~node'
+  --all                   Report every socket, not just LISTEN sockets
+  --initial               Report sockets that already exist at startup as opened
+  --rules                 Run the [[rules]] from the config after each scan and
+                          report what they do on stderr
+  --dry-run               With --rules, only log what the rules would do
+  --format <human|json>   Output format; json prints one object per line
+
+With a running `portkill daemon` the events come from its scans unless
+--interval or --rules is given.
+";
+
+struct WatchOptions {
+    interval: Duration,
+    ports: Option<PortRange>,
+    process: Option<String>,
+    query: Query,
+    all_states: bool,
+    format: OutputFormat,
+}
+
+impl WatchOptions {
+    fn matches(&self, entry: &PortEntry) -> bool {
+        let state_match = self.all_states
+            || self.query.constrains(Field::State)
+            || entry.state == SocketState::Listen;
+        let port_match = self.ports.is_none_or(|range| range.contains(entry.port));
+        let process_match = self.process.as_ref().is_none_or(|name| {
+            entry
+                .process_name
+                .to_lowercase()
+                .contains(&name.to_lowercase())
+        });
+
+        state_match && port_match && process_match && self.query.matches(entry)
+    }
+}
+
+pub fn run(mut args: Args) -> Result<i32, String> {
+    if args.flag("--help") {
+        print!("{}", USAGE);
+        return Ok(EXIT_OK);
+    }
+
+    let interval = args.value("--interval")?;
+    let options = WatchOptions {
+        interval: match &interval {
+            Some(interval) => parse_duration(interval)?,
+            None => Duration::from_secs(1),
+        },
+        ports: args
+            .value("--port")?
+            .map(|ports| PortRange::parse(&ports))
+            .transpose()?,
+        process: args.value("--process")?,
+        query: args.filter()?,
+        all_states: args.flag("--all"),
+        format: args.format()?,
+    };
+    let initial = args.flag("--initial");
+    let run_rules = args.flag("--rules");
+    let dry_run = args.flag("--dry-run");
+    args.finish()?;
+
+    if options.interval.is_zero() {
+        return Err("--interval must be longer than zero".to_string());
+    }
+    if dry_run && !run_rules {
+        return Err("--dry-run only applies to --rules".to_string());
+    }
+    let mut rules = run_rules.then(|| RuleEngine::from_config(dry_run));
+    if rules.as_ref().is_some_and(RuleEngine::is_empty) {
+        eprintln!("portkill watch: no [[rules]] in the config");
+    }
+
+    if interval.is_none() && rules.is_none() {
+        if let Some(client) = Client::connect() {
+            return watch_daemon(client, &options, initial);
+        }
+    }
+
+    let mut previous = match Scanner::scan_all() {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("portkill watch: failed to scan ports: {}", e);
+            return Ok(EXIT_FAILURE);
+        }
+    };
+
+    if options.format == OutputFormat::Human {
+        eprintln!(
+            "Watching {} sockets every {:?} (Ctrl+C to stop)",
+            if options.all_states {
+                "all"
+            } else {
+                "listening"
+            },
+            options.interval
+        );
+    }
+
+    if let Some(rules) = &mut rules {
+        for outcome in rules.evaluate(&previous) {
+            eprintln!("{}", outcome);
+        }
+    }
+
+    if initial && !emit(&options, &SnapshotDiff::between(&[], &previous)) {
+        return Ok(EXIT_OK);
+    }
+
+    loop {
+        std::thread::sleep(options.interval);
+
+        let current = match Scanner::scan_all() {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("Failed to scan ports: {}", e);
+                continue;
+            }
+        };
+
+        if let Some(rules) = &mut rules {
+            for outcome in rules.evaluate(&current) {
+                eprintln!("{}", outcome);
+            }
+        }
+
+        // Stop quietly once the reader of our output goes away (e.g. `| head`)
+        if !emit(&options, &SnapshotDiff::between(&previous, &current)) {
+            return Ok(EXIT_OK);
+        }
+        previous = current;
+    }
+}
+
+fn watch_daemon(mut client: Client, options: &WatchOptions, initial: bool) -> Result<i32, String> {
+    let existing = match initial.then(|| client.list()).transpose() {
+        Ok(existing) => existing,
+        Err(e) => {
+            eprintln!("portkill watch: {}", e);
+            return Ok(EXIT_FAILURE);
+        }
+    };
+    // Filtered here so --port and --process apply as well
+    let events = match client.subscribe(None, true) {
+        Ok(events) => events,
+        Err(e) => {
+            eprintln!("portkill watch: {}", e);
+            return Ok(EXIT_FAILURE);
+        }
+    };
+
+    if options.format == OutputFormat::Human {
+        eprintln!(
+            "Watching {} sockets through the daemon (Ctrl+C to stop)",
+            if options.all_states {
+                "all"
+            } else {
+                "listening"
+            }
+        );
+    }
+
+    if let Some(existing) = existing {
+        if !emit(options, &SnapshotDiff::between(&[], &existing)) {
+            return Ok(EXIT_OK);
+        }
+    }
+
+    for event in events {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("portkill watch: {}", e);
+                return Ok(EXIT_FAILURE);
+            }
+        };
+        let diff = SnapshotDiff {
+            changes: vec![change_of(event)],
+        };
+        if !emit(options, &diff) {
+            return Ok(EXIT_OK);
+        }
+    }
+
+    eprintln!("portkill watch: the daemon stopped");
+    Ok(EXIT_FAILURE)
+}
+
+fn change_of(event: Event) -> EntryChange {
+    EntryChange {
+        kind: match event.event {
+            HistoryEvent::Open => ChangeKind::New,
+            HistoryEvent::Close => ChangeKind::Gone,
+            HistoryEvent::Change => ChangeKind::Changed,
+        },
+        entry: event.entry,
+        previous: event.previous,
+    }
+}
+
+// Returns false when stdout has been closed
+fn emit(options: &WatchOptions, diff: &SnapshotDiff) -> bool {
+    let mut out = std::io::stdout().lock();
+
+    for change in &diff.changes {
+        if !options.matches(&change.entry) {
+            continue;
+        }
+
+        let line = match options.format {
+            OutputFormat::Human => format_human(change),
+            OutputFormat::Json => format_json(change),
+        };
+
+        if writeln!(out, "{}", line).is_err() {
+            return false;
+        }
+    }
+
+    out.flush().is_ok()
+}
+
+fn event_name(kind: ChangeKind) -> &'static str {
+    match kind {
+        ChangeKind::New => "open",
+        ChangeKind::Gone => "close",
+        ChangeKind::Changed => "change",
+    }
+}
+
+fn format_human(change: &EntryChange) -> String {
+    let entry = &change.entry;
+    let time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+
+    let state = match &change.previous {
+        Some(previous) if previous.state != entry.state => {
+            format!("{} -> {}", previous.state, entry.state)
+        }
+        _ => entry.state.to_string(),
+    };
+
+    let owner = match entry.pid {
+        Some(pid) => format!("{} (PID {}, {})", entry.process_name, pid, entry.user),
+        None => "-".to_string(),
+    };
+
+    let mut line = format!(
+        "{} {:<6} {:<4} {:<22} {:<24} {}",
+        time,
+        event_name(change.kind).to_uppercase(),
+        entry.protocol,
+        entry.local_addr,
+        state,
+        owner
+    );
+
+    if entry.state != SocketState::Listen && !entry.remote_addr.is_empty() {
+        line.push_str(&format!(" <-> {}", entry.remote_addr));
+    }
+
+    line
+}
+
+fn format_json(change: &EntryChange) -> String {
+    let mut event = serde_json::json!({
+        "time": chrono::Local::now().to_rfc3339(),
+        "event": event_name(change.kind),
+        "entry": change.entry,
+    });
+
+    if let Some(previous) = &change.previous {
+        event["previous"] = serde_json::json!(previous);
+    }
+
+    event.to_string()
+}
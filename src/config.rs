@@ -0,0 +1,676 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use nix::sys::signal::Signal;
+use regex_lite::Regex;
+use serde::Deserialize;
+
+use crate::ports::PortRange;
+use crate::query::Query;
+use crate::rules::{Action, Rule, TimeWindow};
+
+// Team-wide conventions shared by the GUI and CLI, read from
+// /etc/portkill.toml and then ~/.config/portkill/config.toml:
+//
+//   [labels]
+//   3000 = "Frontend (vite)"
+//   "8000-8099" = "Review apps"
+//
+//   [protect]
+//   processes = ["sshd", "~^postgres"]
+//   users = ["root"]
+//
+//   [kill]
+//   signal = "SIGTERM"
+//   grace_period = "5s"
+//
+//   [defaults]
+//   filter = "!user:root"
+//   format = "json"
+//
+//   [stats]
+//   ephemeral_warn_percent = 80
+//   backlog_warn_percent = 90
+//
+//   [history]
+//   record = true
+//   path = "/var/lib/portkill/history.ndjson"
+//   retention = "7d"
+//   max_size_mb = 50
+//
+//   [daemon]
+//   socket = "/run/portkill/daemon.sock"
+//   scan_interval = "2s"
+//   metrics = "127.0.0.1:9469"
+//
+//   [[rules]]
+//   name = "stale vite"
+//   filter = "port:5173 cmd:~node.*vite"
+//   listening_for = "8h"
+//   max_connections = 0
+//   action = "signal"
+//
+//   [actions]
+//   dry_run = true
+//   log = "/var/log/portkill/actions.ndjson"
+//
+// The user file overrides the system file's labels and scalar settings;
+// protection rules and [[rules]] from both files apply.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub labels: Vec<(PortRange, String)>,
+    pub protected_processes: Vec<ProcessRule>,
+    pub protected_users: Vec<String>,
+    pub kill_signal: Signal,
+    // After sending `kill_signal`, SIGKILL the process if it is still running
+    // this much later. Zero disables the escalation.
+    pub grace_period: Duration,
+    pub default_filter: Option<String>,
+    pub default_format: Option<String>,
+    // Warn once outgoing connections use this much of the ephemeral port range
+    pub ephemeral_warn_percent: f64,
+    // Warn when a listener's accept queue fills up this much of its backlog
+    pub backlog_warn_percent: f64,
+    // Record listener changes while the GUI runs
+    pub history_record: bool,
+    // None for the default under $XDG_STATE_HOME
+    pub history_path: Option<PathBuf>,
+    pub history_retention: Duration,
+    pub history_max_bytes: u64,
+    // None for the default under $XDG_RUNTIME_DIR
+    pub daemon_socket: Option<PathBuf>,
+    pub daemon_scan_interval: Duration,
+    // Where the daemon serves Prometheus metrics; None for nowhere
+    pub daemon_metrics: Option<String>,
+    pub rules: Vec<Rule>,
+    // Log what rules would do instead of doing it
+    pub actions_dry_run: bool,
+    // None for the default under $XDG_STATE_HOME
+    pub actions_log: Option<PathBuf>,
+    // Problems found while loading, reported once by each front end
+    pub warnings: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub enum ProcessRule {
+    Name(String),
+    Pattern(Regex),
+}
+
+const SYSTEM_CONFIG: &str = "/etc/portkill.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    labels: BTreeMap<String, String>,
+    protect: ProtectSection,
+    kill: KillSection,
+    defaults: DefaultsSection,
+    stats: StatsSection,
+    history: HistorySection,
+    daemon: DaemonSection,
+    rules: Vec<RuleSection>,
+    actions: ActionsSection,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ProtectSection {
+    processes: Vec<String>,
+    users: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct KillSection {
+    signal: Option<String>,
+    grace_period: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DefaultsSection {
+    filter: Option<String>,
+    format: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct StatsSection {
+    ephemeral_warn_percent: Option<f64>,
+    backlog_warn_percent: Option<f64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct HistorySection {
+    record: Option<bool>,
+    path: Option<PathBuf>,
+    retention: Option<String>,
+    max_size_mb: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DaemonSection {
+    socket: Option<PathBuf>,
+    scan_interval: Option<String>,
+    metrics: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RuleSection {
+    name: Option<String>,
+    filter: Option<String>,
+    listening_for: Option<String>,
+    process_age: Option<String>,
+    min_connections: Option<usize>,
+    max_connections: Option<usize>,
+    hours: Option<String>,
+    action: Option<String>,
+    signal: Option<String>,
+    unit: Option<String>,
+    dry_run: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ActionsSection {
+    dry_run: Option<bool>,
+    log: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            labels: Vec::new(),
+            protected_processes: Vec::new(),
+            protected_users: Vec::new(),
+            kill_signal: Signal::SIGTERM,
+            grace_period: Duration::ZERO,
+            default_filter: None,
+            default_format: None,
+            ephemeral_warn_percent: 80.0,
+            backlog_warn_percent: 90.0,
+            history_record: false,
+            history_path: None,
+            history_retention: Duration::from_secs(7 * 86400),
+            history_max_bytes: 50 * 1024 * 1024,
+            daemon_socket: None,
+            daemon_scan_interval: Duration::from_secs(2),
+            daemon_metrics: None,
+            rules: Vec::new(),
+            actions_dry_run: false,
+            actions_log: None,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    // Loaded on first use; a broken file is reported in `warnings` and skipped
+    pub fn global() -> &'static Config {
+        static CONFIG: OnceLock<Config> = OnceLock::new();
+        CONFIG.get_or_init(Self::load)
+    }
+
+    pub fn load() -> Self {
+        let mut config = Self::default();
+
+        let paths = std::iter::once(PathBuf::from(SYSTEM_CONFIG)).chain(Self::user_path());
+        for path in paths {
+            if let Err(e) = config.merge_file(&path) {
+                log::warn!("{}", e);
+                config.warnings.push(e);
+            }
+        }
+
+        config
+    }
+
+    // $XDG_CONFIG_HOME/portkill/config.toml, defaulting to ~/.config
+    pub fn user_path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config_home.join("portkill").join("config.toml"))
+    }
+
+    pub fn label_for(&self, port: u16) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(range, _)| range.contains(port))
+            .map(|(_, label)| label.as_str())
+    }
+
+    pub fn is_protected(&self, process_name: &str, user: &str) -> bool {
+        let process_match = self.protected_processes.iter().any(|rule| match rule {
+            ProcessRule::Name(name) => name == process_name,
+            ProcessRule::Pattern(regex) => regex.is_match(process_name),
+        });
+
+        process_match || (!user.is_empty() && self.protected_users.iter().any(|name| name == user))
+    }
+
+    fn merge_file(&mut self, path: &Path) -> Result<(), String> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+
+        let file: ConfigFile = toml::from_str(&contents)
+            .map_err(|e| format!("{}: {}", path.display(), e.to_string().trim_end()))?;
+        self.merge(file)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn merge(&mut self, file: ConfigFile) -> Result<(), String> {
+        // Validate everything before changing anything, so a bad file is skipped whole
+        let mut labels = Vec::new();
+        for (ports, label) in file.labels {
+            labels.push((
+                PortRange::parse(&ports).map_err(|e| format!("[labels] {}", e))?,
+                label,
+            ));
+        }
+        // Single ports take precedence over ranges containing them
+        labels.sort_by_key(|(range, _)| range.end - range.start);
+
+        let mut processes = Vec::new();
+        for rule in file.protect.processes {
+            processes.push(match rule.strip_prefix('~') {
+                Some(pattern) => ProcessRule::Pattern(
+                    Regex::new(pattern)
+                        .map_err(|e| format!("[protect] invalid pattern '{}': {}", pattern, e))?,
+                ),
+                None => ProcessRule::Name(rule),
+            });
+        }
+
+        let signal = file
+            .kill
+            .signal
+            .map(|signal| parse_signal(&signal).map_err(|e| format!("[kill] {}", e)))
+            .transpose()?;
+        let grace_period = file
+            .kill
+            .grace_period
+            .map(|period| parse_duration(&period).map_err(|e| format!("[kill] {}", e)))
+            .transpose()?;
+
+        let retention = file
+            .history
+            .retention
+            .map(|retention| parse_duration(&retention).map_err(|e| format!("[history] {}", e)))
+            .transpose()?;
+        if retention.is_some_and(|retention| retention.is_zero()) {
+            return Err("[history] retention must be longer than zero".to_string());
+        }
+        if file.history.max_size_mb == Some(0) {
+            return Err("[history] max_size_mb must be at least 1".to_string());
+        }
+        let scan_interval = file
+            .daemon
+            .scan_interval
+            .map(|interval| parse_duration(&interval).map_err(|e| format!("[daemon] {}", e)))
+            .transpose()?;
+        if scan_interval.is_some_and(|interval| interval.is_zero()) {
+            return Err("[daemon] scan_interval must be longer than zero".to_string());
+        }
+
+        let mut rules = Vec::new();
+        for rule in file.rules {
+            rules.push(Self::rule(rule)?);
+        }
+
+        for (key, percent) in [
+            ("ephemeral_warn_percent", file.stats.ephemeral_warn_percent),
+            ("backlog_warn_percent", file.stats.backlog_warn_percent),
+        ] {
+            if let Some(percent) = percent.filter(|percent| !(0.0..=100.0).contains(percent)) {
+                return Err(format!(
+                    "[stats] {} must be between 0 and 100, got {}",
+                    key, percent
+                ));
+            }
+        }
+
+        // Later files win, so their labels are looked up first
+        labels.append(&mut self.labels);
+        self.labels = labels;
+        self.protected_processes.append(&mut processes);
+        self.protected_users.extend(file.protect.users);
+        if let Some(signal) = signal {
+            self.kill_signal = signal;
+        }
+        if let Some(grace_period) = grace_period {
+            self.grace_period = grace_period;
+        }
+        if file.defaults.filter.is_some() {
+            self.default_filter = file.defaults.filter;
+        }
+        if file.defaults.format.is_some() {
+            self.default_format = file.defaults.format;
+        }
+        if let Some(percent) = file.stats.ephemeral_warn_percent {
+            self.ephemeral_warn_percent = percent;
+        }
+        if let Some(percent) = file.stats.backlog_warn_percent {
+            self.backlog_warn_percent = percent;
+        }
+        if let Some(record) = file.history.record {
+            self.history_record = record;
+        }
+        if file.history.path.is_some() {
+            self.history_path = file.history.path;
+        }
+        if let Some(retention) = retention {
+            self.history_retention = retention;
+        }
+        if let Some(max_size_mb) = file.history.max_size_mb {
+            self.history_max_bytes = max_size_mb * 1024 * 1024;
+        }
+        if file.daemon.socket.is_some() {
+            self.daemon_socket = file.daemon.socket;
+        }
+        if let Some(scan_interval) = scan_interval {
+            self.daemon_scan_interval = scan_interval;
+        }
+        if file.daemon.metrics.is_some() {
+            self.daemon_metrics = file.daemon.metrics;
+        }
+        self.rules.append(&mut rules);
+        if let Some(dry_run) = file.actions.dry_run {
+            self.actions_dry_run = dry_run;
+        }
+        if file.actions.log.is_some() {
+            self.actions_log = file.actions.log;
+        }
+
+        Ok(())
+    }
+
+    fn rule(section: RuleSection) -> Result<Rule, String> {
+        let name = section
+            .name
+            .filter(|name| !name.trim().is_empty())
+            .ok_or("[[rules]] every rule needs a name")?;
+        let context = |e: String| format!("[[rules]] '{}': {}", name, e);
+        let duration = |text: Option<String>| {
+            text.map(|text| parse_duration(&text).map_err(context))
+                .transpose()
+        };
+
+        let filter = section.filter.unwrap_or_default();
+        let query = Query::parse(&filter).map_err(context)?;
+        let listening_for = duration(section.listening_for)?;
+        let process_age = duration(section.process_age)?;
+        if let (Some(min), Some(max)) = (section.min_connections, section.max_connections) {
+            if min > max {
+                return Err(context(format!(
+                    "min_connections {} is above max_connections {}",
+                    min, max
+                )));
+            }
+        }
+        let hours = section
+            .hours
+            .map(|hours| TimeWindow::parse(&hours).map_err(context))
+            .transpose()?;
+        let signal = section
+            .signal
+            .map(|signal| parse_signal(&signal).map_err(context))
+            .transpose()?;
+        let action = section
+            .action
+            .ok_or_else(|| context("missing action (notify, signal or stop-unit)".to_string()))?;
+        let action = Action::parse(&action, signal, section.unit).map_err(context)?;
+
+        Ok(Rule {
+            name,
+            filter,
+            query,
+            listening_for,
+            process_age,
+            min_connections: section.min_connections,
+            max_connections: section.max_connections,
+            hours,
+            action,
+            dry_run: section.dry_run,
+        })
+    }
+}
+
+// "SIGTERM", "TERM", "term" or a signal number such as "15"
+pub fn parse_signal(text: &str) -> Result<Signal, String> {
+    let text = text.trim();
+
+    if let Ok(number) = text.parse::<i32>() {
+        return Signal::try_from(number).map_err(|_| format!("invalid signal '{}'", text));
+    }
+
+    let name = text.to_uppercase();
+    let name = if name.starts_with("SIG") {
+        name
+    } else {
+        format!("SIG{}", name)
+    };
+
+    name.parse::<Signal>()
+        .map_err(|_| format!("invalid signal '{}'", text))
+}
+
+// Accepts "500ms", "30s", "5m", "2h", "1d"; a bare number means seconds
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", text))?;
+
+    let seconds = match unit {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        "d" => value * 86400.0,
+        _ => return Err(format!("invalid duration unit in '{}'", text)),
+    };
+
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("duration '{}' is too long", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Layers the files over the defaults like `load` does with the system and
+    // user file
+    fn layered(files: &[&str]) -> Config {
+        let mut config = Config::default();
+        for (index, contents) in files.iter().enumerate() {
+            let path = std::env::temp_dir().join(format!(
+                "portkill-config-test-{}-{:?}-{}.toml",
+                std::process::id(),
+                std::thread::current().id(),
+                index
+            ));
+            std::fs::write(&path, contents).unwrap();
+            let result = config.merge_file(&path);
+            std::fs::remove_file(&path).unwrap();
+            if let Err(e) = result {
+                config.warnings.push(e);
+            }
+        }
+        config
+    }
+
+    const SYSTEM: &str = r#"
+[labels]
+3000 = "Frontend"
+"8000-8099" = "Review apps"
+
+[protect]
+processes = ["sshd"]
+users = ["root"]
+
+[kill]
+signal = "SIGTERM"
+grace_period = "5s"
+
+[defaults]
+filter = "!user:root"
+
+[history]
+record = true
+retention = "30d"
+
+[[rules]]
+name = "idle"
+action = "notify"
+"#;
+
+    const USER: &str = r#"
+[labels]
+3000 = "My app"
+8080 = "Preview"
+
+[protect]
+processes = ["~^postgres"]
+
+[kill]
+grace_period = "10s"
+
+[history]
+max_size_mb = 5
+
+[[rules]]
+name = "night"
+hours = "22-6"
+action = "signal"
+signal = "INT"
+"#;
+
+    #[test]
+    fn later_files_override_scalars() {
+        let config = layered(&[SYSTEM, USER]);
+        assert!(config.warnings.is_empty(), "{:?}", config.warnings);
+
+        assert_eq!(config.kill_signal, Signal::SIGTERM);
+        assert_eq!(config.grace_period, Duration::from_secs(10));
+        assert_eq!(config.default_filter.as_deref(), Some("!user:root"));
+        assert!(config.history_record);
+        assert_eq!(config.history_retention, Duration::from_secs(30 * 86400));
+        assert_eq!(config.history_max_bytes, 5 * 1024 * 1024);
+        // Untouched by either file
+        assert_eq!(config.daemon_scan_interval, Duration::from_secs(2));
+    }
+
+    #[test]
+    fn later_files_win_labels() {
+        let config = layered(&[SYSTEM, USER]);
+        assert_eq!(config.label_for(3000), Some("My app"));
+        assert_eq!(config.label_for(8080), Some("Preview"));
+        assert_eq!(config.label_for(8081), Some("Review apps"));
+        assert_eq!(config.label_for(22), None);
+    }
+
+    #[test]
+    fn protection_and_rules_accumulate() {
+        let config = layered(&[SYSTEM, USER]);
+        assert!(config.is_protected("sshd", "dev"));
+        assert!(config.is_protected("postgres: writer", "dev"));
+        assert!(config.is_protected("node", "root"));
+        assert!(!config.is_protected("node", "dev"));
+        assert!(!config.is_protected("node", ""));
+
+        let names: Vec<&str> = config.rules.iter().map(|rule| rule.name.as_str()).collect();
+        assert_eq!(names, ["idle", "night"]);
+        assert_eq!(config.rules[1].action, Action::Signal(Some(Signal::SIGINT)));
+        assert_eq!(
+            config.rules[1].hours,
+            Some(TimeWindow::parse("22:00-06:00").unwrap())
+        );
+    }
+
+    #[test]
+    fn broken_file_is_skipped_whole() {
+        let broken = r#"
+[kill]
+grace_period = "10s"
+
+[labels]
+"9000-8000" = "Backwards"
+"#;
+        let config = layered(&[SYSTEM, broken]);
+        assert_eq!(config.warnings.len(), 1);
+        assert!(config.warnings[0].contains("[labels] invalid port range '9000-8000'"));
+        assert_eq!(config.grace_period, Duration::from_secs(5));
+
+        for (contents, error) in [
+            ("[kill]\ngrace = \"1s\"\n", "unknown field `grace`"),
+            ("[kill]\ngrace_period = \"soon\"\n", "[kill] invalid duration"),
+            ("[stats]\nephemeral_warn_percent = -1.0\n", "must be between 0 and 100"),
+            ("[stats]\nbacklog_warn_percent = 120.0\n", "must be between 0 and 100"),
+            ("[history]\nretention = \"0s\"\n", "retention must be longer than zero"),
+            ("[daemon]\nscan_interval = \"0s\"\n", "scan_interval must be longer than zero"),
+            ("[[rules]]\naction = \"notify\"\n", "every rule needs a name"),
+            ("[[rules]]\nname = \"x\"\n", "missing action"),
+            (
+                "[[rules]]\nname = \"x\"\naction = \"notify\"\nsignal = \"KILL\"\n",
+                "'signal' only applies",
+            ),
+            (
+                "[[rules]]\nname = \"x\"\naction = \"notify\"\nmin_connections = 3\nmax_connections = 1\n",
+                "min_connections 3 is above max_connections 1",
+            ),
+        ] {
+            let config = layered(&[contents]);
+            assert_eq!(config.warnings.len(), 1, "{}", contents);
+            assert!(config.warnings[0].contains(error), "{}", config.warnings[0]);
+        }
+    }
+
+    #[test]
+    fn missing_file_is_fine() {
+        let mut config = Config::default();
+        assert!(config
+            .merge_file(Path::new("/nonexistent/portkill.toml"))
+            .is_ok());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration(" 1.5m "), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86400)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("-1s").is_err());
+        assert!(parse_duration("5w").is_err());
+        assert_eq!(
+            parse_duration("99999999999999999999999h"),
+            Err("duration '99999999999999999999999h' is too long".to_string())
+        );
+    }
+
+    #[test]
+    fn signals() {
+        for text in ["SIGINT", "INT", "int", "2"] {
+            assert_eq!(parse_signal(text), Ok(Signal::SIGINT), "{}", text);
+        }
+        assert!(parse_signal("SIGNOPE").is_err());
+        assert!(parse_signal("999").is_err());
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+
+use crate::scanner::{PortEntry, SocketState};
+use crate::stats::Count;
+
+// Inbound connections grouped under the listening socket that accepted them:
+// same protocol and local port, and the listener's address (or any address
+// when it listens on the wildcard).
+#[derive(Clone, Debug)]
+pub struct ListenerConnections<'a> {
+    pub listener: &'a PortEntry,
+    pub connections: Vec<&'a PortEntry>,
+}
+
+impl<'a> ListenerConnections<'a> {
+    // One group per listener, in the order of `entries`. Connections nothing
+    // listens for (outgoing ones) are left out.
+    pub fn group(entries: &'a [PortEntry]) -> Vec<Self> {
+        let mut groups: Vec<Self> = entries
+            .iter()
+            .filter(|entry| entry.state == SocketState::Listen)
+            .map(|listener| Self {
+                listener,
+                connections: Vec::new(),
+            })
+            .collect();
+
+        // Listeners by local port, so each connection only looks at its own
+        let mut by_port: HashMap<u16, Vec<usize>> = HashMap::new();
+        for (index, group) in groups.iter().enumerate() {
+            by_port.entry(group.listener.port).or_default().push(index);
+        }
+
+        for connection in entries
+            .iter()
+            .filter(|entry| entry.state != SocketState::Listen)
+        {
+            let Some(candidates) = by_port.get(&connection.port) else {
+                continue;
+            };
+            // A listener on the connection's exact address beats a wildcard one
+            let best = candidates
+                .iter()
+                .copied()
+                .filter(|&index| Self::accepts(groups[index].listener, connection))
+                .max_by_key(|&index| !Self::is_wildcard(&groups[index].listener.local_addr));
+            if let Some(index) = best {
+                groups[index].connections.push(connection);
+            }
+        }
+
+        groups
+    }
+
+    pub fn accepts(listener: &PortEntry, connection: &PortEntry) -> bool {
+        if listener.state != SocketState::Listen
+            || connection.state == SocketState::Listen
+            || listener.port != connection.port
+            || listener.protocol != connection.protocol
+        {
+            return false;
+        }
+
+        Self::is_wildcard(&listener.local_addr)
+            || Self::host(&listener.local_addr) == Self::host(&connection.local_addr)
+    }
+
+    // Remote hosts with their number of connections, most connected first
+    pub fn peers(&self) -> Vec<Count> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for connection in &self.connections {
+            *counts
+                .entry(Self::host(&connection.remote_addr))
+                .or_insert(0) += 1;
+        }
+
+        let mut peers: Vec<Count> = counts
+            .into_iter()
+            .map(|(name, count)| Count { name, count })
+            .collect();
+        peers.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        peers
+    }
+
+    // "10.0.0.5 (2), 10.0.0.7"
+    pub fn peers_label(&self) -> String {
+        self.peers()
+            .iter()
+            .map(|peer| match peer.count {
+                1 => peer.name.clone(),
+                count => format!("{} ({})", peer.name, count),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn is_wildcard(local_addr: &str) -> bool {
+        match local_addr.parse::<SocketAddr>() {
+            Ok(addr) => addr.ip().is_unspecified(),
+            // lsof's "*:5432"
+            Err(_) => local_addr.starts_with('*'),
+        }
+    }
+
+    // The address without its port; IPv4 peers of IPv6 sockets lose their
+    // ::ffff: prefix
+    fn host(addr: &str) -> String {
+        match addr.parse::<SocketAddr>() {
+            Ok(addr) => match addr.ip() {
+                IpAddr::V6(ip) => ip.to_canonical().to_string(),
+                ip => ip.to_string(),
+            },
+            Err(_) => addr
+                .rsplit_once(':')
+                .map_or(addr, |(host, _)| host)
+                .to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listener(local_addr: &str) -> PortEntry {
+        let port = local_addr.parse::<SocketAddr>().unwrap().port();
+        PortEntry {
+            local_addr: local_addr.to_string(),
+            ..PortEntry::listener(port, "postgres")
+        }
+    }
+
+    fn connection(local_addr: &str, remote_addr: &str) -> PortEntry {
+        PortEntry {
+            state: SocketState::Established,
+            remote_addr: remote_addr.to_string(),
+            ..listener(local_addr)
+        }
+    }
+
+    fn grouped<'a>(groups: &[ListenerConnections<'a>]) -> Vec<(&'a str, Vec<&'a str>)> {
+        groups
+            .iter()
+            .map(|group| {
+                (
+                    group.listener.local_addr.as_str(),
+                    group
+                        .connections
+                        .iter()
+                        .map(|connection| connection.remote_addr.as_str())
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ipv4_wildcard_takes_what_no_specific_listener_does() {
+        let entries = vec![
+            listener("0.0.0.0:5432"),
+            listener("127.0.0.1:5432"),
+            connection("127.0.0.1:5432", "127.0.0.1:50000"),
+            connection("10.0.0.2:5432", "10.0.0.5:50001"),
+            // Outgoing, from a port nothing listens on
+            connection("10.0.0.2:40000", "10.0.0.9:5432"),
+        ];
+
+        let groups = ListenerConnections::group(&entries);
+
+        assert_eq!(
+            grouped(&groups),
+            vec![
+                ("0.0.0.0:5432", vec!["10.0.0.5:50001"]),
+                ("127.0.0.1:5432", vec!["127.0.0.1:50000"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn ipv6_wildcard_takes_mapped_ipv4_connections() {
+        let entries = vec![
+            listener("[::]:8080"),
+            listener("[::1]:8080"),
+            listener("127.0.0.1:3000"),
+            connection("[::ffff:10.0.0.2]:8080", "[::ffff:10.0.0.5]:50000"),
+            connection("[::1]:8080", "[::1]:50001"),
+            connection("[2001:db8::2]:8080", "[2001:db8::5]:50002"),
+            // An IPv6 socket's mapped address still matches the IPv4 listener
+            connection("[::ffff:127.0.0.1]:3000", "[::ffff:127.0.0.1]:50003"),
+        ];
+
+        let groups = ListenerConnections::group(&entries);
+
+        assert_eq!(
+            grouped(&groups),
+            vec![
+                (
+                    "[::]:8080",
+                    vec!["[::ffff:10.0.0.5]:50000", "[2001:db8::5]:50002"]
+                ),
+                ("[::1]:8080", vec!["[::1]:50001"]),
+                ("127.0.0.1:3000", vec!["[::ffff:127.0.0.1]:50003"]),
+            ]
+        );
+        assert_eq!(groups[0].peers_label(), "10.0.0.5, 2001:db8::5");
+    }
+
+    #[test]
+    fn peers_are_counted_by_host() {
+        let entries = vec![
+            listener("0.0.0.0:5432"),
+            connection("10.0.0.2:5432", "10.0.0.7:50000"),
+            connection("10.0.0.2:5432", "10.0.0.5:50001"),
+            connection("10.0.0.2:5432", "10.0.0.5:50002"),
+        ];
+
+        let groups = ListenerConnections::group(&entries);
+
+        assert_eq!(groups[0].peers_label(), "10.0.0.5 (2), 10.0.0.7");
+    }
+}
@@ -0,0 +1,600 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use nix::sys::stat::{umask, Mode};
+use serde_json::{json, Value};
+
+use crate::config::parse_signal;
+use crate::history::{HistoryEvent, Recorder};
+use crate::metrics::{MetricsServer, Scrape};
+use crate::query::{Field, Query};
+use crate::rpc::{self, Event, Request, Status};
+use crate::rules::{Outcome, RuleEngine};
+use crate::scanner::{ChangeKind, Killer, PortEntry, Scanner, SnapshotDiff, SocketState};
+
+// `portkill daemon`: scans continuously, records history and serves the
+// latest snapshot, change events and kills over the socket in `rpc`
+pub struct Daemon {
+    listener: UnixListener,
+    metrics: Option<MetricsServer>,
+}
+
+struct Shared {
+    snapshot: RwLock<Snapshot>,
+    subscribers: Mutex<Vec<Subscriber>>,
+    recording: bool,
+    rules: usize,
+}
+
+#[derive(Default)]
+struct Snapshot {
+    entries: Vec<PortEntry>,
+    scanned_at: Option<DateTime<Local>>,
+    scan_duration: Option<Duration>,
+    scans: u64,
+    scan_errors: u64,
+}
+
+struct Subscriber {
+    query: Query,
+    all_states: bool,
+    events: Sender<Value>,
+}
+
+impl Subscriber {
+    // LISTEN sockets only unless asked for all, as with `portkill watch`
+    fn wants(&self, entry: &PortEntry) -> bool {
+        let state_match = self.all_states
+            || self.query.constrains(Field::State)
+            || entry.state == SocketState::Listen;
+        state_match && self.query.matches(entry)
+    }
+}
+
+// (code, message) of a JSON-RPC error
+type Failure = (i64, String);
+
+impl Daemon {
+    // Only the owner may connect, so clients act with the daemon's own
+    // permissions. A socket left behind by a daemon that died is replaced,
+    // anything else at the path is left alone.
+    pub fn bind(path: &Path) -> Result<Self, String> {
+        let describe = |e: std::io::Error| format!("{}: {}", path.display(), e);
+
+        match std::fs::symlink_metadata(path) {
+            Ok(metadata) if !metadata.file_type().is_socket() => {
+                return Err(format!(
+                    "{} exists and is not a socket; refusing to replace it",
+                    path.display()
+                ))
+            }
+            Ok(_) if UnixStream::connect(path).is_ok() => {
+                return Err(format!(
+                    "a daemon is already listening on {}",
+                    path.display()
+                ))
+            }
+            Ok(_) => std::fs::remove_file(path).map_err(describe)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(describe(e)),
+        }
+        if let Some(dir) = path.parent().filter(|dir| !dir.exists()) {
+            std::fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(dir)
+                .map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+
+        // The socket is created with the umask's permissions, so it is never
+        // reachable by other users, not even between bind and a chmod
+        let previous = umask(Mode::from_bits_truncate(0o177));
+        let listener = UnixListener::bind(path);
+        umask(previous);
+
+        Ok(Self {
+            listener: listener.map_err(describe)?,
+            metrics: None,
+        })
+    }
+
+    // Also answers Prometheus scrapes from the latest scan
+    pub fn with_metrics(mut self, server: MetricsServer) -> Self {
+        self.metrics = Some(server);
+        self
+    }
+
+    // Serves until the process is killed. `on_action` sees what the rules did
+    // after each scan.
+    pub fn run(
+        self,
+        interval: Duration,
+        recorder: Option<Recorder>,
+        rules: RuleEngine,
+        on_action: fn(&Outcome),
+    ) -> Result<(), String> {
+        let shared = Arc::new(Shared {
+            snapshot: RwLock::new(Snapshot::default()),
+            subscribers: Mutex::new(Vec::new()),
+            recording: recorder.is_some(),
+            rules: rules.rules().len(),
+        });
+
+        let scanner = Arc::clone(&shared);
+        thread::Builder::new()
+            .name("portkill-daemon-scan".to_string())
+            .spawn(move || scanner.scan_loop(interval, recorder, rules, on_action))
+            .map_err(|e| format!("failed to start the scan thread: {}", e))?;
+
+        if let Some(server) = self.metrics {
+            let scraped = Arc::clone(&shared);
+            thread::Builder::new()
+                .name("portkill-daemon-metrics".to_string())
+                .spawn(move || server.serve(|| scraped.metrics()))
+                .map_err(|e| format!("failed to start the metrics thread: {}", e))?;
+        }
+
+        Self::accept(&self.listener, &shared);
+        Ok(())
+    }
+
+    // One thread per client
+    fn accept(listener: &UnixListener, shared: &Arc<Shared>) {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("Failed to accept a client: {}", e);
+                    continue;
+                }
+            };
+
+            let shared = Arc::clone(shared);
+            thread::spawn(move || {
+                if let Err(e) = shared.serve(stream) {
+                    log::debug!("Client disconnected: {}", e);
+                }
+            });
+        }
+    }
+}
+
+impl Shared {
+    fn scan_loop(
+        &self,
+        interval: Duration,
+        mut recorder: Option<Recorder>,
+        mut rules: RuleEngine,
+        on_action: fn(&Outcome),
+    ) {
+        loop {
+            let started = Instant::now();
+            match Scanner::scan_all() {
+                Ok(entries) => {
+                    let duration = started.elapsed();
+                    if let Some(active) = &mut recorder {
+                        if let Err(e) = active.record(&entries) {
+                            log::warn!("History recording stopped: {}", e);
+                            recorder = None;
+                        }
+                    }
+                    let outcomes = rules.evaluate(&entries);
+                    for outcome in &outcomes {
+                        on_action(outcome);
+                    }
+                    self.publish(entries, duration, &outcomes);
+                }
+                Err(e) => {
+                    log::warn!("Failed to scan ports: {}", e);
+                    self.snapshot
+                        .write()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .scan_errors += 1;
+                }
+            }
+            thread::sleep(interval);
+        }
+    }
+
+    // Replaces the snapshot and sends what changed to the subscribers
+    fn publish(&self, entries: Vec<PortEntry>, duration: Duration, outcomes: &[Outcome]) {
+        let now = Local::now();
+        let mut snapshot = self.snapshot.write().unwrap_or_else(|e| e.into_inner());
+        let diff = match snapshot.scanned_at {
+            Some(_) => SnapshotDiff::between(&snapshot.entries, &entries),
+            None => SnapshotDiff::default(),
+        };
+        snapshot.entries = entries;
+        snapshot.scanned_at = Some(now);
+        snapshot.scan_duration = Some(duration);
+        snapshot.scans += 1;
+        drop(snapshot);
+
+        if diff.is_empty() && outcomes.is_empty() {
+            return;
+        }
+
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        for outcome in outcomes {
+            let message = rpc::notification("action", json!(outcome));
+            subscribers.retain(|subscriber| {
+                !subscriber.wants(&outcome.entry) || subscriber.events.send(message.clone()).is_ok()
+            });
+        }
+        for change in &diff.changes {
+            let event = Event {
+                time: now,
+                event: match change.kind {
+                    ChangeKind::New => HistoryEvent::Open,
+                    ChangeKind::Gone => HistoryEvent::Close,
+                    ChangeKind::Changed => HistoryEvent::Change,
+                },
+                entry: change.entry.clone(),
+                previous: change.previous.clone(),
+            };
+            let message = rpc::notification("event", json!(event));
+
+            // A subscriber whose connection is gone drops out here
+            subscribers.retain(|subscriber| {
+                !subscriber.wants(&change.entry) || subscriber.events.send(message.clone()).is_ok()
+            });
+        }
+    }
+
+    fn serve(&self, stream: UnixStream) -> Result<(), String> {
+        let mut writer = stream.try_clone().map_err(|e| e.to_string())?;
+        let reader = BufReader::new(stream);
+
+        for line in reader.lines() {
+            let line = line.map_err(|e| e.to_string())?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let request: Request = match serde_json::from_str(&line) {
+                Ok(request) => request,
+                Err(e) => {
+                    let code = match serde_json::from_str::<Value>(&line) {
+                        Ok(_) => rpc::INVALID_REQUEST,
+                        Err(_) => rpc::PARSE_ERROR,
+                    };
+                    Self::send(&mut writer, &rpc::error(&Value::Null, code, &e.to_string()))?;
+                    continue;
+                }
+            };
+            let id = request.id.clone().unwrap_or(Value::Null);
+
+            if request.method == "subscribe" {
+                return self.stream_events(&mut writer, &id, &request.params);
+            }
+
+            let reply = match self.dispatch(&request) {
+                Ok(result) => rpc::response(&id, result),
+                Err((code, message)) => rpc::error(&id, code, &message),
+            };
+            // Requests without an id are notifications and get no reply
+            if request.id.is_some() {
+                Self::send(&mut writer, &reply)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn dispatch(&self, request: &Request) -> Result<Value, Failure> {
+        match request.method.as_str() {
+            "status" => Ok(json!(self.status())),
+            "list" => self.list(&request.params),
+            "kill" => self.kill(&request.params),
+            method => Err((
+                rpc::METHOD_NOT_FOUND,
+                format!("unknown method '{}'", method),
+            )),
+        }
+    }
+
+    fn status(&self) -> Status {
+        let snapshot = self.snapshot.read().unwrap_or_else(|e| e.into_inner());
+        Status {
+            api: rpc::API_VERSION,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            pid: std::process::id(),
+            scanned_at: snapshot.scanned_at,
+            sockets: snapshot.entries.len(),
+            subscribers: self
+                .subscribers
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .len(),
+            recording: self.recording,
+            rules: self.rules,
+        }
+    }
+
+    fn metrics(&self) -> String {
+        let snapshot = self.snapshot.read().unwrap_or_else(|e| e.into_inner());
+        Scrape {
+            entries: &snapshot.entries,
+            scanned_at: snapshot.scanned_at,
+            scan_duration: snapshot.scan_duration,
+            scans: snapshot.scans,
+            scan_errors: snapshot.scan_errors,
+        }
+        .render()
+    }
+
+    fn list(&self, params: &Value) -> Result<Value, Failure> {
+        let query = Self::query(params)?;
+        let snapshot = self.snapshot.read().unwrap_or_else(|e| e.into_inner());
+        let Some(scanned_at) = snapshot.scanned_at else {
+            return Err((
+                rpc::NO_SCAN_YET,
+                "the first scan hasn't finished yet".to_string(),
+            ));
+        };
+
+        let entries: Vec<&PortEntry> = snapshot
+            .entries
+            .iter()
+            .filter(|entry| query.matches(entry))
+            .collect();
+        Ok(json!({ "scanned_at": scanned_at, "entries": entries }))
+    }
+
+    // Only processes that hold a socket and that the protection rules allow
+    fn kill(&self, params: &Value) -> Result<Value, Failure> {
+        let invalid = |message: &str| (rpc::INVALID_PARAMS, message.to_string());
+        let pid = params["pid"]
+            .as_u64()
+            .and_then(|pid| u32::try_from(pid).ok())
+            .ok_or_else(|| invalid("'pid' must be a process ID"))?;
+        let signal = match &params["signal"] {
+            Value::Null => None,
+            Value::String(signal) => Some(parse_signal(signal).map_err(|e| invalid(&e))?),
+            _ => return Err(invalid("'signal' must be a signal name or number")),
+        };
+
+        let holds_socket = self
+            .snapshot
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .iter()
+            .any(|entry| entry.pid == Some(pid));
+        if !holds_socket {
+            return Err((
+                rpc::NOT_PERMITTED,
+                format!("PID {} holds no socket in the latest scan", pid),
+            ));
+        }
+
+        if !Killer::can_kill(pid) {
+            return Err((
+                rpc::NOT_PERMITTED,
+                format!("PID {} is a system or protected process", pid),
+            ));
+        }
+
+        let result = match signal {
+            Some(signal) => Killer::kill(pid, signal),
+            None => Killer::terminate(pid).map(|termination| termination.message),
+        };
+        match result {
+            Ok(message) => {
+                log::info!("Killed for a client: {}", message);
+                Ok(json!({ "message": message }))
+            }
+            Err(e) => Err((rpc::KILL_FAILED, e)),
+        }
+    }
+
+    // The connection carries nothing but events from here on
+    fn stream_events(
+        &self,
+        writer: &mut UnixStream,
+        id: &Value,
+        params: &Value,
+    ) -> Result<(), String> {
+        let query = match Self::query(params) {
+            Ok(query) => query,
+            Err((code, message)) => return Self::send(writer, &rpc::error(id, code, &message)),
+        };
+
+        let (events_tx, events_rx) = mpsc::channel();
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Subscriber {
+                query,
+                all_states: params["all"].as_bool().unwrap_or(false),
+                events: events_tx,
+            });
+        Self::send(writer, &rpc::response(id, json!({ "subscribed": true })))?;
+
+        for event in events_rx {
+            Self::send(writer, &event)?;
+        }
+        Ok(())
+    }
+
+    fn query(params: &Value) -> Result<Query, Failure> {
+        match &params["filter"] {
+            Value::Null => Ok(Query::default()),
+            Value::String(filter) => Query::parse(filter).map_err(|e| (rpc::INVALID_PARAMS, e)),
+            _ => Err((rpc::INVALID_PARAMS, "'filter' must be a string".to_string())),
+        }
+    }
+
+    fn send(writer: &mut UnixStream, message: &Value) -> Result<(), String> {
+        writeln!(writer, "{}", message).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::process::ExitStatusExt;
+    use std::path::PathBuf;
+    use std::process::{Command, Stdio};
+
+    use crate::rpc::Client;
+
+    fn socket_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "portkill-daemon-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    // A daemon serving `entries` as its latest scan, without scanning
+    fn serve(name: &str, entries: Option<Vec<PortEntry>>) -> PathBuf {
+        let path = socket_dir(name).join("daemon.sock");
+        let daemon = Daemon::bind(&path).unwrap();
+        let shared = Arc::new(Shared {
+            snapshot: RwLock::new(Snapshot {
+                scanned_at: entries.is_some().then(Local::now),
+                entries: entries.unwrap_or_default(),
+                ..Snapshot::default()
+            }),
+            subscribers: Mutex::new(Vec::new()),
+            recording: false,
+            rules: 0,
+        });
+        thread::spawn(move || Daemon::accept(&daemon.listener, &shared));
+        path
+    }
+
+    fn holding_socket(pid: u32) -> PortEntry {
+        PortEntry {
+            pid: Some(pid),
+            ..PortEntry::listener(8080, "sleep")
+        }
+    }
+
+    #[test]
+    fn binds_an_owner_only_socket_and_replaces_a_stale_one() {
+        let dir = socket_dir("bind");
+        let path = dir.join("daemon.sock");
+
+        let daemon = Daemon::bind(&path).unwrap();
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&dir), 0o700);
+        assert_eq!(mode(&path), 0o600);
+
+        let error = Daemon::bind(&path).err().unwrap();
+        assert!(error.contains("already listening"), "{}", error);
+
+        // The socket file outlives a daemon that died
+        drop(daemon);
+        assert!(path.exists());
+        Daemon::bind(&path).unwrap();
+
+        let file = dir.join("not-a-socket");
+        std::fs::write(&file, "keep").unwrap();
+        let error = Daemon::bind(&file).err().unwrap();
+        assert!(error.contains("not a socket"), "{}", error);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn answers_one_json_object_per_line() {
+        let path = serve("framing", Some(Vec::new()));
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        // Written at once, answered in order; blank lines and notifications
+        // get no reply
+        let requests = [
+            "",
+            "{not json",
+            r#"{"jsonrpc": "2.0", "id": 1}"#,
+            r#"{"jsonrpc": "2.0", "method": "status"}"#,
+            r#"{"jsonrpc": "2.0", "id": "a", "method": "restart"}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "list", "params": {"filter": 5}}"#,
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "status"}"#,
+        ];
+        writeln!(stream, "{}", requests.join("\n")).unwrap();
+
+        let mut reader = BufReader::new(stream);
+        let mut reply = || {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            serde_json::from_str::<Value>(&line).unwrap()
+        };
+
+        let parse_error = reply();
+        assert_eq!(parse_error["id"], Value::Null);
+        assert_eq!(parse_error["error"]["code"], rpc::PARSE_ERROR);
+        assert_eq!(reply()["error"]["code"], rpc::INVALID_REQUEST);
+        let unknown = reply();
+        assert_eq!(unknown["id"], "a");
+        assert_eq!(unknown["error"]["code"], rpc::METHOD_NOT_FOUND);
+        assert_eq!(reply()["error"]["code"], rpc::INVALID_PARAMS);
+        let status = reply();
+        assert_eq!(status["jsonrpc"], "2.0");
+        assert_eq!(status["id"], 3);
+        assert_eq!(status["result"]["api"], rpc::API_VERSION);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn clients_read_the_latest_scan() {
+        let path = serve("list", Some(vec![PortEntry::listener(5432, "postgres")]));
+
+        let mut client = Client::connect_to(&path).unwrap();
+        let entries = client.list().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].process_name, "postgres");
+        assert_eq!(client.status().unwrap().sockets, 1);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        let path = serve("no-scan", None);
+        let error = Client::connect_to(&path).unwrap().list().err().unwrap();
+        assert_eq!(error, "the first scan hasn't finished yet");
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn kills_only_socket_holders_the_protection_rules_allow() {
+        let mut child = Command::new("sleep")
+            .arg("30")
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        let path = serve("kill", Some(vec![holding_socket(pid), holding_socket(1)]));
+        let mut client = Client::connect_to(&path).unwrap();
+
+        let error = client.kill(std::process::id(), None).err().unwrap();
+        assert!(error.contains("holds no socket"), "{}", error);
+        let error = client.kill(1, Some("SIGKILL")).err().unwrap();
+        assert!(error.contains("protected"), "{}", error);
+        let error = client.kill(pid, Some("SIGNOPE")).err().unwrap();
+        assert!(error.contains("SIGNOPE"), "{}", error);
+        let error = client.call("kill", json!({ "pid": "x" })).err().unwrap();
+        assert_eq!(error, "'pid' must be a process ID");
+
+        let message = client.kill(pid, Some("SIGKILL")).unwrap();
+        assert!(message.contains(&format!("PID {}", pid)), "{}", message);
+        let status = child.wait().unwrap();
+        assert_eq!(status.signal(), Some(9));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
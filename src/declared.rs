@@ -0,0 +1,528 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Serialize;
+
+// Ports a project expects to bind, collected from the files in its directory:
+//
+//   docker-compose.yml, compose.yaml   published `ports:` of each service
+//   .env, .env.*                       PORT= and *_PORT= variables
+//   package.json                       --port / PORT= in scripts
+//   Procfile, Procfile.*               --port / PORT= in process commands
+//
+// Parsing is line-based and forgiving; anything unrecognized is skipped.
+#[derive(Clone, Debug, Serialize)]
+pub struct DeclaredPort {
+    pub port: u16,
+    pub file: String,
+    pub line: usize,
+    // Where in the file, e.g. `service "web"` or `script "dev"`
+    pub source: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Declarations {
+    pub ports: Vec<DeclaredPort>,
+    // Files that exist but could not be read or parsed
+    pub warnings: Vec<String>,
+}
+
+const COMPOSE_FILES: &[&str] = &[
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yaml",
+    "docker-compose.yml",
+];
+
+// Templates list placeholder values, not ports anything will bind
+const ENV_TEMPLATE_SUFFIXES: &[&str] = &[".example", ".sample", ".template", ".dist"];
+
+// Compose port ranges ("3000-3999:3000-3999") are expanded up to this many ports
+const MAX_RANGE_PORTS: usize = 100;
+
+impl Declarations {
+    pub fn scan(dir: &Path) -> Self {
+        let mut declarations = Self::default();
+
+        let mut files: Vec<String> = match std::fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect(),
+            Err(e) => {
+                declarations
+                    .warnings
+                    .push(format!("{}: {}", dir.display(), e));
+                return declarations;
+            }
+        };
+        files.sort();
+
+        // .env values are needed to interpolate ${VAR} in compose files
+        let mut env = HashMap::new();
+        for name in files.iter().filter(|name| Self::is_env_file(name)) {
+            if let Some(contents) = declarations.read(dir, name) {
+                declarations.scan_env(name, &contents, &mut env);
+            }
+        }
+
+        for name in &files {
+            if COMPOSE_FILES.contains(&name.as_str()) {
+                if let Some(contents) = declarations.read(dir, name) {
+                    declarations.scan_compose(name, &contents, &env);
+                }
+            } else if name == "package.json" {
+                if let Some(contents) = declarations.read(dir, name) {
+                    declarations.scan_package_json(name, &contents);
+                }
+            } else if name == "Procfile" || name.starts_with("Procfile.") {
+                if let Some(contents) = declarations.read(dir, name) {
+                    declarations.scan_procfile(name, &contents);
+                }
+            }
+        }
+
+        declarations
+    }
+
+    fn is_env_file(name: &str) -> bool {
+        (name == ".env" || name.starts_with(".env."))
+            && !ENV_TEMPLATE_SUFFIXES
+                .iter()
+                .any(|suffix| name.ends_with(suffix))
+    }
+
+    fn read(&mut self, dir: &Path, name: &str) -> Option<String> {
+        match std::fs::read_to_string(dir.join(name)) {
+            Ok(contents) => Some(contents),
+            Err(e) => {
+                self.warnings.push(format!("{}: {}", name, e));
+                None
+            }
+        }
+    }
+
+    fn push(&mut self, port: u16, file: &str, line: usize, source: String) {
+        self.ports.push(DeclaredPort {
+            port,
+            file: file.to_string(),
+            line,
+            source,
+        });
+    }
+
+    // PORT=3000, DB_PORT="5432", export API_PORT=8080
+    fn scan_env(&mut self, file: &str, contents: &str, env: &mut HashMap<String, String>) {
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let key = key.trim();
+            let value = value.trim().trim_matches(['"', '\'']);
+
+            env.insert(key.to_string(), value.to_string());
+
+            if key == "PORT" || key.ends_with("_PORT") {
+                if let Ok(port) = value.parse::<u16>() {
+                    self.push(port, file, index + 1, key.to_string());
+                }
+            }
+        }
+    }
+
+    fn scan_package_json(&mut self, file: &str, contents: &str) {
+        let manifest: serde_json::Value = match serde_json::from_str(contents) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                self.warnings.push(format!("{}: {}", file, e));
+                return;
+            }
+        };
+
+        let Some(scripts) = manifest
+            .get("scripts")
+            .and_then(|scripts| scripts.as_object())
+        else {
+            return;
+        };
+
+        for (name, command) in scripts {
+            let Some(command) = command.as_str() else {
+                continue;
+            };
+            // JSON carries no positions; point at the script's key instead
+            let key = format!("\"{}\"", name);
+            let line = contents
+                .lines()
+                .position(|line| line.trim_start().starts_with(&key))
+                .map_or(0, |index| index + 1);
+
+            for port in Self::command_ports(command) {
+                self.push(port, file, line, format!("script \"{}\"", name));
+            }
+        }
+    }
+
+    // <process type>: <command>
+    fn scan_procfile(&mut self, file: &str, contents: &str) {
+        for (index, line) in contents.lines().enumerate() {
+            let Some((name, command)) = line.split_once(':') else {
+                continue;
+            };
+            if name.trim_start().starts_with('#') {
+                continue;
+            }
+
+            for port in Self::command_ports(command) {
+                self.push(
+                    port,
+                    file,
+                    index + 1,
+                    format!("process \"{}\"", name.trim()),
+                );
+            }
+        }
+    }
+
+    // `--port 3000`, `--port=3000`, `-p 3000` and `PORT=3000 cmd`
+    fn command_ports(command: &str) -> Vec<u16> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let mut ports = Vec::new();
+
+        for (index, word) in words.iter().enumerate() {
+            let value = match *word {
+                "--port" | "-p" => words.get(index + 1).copied(),
+                _ => word
+                    .strip_prefix("--port=")
+                    .or_else(|| word.strip_prefix("PORT=")),
+            };
+
+            if let Some(port) =
+                value.and_then(|value| value.trim_matches(['"', '\'']).parse::<u16>().ok())
+            {
+                ports.push(port);
+            }
+        }
+
+        ports
+    }
+
+    // Follows `services:` -> `<name>:` -> `ports:` by indentation
+    fn scan_compose(&mut self, file: &str, contents: &str, env: &HashMap<String, String>) {
+        let mut services_indent = None;
+        let mut service_indent = None;
+        let mut service = String::new();
+        let mut ports_indent = None;
+
+        for (index, raw_line) in contents.lines().enumerate() {
+            let line = Self::strip_yaml_comment(raw_line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            let indent = line.len() - line.trim_start().len();
+            let text = line.trim();
+
+            if ports_indent.is_some_and(|ports_indent| indent <= ports_indent) {
+                ports_indent = None;
+            }
+            if services_indent.is_some_and(|services_indent| indent <= services_indent) {
+                services_indent = None;
+                service_indent = None;
+            }
+
+            if ports_indent.is_some() {
+                // Short syntax "- 8080:80", long syntax "published: 8080"
+                let item = text.strip_prefix("- ").unwrap_or(text).trim();
+                let value = match item.split_once(':') {
+                    Some(("published", value)) => Some(value.trim()),
+                    Some((
+                        "target" | "protocol" | "host_ip" | "mode" | "name" | "app_protocol",
+                        _,
+                    )) => None,
+                    _ if text.starts_with('-') => {
+                        for port in Self::compose_ports(item, env) {
+                            self.push(port, file, index + 1, format!("service \"{}\"", service));
+                        }
+                        None
+                    }
+                    _ => None,
+                };
+
+                if let Some(port) =
+                    value.and_then(|value| Self::interpolate(value, env).parse::<u16>().ok())
+                {
+                    self.push(port, file, index + 1, format!("service \"{}\"", service));
+                }
+                continue;
+            }
+
+            if services_indent.is_none() {
+                if text == "services:" {
+                    services_indent = Some(indent);
+                }
+                continue;
+            }
+
+            // The first key below `services:` sets the indentation of service names
+            if service_indent.is_none() {
+                service_indent = Some(indent);
+            }
+            if Some(indent) == service_indent {
+                if let Some(name) = text.strip_suffix(':') {
+                    service = name.trim_matches(['"', '\'']).to_string();
+                }
+                continue;
+            }
+
+            if let Some(value) = text.strip_prefix("ports:") {
+                let value = value.trim();
+                // Flow style: ports: ["8080:80", "5432:5432"]
+                if let Some(items) = value
+                    .strip_prefix('[')
+                    .and_then(|value| value.strip_suffix(']'))
+                {
+                    for item in items.split(',') {
+                        for port in Self::compose_ports(item, env) {
+                            self.push(port, file, index + 1, format!("service \"{}\"", service));
+                        }
+                    }
+                } else {
+                    ports_indent = Some(indent);
+                }
+            }
+        }
+    }
+
+    // "8080:80", "127.0.0.1:8080:80/tcp", "3000-3002:3000-3002". A bare "80"
+    // publishes on a random host port, so it declares nothing.
+    fn compose_ports(item: &str, env: &HashMap<String, String>) -> Vec<u16> {
+        let item = Self::interpolate(item.trim().trim_matches(['"', '\'']), env);
+
+        let item = match item.rsplit_once('/') {
+            Some((_, protocol)) if protocol.eq_ignore_ascii_case("udp") => return Vec::new(),
+            Some((item, _)) => item,
+            None => item.as_str(),
+        };
+
+        // Drop the container side; what remains ends with the host port
+        let Some((host, _container)) = item.rsplit_once(':') else {
+            return Vec::new();
+        };
+        let host_port = host.rsplit(':').next().unwrap_or(host);
+
+        let (start, end) = match host_port.split_once('-') {
+            Some((start, end)) => (start.parse::<u16>(), end.parse::<u16>()),
+            None => (host_port.parse::<u16>(), host_port.parse::<u16>()),
+        };
+
+        match (start, end) {
+            (Ok(start), Ok(end)) if start <= end => (start..=end).take(MAX_RANGE_PORTS).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Replaces ${VAR}, ${VAR:-default} and ${VAR-default} using .env values
+    fn interpolate(text: &str, env: &HashMap<String, String>) -> String {
+        let mut result = String::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find("${") {
+            let Some(length) = rest[start..].find('}') else {
+                break;
+            };
+            let expression = &rest[start + 2..start + length];
+            let (name, default) = match expression
+                .split_once(":-")
+                .or_else(|| expression.split_once('-'))
+            {
+                Some((name, default)) => (name, default),
+                None => (expression, ""),
+            };
+
+            result.push_str(&rest[..start]);
+            result.push_str(env.get(name).map_or(default, String::as_str));
+            rest = &rest[start + length + 1..];
+        }
+
+        result.push_str(rest);
+        result
+    }
+
+    fn strip_yaml_comment(line: &str) -> &str {
+        match line.find(" #") {
+            Some(index) => &line[..index],
+            None if line.trim_start().starts_with('#') => "",
+            None => line,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ports(declarations: &Declarations) -> Vec<(u16, usize, &str)> {
+        declarations
+            .ports
+            .iter()
+            .map(|port| (port.port, port.line, port.source.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn env_files() {
+        let mut declarations = Declarations::default();
+        let mut env = HashMap::new();
+        declarations.scan_env(
+            ".env",
+            "# PORT=1\n\
+             PORT=3000\n\
+             DB_PORT=\"5432\"\n\
+             export API_PORT='8080'\n\
+             REPORT=4000\n\
+             CACHE_PORT=${REDIS_PORT}\n\
+             HOST=localhost\n",
+            &mut env,
+        );
+
+        assert_eq!(
+            ports(&declarations),
+            [
+                (3000, 2, "PORT"),
+                (5432, 3, "DB_PORT"),
+                (8080, 4, "API_PORT")
+            ]
+        );
+        assert_eq!(env["API_PORT"], "8080");
+        assert_eq!(env["HOST"], "localhost");
+    }
+
+    #[test]
+    fn env_templates_are_skipped() {
+        assert!(Declarations::is_env_file(".env"));
+        assert!(Declarations::is_env_file(".env.local"));
+        assert!(!Declarations::is_env_file(".env.example"));
+        assert!(!Declarations::is_env_file(".env.local.sample"));
+        assert!(!Declarations::is_env_file("env"));
+    }
+
+    #[test]
+    fn package_json_scripts() {
+        let mut declarations = Declarations::default();
+        declarations.scan_package_json(
+            "package.json",
+            r#"{
+  "name": "web",
+  "scripts": {
+    "dev": "vite --port 5173",
+    "api": "PORT=4000 node server.js",
+    "preview": "vite preview --port=4173 -p '4174'",
+    "build": "vite build"
+  }
+}"#,
+        );
+
+        // serde_json keeps object keys sorted
+        assert_eq!(
+            ports(&declarations),
+            [
+                (4000, 5, "script \"api\""),
+                (5173, 4, "script \"dev\""),
+                (4173, 6, "script \"preview\""),
+                (4174, 6, "script \"preview\"")
+            ]
+        );
+
+        let mut broken = Declarations::default();
+        broken.scan_package_json("package.json", "{ \"scripts\": ");
+        assert!(broken.ports.is_empty());
+        assert_eq!(broken.warnings.len(), 1);
+    }
+
+    #[test]
+    fn procfile_processes() {
+        let mut declarations = Declarations::default();
+        declarations.scan_procfile(
+            "Procfile",
+            "web: bundle exec puma -p 3000\n\
+             # worker: sidekiq --port 9999\n\
+             worker: bundle exec sidekiq\n\
+             api: PORT=4000 node api.js\n",
+        );
+
+        assert_eq!(
+            ports(&declarations),
+            [(3000, 1, "process \"web\""), (4000, 4, "process \"api\"")]
+        );
+    }
+
+    #[test]
+    fn compose_ports() {
+        let env = HashMap::from([("WEB_PORT".to_string(), "8000".to_string())]);
+        let mut declarations = Declarations::default();
+        declarations.scan_compose(
+            "compose.yaml",
+            r#"
+x-ports: &ports
+  - "9999:9999"
+services:
+  web:
+    image: nginx
+    ports:
+      - "${WEB_PORT:-80}:80"
+      - 127.0.0.1:8443:443/tcp
+      - "53:53/udp"
+      - "9229"    # random host port
+  db:
+    ports: ["5432:5432", "${DB_PORT:-5433}:5432"]
+  "api":
+    ports:
+      - target: 3000
+        published: 3000
+        protocol: tcp
+      - 3001-3003:3001-3003
+volumes:
+  data:
+    ports: should not count
+"#,
+            &env,
+        );
+
+        assert_eq!(
+            ports(&declarations),
+            [
+                (8000, 8, "service \"web\""),
+                (8443, 9, "service \"web\""),
+                (5432, 13, "service \"db\""),
+                (5433, 13, "service \"db\""),
+                (3000, 17, "service \"api\""),
+                (3001, 19, "service \"api\""),
+                (3002, 19, "service \"api\""),
+                (3003, 19, "service \"api\"")
+            ]
+        );
+    }
+
+    #[test]
+    fn compose_port_ranges_are_capped() {
+        let ports = Declarations::compose_ports("10000-20000:10000-20000", &HashMap::new());
+        assert_eq!(ports.len(), MAX_RANGE_PORTS);
+        assert_eq!(ports[0], 10000);
+    }
+
+    #[test]
+    fn interpolation() {
+        let env = HashMap::from([("PORT".to_string(), "3000".to_string())]);
+        assert_eq!(Declarations::interpolate("${PORT}:80", &env), "3000:80");
+        assert_eq!(Declarations::interpolate("${MISSING:-8080}", &env), "8080");
+        assert_eq!(Declarations::interpolate("${MISSING-8081}", &env), "8081");
+        assert_eq!(Declarations::interpolate("${MISSING}", &env), "");
+        assert_eq!(Declarations::interpolate("${PORT", &env), "${PORT");
+    }
+}
@@ -0,0 +1,1071 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::process::Command;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::scanner::{PortEntry, SocketState};
+
+// Whether the host firewall lets a new TCP connection from another host reach
+// a listener, worked out by walking the INPUT chains of the live ruleset
+// (`nft -j list ruleset`, falling back to `iptables-save`/`ip6tables-save`).
+//
+// "Reachable" means some outside host can connect: an ACCEPT limited to a
+// source network still counts, a DROP limited to one does not.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reachability {
+    Reachable,
+    Blocked,
+    Unknown,
+}
+
+impl std::fmt::Display for Reachability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reachability::Reachable => write!(f, "reachable from outside"),
+            Reachability::Blocked => write!(f, "blocked"),
+            Reachability::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FirewallVerdict {
+    pub reachability: Reachability,
+    // The rule or chain policy that decided it, or why it couldn't be decided
+    pub rule: String,
+}
+
+// The ruleset is re-read at most this often; scans run every few seconds
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+// Guards against jump loops in hand-written iptables rulesets
+const MAX_JUMP_DEPTH: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Family {
+    V4,
+    V6,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Action {
+    Accept,
+    Drop,
+    Jump(String),
+    Goto(String),
+    Return,
+    // LOG, counter, MARK and other non-terminating statements
+    Continue,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Match {
+    No,
+    Yes,
+    // Matches only some outside traffic, e.g. one source network
+    Partly,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    text: String,
+    // Some(false) when the rule only applies to other protocols
+    tcp: Option<bool>,
+    // Port ranges the destination port must (or, when negated, must not) be in
+    ports: Vec<(Vec<(u16, u16)>, bool)>,
+    family: Option<Family>,
+    // Only matches traffic arriving on the loopback interface
+    loopback_only: bool,
+    // Never matches the first packet of a connection, e.g. ESTABLISHED only
+    never_new: bool,
+    narrowed: bool,
+    action: Action,
+}
+
+impl Rule {
+    fn new(text: String) -> Self {
+        Rule {
+            text,
+            tcp: None,
+            ports: Vec::new(),
+            family: None,
+            loopback_only: false,
+            never_new: false,
+            narrowed: false,
+            action: Action::Continue,
+        }
+    }
+
+    fn matches(&self, port: u16, family: Family) -> Match {
+        let port_matches = self.ports.iter().all(|(ranges, negated)| {
+            ranges
+                .iter()
+                .any(|(start, end)| (*start..=*end).contains(&port))
+                != *negated
+        });
+
+        if self.tcp == Some(false)
+            || self.family.is_some_and(|rule_family| rule_family != family)
+            || self.loopback_only
+            || self.never_new
+            || !port_matches
+        {
+            Match::No
+        } else if self.narrowed {
+            Match::Partly
+        } else {
+            Match::Yes
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Decision {
+    accept: bool,
+    rule: String,
+}
+
+#[derive(Clone, Debug)]
+struct Chain {
+    rules: Vec<Rule>,
+    // Base chains only
+    policy: Option<bool>,
+    label: String,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Ruleset {
+    // Chains hooked into input, in the order the kernel runs them
+    base_chains: Vec<String>,
+    chains: HashMap<String, Chain>,
+}
+
+impl Ruleset {
+    fn verdict(&self, port: u16, family: Family) -> FirewallVerdict {
+        if self.base_chains.is_empty() {
+            return FirewallVerdict {
+                reachability: Reachability::Reachable,
+                rule: "no input filter rules".to_string(),
+            };
+        }
+
+        // A packet has to be accepted by every base chain; a drop in any is final
+        let mut accepted_by = None;
+        for name in &self.base_chains {
+            let chain = &self.chains[name];
+            let decision = self
+                .evaluate(name, port, family, 0)
+                .unwrap_or_else(|| Decision {
+                    accept: chain.policy.unwrap_or(true),
+                    rule: format!(
+                        "policy {} of {}",
+                        if chain.policy.unwrap_or(true) {
+                            "accept"
+                        } else {
+                            "drop"
+                        },
+                        chain.label
+                    ),
+                });
+
+            if !decision.accept {
+                return FirewallVerdict {
+                    reachability: Reachability::Blocked,
+                    rule: decision.rule,
+                };
+            }
+            accepted_by.get_or_insert(decision.rule);
+        }
+
+        FirewallVerdict {
+            reachability: Reachability::Reachable,
+            rule: accepted_by.unwrap_or_default(),
+        }
+    }
+
+    // None when the packet falls off the end of the chain or hits RETURN
+    fn evaluate(&self, name: &str, port: u16, family: Family, depth: usize) -> Option<Decision> {
+        let chain = self.chains.get(name)?;
+        if depth > MAX_JUMP_DEPTH {
+            return None;
+        }
+
+        for rule in &chain.rules {
+            let matched = rule.matches(port, family);
+            if matched == Match::No {
+                continue;
+            }
+
+            let decision = match &rule.action {
+                Action::Accept => Some(Decision {
+                    accept: true,
+                    rule: rule.text.clone(),
+                }),
+                Action::Drop => Some(Decision {
+                    accept: false,
+                    rule: rule.text.clone(),
+                }),
+                Action::Jump(target) => self.evaluate(target, port, family, depth + 1),
+                // Returning from a goto'd chain also leaves this one
+                Action::Goto(target) if matched == Match::Yes => {
+                    return self.evaluate(target, port, family, depth + 1);
+                }
+                Action::Goto(target) => self.evaluate(target, port, family, depth + 1),
+                Action::Return if matched == Match::Yes => return None,
+                Action::Return | Action::Continue => None,
+            };
+
+            // Traffic that only partly matches is let through by an accept, but
+            // the rest of it carries on past a drop
+            if let Some(decision) = decision {
+                if matched == Match::Yes || decision.accept {
+                    return Some(decision);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+// When the cached ruleset was read
+type Loaded = (Instant, Arc<Firewall>);
+
+pub struct Firewall {
+    // "nftables" or "iptables"
+    source: &'static str,
+    v4: Option<Ruleset>,
+    v6: Option<Ruleset>,
+    // Why the ruleset couldn't be read, reported as the rule of unknown verdicts
+    error: Option<String>,
+}
+
+impl Firewall {
+    // The live ruleset, re-read when the cached copy is older than RELOAD_INTERVAL
+    pub fn current() -> Arc<Firewall> {
+        static CACHE: OnceLock<Mutex<Option<Loaded>>> = OnceLock::new();
+
+        let cache = CACHE.get_or_init(|| Mutex::new(None));
+        let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+
+        match cache.as_ref() {
+            Some((loaded, firewall)) if loaded.elapsed() < RELOAD_INTERVAL => firewall.clone(),
+            _ => {
+                let firewall = Arc::new(Self::load());
+                *cache = Some((Instant::now(), firewall.clone()));
+                firewall
+            }
+        }
+    }
+
+    pub fn load() -> Firewall {
+        if !cfg!(target_os = "linux") {
+            return Self::unknown("firewall rules can only be read on Linux".to_string());
+        }
+
+        let nft_error = match Self::run("nft", &["-j", "list", "ruleset"]) {
+            Ok(output) => match Self::from_nft_json(&output) {
+                Ok(firewall) => return firewall,
+                Err(e) => e,
+            },
+            Err(e) => e,
+        };
+
+        let v4 = Self::run("iptables-save", &[]);
+        let v6 = Self::run("ip6tables-save", &[]);
+        match (&v4, &v6) {
+            (Err(_), Err(e)) => Self::unknown(format!("{}; {}", nft_error, e)),
+            _ => {
+                let mut firewall = Self::from_iptables_save(v4.as_deref().ok(), v6.as_deref().ok());
+                firewall.error = v4.err().or(v6.err());
+                firewall
+            }
+        }
+    }
+
+    fn unknown(error: String) -> Firewall {
+        Firewall {
+            source: "",
+            v4: None,
+            v6: None,
+            error: Some(error),
+        }
+    }
+
+    fn run(program: &str, args: &[&str]) -> Result<String, String> {
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|e| format!("{}: {}", program, e))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            // Both tools need CAP_NET_ADMIN even to read the ruleset
+            return Err(format!(
+                "{}: {} (run as root to read firewall rules)",
+                program,
+                stderr.trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    // Annotates every listener that isn't bound to loopback only
+    pub fn annotate(&self, entries: &mut [PortEntry]) {
+        for entry in entries.iter_mut() {
+            if entry.state == SocketState::Listen {
+                entry.firewall = self.verdict(&entry.local_addr, entry.port);
+            }
+        }
+    }
+
+    // None for loopback addresses, which other hosts can't reach anyway
+    pub fn verdict(&self, local_addr: &str, port: u16) -> Option<FirewallVerdict> {
+        let families: &[Family] = match local_addr.parse::<SocketAddr>() {
+            Ok(addr) if addr.ip().is_loopback() => return None,
+            Ok(SocketAddr::V4(_)) => &[Family::V4],
+            // Wildcard IPv6 sockets also accept IPv4 unless bound v6-only
+            Ok(SocketAddr::V6(addr)) if addr.ip().is_unspecified() => &[Family::V6, Family::V4],
+            Ok(SocketAddr::V6(_)) => &[Family::V6],
+            // lsof's "*:8080"
+            Err(_) => &[Family::V4, Family::V6],
+        };
+
+        let verdicts: Vec<FirewallVerdict> = families
+            .iter()
+            .map(|family| self.family_verdict(port, *family))
+            .collect();
+
+        [
+            Reachability::Reachable,
+            Reachability::Unknown,
+            Reachability::Blocked,
+        ]
+        .into_iter()
+        .find_map(|reachability| {
+            verdicts
+                .iter()
+                .find(|verdict| verdict.reachability == reachability)
+                .cloned()
+        })
+    }
+
+    fn family_verdict(&self, port: u16, family: Family) -> FirewallVerdict {
+        let ruleset = match family {
+            Family::V4 => self.v4.as_ref(),
+            Family::V6 => self.v6.as_ref(),
+        };
+
+        match ruleset {
+            Some(ruleset) => {
+                let verdict = ruleset.verdict(port, family);
+                FirewallVerdict {
+                    rule: format!("{}: {}", self.source, verdict.rule),
+                    ..verdict
+                }
+            }
+            None => FirewallVerdict {
+                reachability: Reachability::Unknown,
+                rule: self.error.clone().unwrap_or_else(|| {
+                    match family {
+                        Family::V4 => "no IPv4 ruleset",
+                        Family::V6 => "no IPv6 ruleset",
+                    }
+                    .to_string()
+                }),
+            },
+        }
+    }
+
+    // `iptables-save` and `ip6tables-save` output
+    pub fn from_iptables_save(v4: Option<&str>, v6: Option<&str>) -> Firewall {
+        Firewall {
+            source: "iptables",
+            v4: v4.map(Self::iptables_ruleset),
+            v6: v6.map(Self::iptables_ruleset),
+            error: None,
+        }
+    }
+
+    // Only the INPUT chain of the filter table, and the chains it jumps to, matter
+    fn iptables_ruleset(text: &str) -> Ruleset {
+        let mut ruleset = Ruleset::default();
+        let mut in_filter = false;
+
+        for line in text.lines() {
+            let line = line.trim();
+
+            if let Some(table) = line.strip_prefix('*') {
+                in_filter = table == "filter";
+                continue;
+            }
+            if !in_filter {
+                continue;
+            }
+
+            // :INPUT DROP [0:0]
+            if let Some(declaration) = line.strip_prefix(':') {
+                let mut parts = declaration.split_whitespace();
+                if let (Some(name), Some(policy)) = (parts.next(), parts.next()) {
+                    let policy = match policy {
+                        "ACCEPT" => Some(true),
+                        "DROP" => Some(false),
+                        _ => None,
+                    };
+                    ruleset.chains.insert(
+                        name.to_string(),
+                        Chain {
+                            rules: Vec::new(),
+                            policy,
+                            label: format!("chain {}", name),
+                        },
+                    );
+                }
+                continue;
+            }
+
+            if line.starts_with("-A ") {
+                let words = Self::shell_words(line);
+                let Some(chain) = words.get(1) else {
+                    continue;
+                };
+                let rule = Self::iptables_rule(line, &words[2..]);
+                if let Some(chain) = ruleset.chains.get_mut(chain.as_str()) {
+                    chain.rules.push(rule);
+                }
+            }
+        }
+
+        if ruleset.chains.contains_key("INPUT") {
+            ruleset.base_chains.push("INPUT".to_string());
+        }
+
+        // Targets that aren't chains in the table (LOG, MARK, ...) don't terminate
+        let chain_names: Vec<String> = ruleset.chains.keys().cloned().collect();
+        for chain in ruleset.chains.values_mut() {
+            for rule in &mut chain.rules {
+                if let Action::Jump(target) | Action::Goto(target) = &rule.action {
+                    if !chain_names.contains(target) {
+                        rule.action = Action::Continue;
+                    }
+                }
+            }
+        }
+
+        ruleset
+    }
+
+    fn iptables_rule(line: &str, words: &[String]) -> Rule {
+        let mut rule = Rule::new(line.to_string());
+        let mut negated = false;
+        let mut index = 0;
+
+        while index < words.len() {
+            let word = words[index].as_str();
+            let value = words.get(index + 1).map(String::as_str).unwrap_or("");
+            index += 1;
+
+            if word == "!" {
+                negated = true;
+                continue;
+            }
+
+            match word {
+                "-p" | "--protocol" => {
+                    let tcp = matches!(value, "tcp" | "6");
+                    if !matches!(value, "all" | "0") {
+                        rule.tcp = Some(tcp != negated);
+                    }
+                    index += 1;
+                }
+                "--dport"
+                | "--destination-port"
+                | "--dports"
+                | "--destination-ports"
+                | "--ports" => {
+                    rule.ports.push((Self::port_ranges(value, ','), negated));
+                    index += 1;
+                }
+                "-i" | "--in-interface" => {
+                    if value == "lo" && !negated {
+                        rule.loopback_only = true;
+                    } else if !(value == "lo" && negated) {
+                        rule.narrowed = true;
+                    }
+                    index += 1;
+                }
+                "--ctstate" | "--state" => {
+                    let new = value.split(',').any(|state| state == "NEW");
+                    if negated {
+                        rule.narrowed = true;
+                    } else if !new {
+                        rule.never_new = true;
+                    }
+                    index += 1;
+                }
+                // The first packet of every connection is a SYN
+                "--syn" => rule.never_new |= negated,
+                "-m" | "--match" => {
+                    if !matches!(
+                        value,
+                        "tcp" | "multiport" | "conntrack" | "state" | "comment"
+                    ) {
+                        rule.narrowed = true;
+                    }
+                    index += 1;
+                }
+                "-s" | "--source" | "-d" | "--destination" | "--sport" | "--source-port"
+                | "--sports" | "--source-ports" => {
+                    rule.narrowed = true;
+                    index += 1;
+                }
+                "-j" | "--jump" => {
+                    rule.action = match value {
+                        "ACCEPT" => Action::Accept,
+                        "DROP" | "REJECT" => Action::Drop,
+                        "RETURN" => Action::Return,
+                        target => Action::Jump(target.to_string()),
+                    };
+                    index += 1;
+                }
+                "-g" | "--goto" => {
+                    rule.action = Action::Goto(value.to_string());
+                    index += 1;
+                }
+                _ => {}
+            }
+            negated = false;
+        }
+
+        rule
+    }
+
+    // "22", "8000:8080", "80,443,8000:8080"
+    fn port_ranges(text: &str, separator: char) -> Vec<(u16, u16)> {
+        text.split(separator)
+            .filter_map(|part| match part.split_once(':') {
+                Some((start, end)) => {
+                    Some((start.parse().unwrap_or(0), end.parse().unwrap_or(u16::MAX)))
+                }
+                None => part.parse().ok().map(|port| (port, port)),
+            })
+            .collect()
+    }
+
+    // Splits on whitespace, keeping "double quoted" comments together
+    fn shell_words(line: &str) -> Vec<String> {
+        let mut words = Vec::new();
+        let mut word = String::new();
+        let mut quoted = false;
+        let mut chars = line.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => quoted = !quoted,
+                '\\' if quoted => word.extend(chars.next()),
+                c if c.is_whitespace() && !quoted => {
+                    if !word.is_empty() {
+                        words.push(std::mem::take(&mut word));
+                    }
+                }
+                c => word.push(c),
+            }
+        }
+        if !word.is_empty() {
+            words.push(word);
+        }
+
+        words
+    }
+
+    // `nft -j list ruleset` output
+    pub fn from_nft_json(text: &str) -> Result<Firewall, String> {
+        let document: serde_json::Value =
+            serde_json::from_str(text).map_err(|e| format!("nft: unexpected output: {}", e))?;
+        let objects = document
+            .get("nftables")
+            .and_then(|objects| objects.as_array())
+            .ok_or_else(|| "nft: unexpected output: no \"nftables\" array".to_string())?;
+
+        let mut v4 = Ruleset::default();
+        let mut v6 = Ruleset::default();
+        // (family, prio, chain key) of every filter chain hooked into input
+        let mut base_chains: Vec<(String, i64, String)> = Vec::new();
+
+        let chain_key = |object: &serde_json::Value, name_field: &str| {
+            format!(
+                "{} {} {}",
+                object["family"].as_str().unwrap_or(""),
+                object["table"].as_str().unwrap_or(""),
+                object[name_field].as_str().unwrap_or("")
+            )
+        };
+
+        for object in objects {
+            if let Some(chain) = object.get("chain") {
+                let key = chain_key(chain, "name");
+                let policy = chain["policy"].as_str().map(|policy| policy == "accept");
+                let chain_entry = Chain {
+                    rules: Vec::new(),
+                    policy: policy.or(Some(true)),
+                    label: key.clone(),
+                };
+
+                let family = chain["family"].as_str().unwrap_or("");
+                for (ruleset, families) in [(&mut v4, ["ip", "inet"]), (&mut v6, ["ip6", "inet"])] {
+                    if families.contains(&family) {
+                        ruleset.chains.insert(key.clone(), chain_entry.clone());
+                    }
+                }
+
+                if chain["hook"] == "input" && chain["type"] == "filter" {
+                    let prio = chain["prio"].as_i64().unwrap_or(0);
+                    base_chains.push((family.to_string(), prio, key));
+                }
+            } else if let Some(rule) = object.get("rule") {
+                let key = chain_key(rule, "chain");
+                let parsed = Self::nft_rule(rule, &key);
+
+                for ruleset in [&mut v4, &mut v6] {
+                    if let Some(chain) = ruleset.chains.get_mut(&key) {
+                        chain.rules.push(parsed.clone());
+                    }
+                }
+            }
+        }
+
+        base_chains.sort_by_key(|(_, prio, _)| *prio);
+        for (family, _, key) in base_chains {
+            if family == "ip" || family == "inet" {
+                v4.base_chains.push(key.clone());
+            }
+            if family == "ip6" || family == "inet" {
+                v6.base_chains.push(key);
+            }
+        }
+
+        Ok(Firewall {
+            source: "nftables",
+            v4: Some(v4),
+            v6: Some(v6),
+            error: None,
+        })
+    }
+
+    fn nft_rule(rule: &serde_json::Value, chain: &str) -> Rule {
+        // JSON carries no rule text; the handle finds it in `nft -a list ruleset`
+        let mut text = format!("{} handle {}", chain, rule["handle"]);
+        if let Some(comment) = rule["comment"].as_str() {
+            text.push_str(&format!(" \"{}\"", comment));
+        }
+        let mut parsed = Rule::new(text);
+
+        let Some(expressions) = rule["expr"].as_array() else {
+            return parsed;
+        };
+
+        for expression in expressions {
+            if let Some(condition) = expression.get("match") {
+                Self::nft_match(&mut parsed, condition);
+            } else if expression.get("accept").is_some() {
+                parsed.action = Action::Accept;
+            } else if expression.get("drop").is_some() || expression.get("reject").is_some() {
+                parsed.action = Action::Drop;
+            } else if expression.get("return").is_some() {
+                parsed.action = Action::Return;
+            } else if let Some(target) = expression["jump"]["target"].as_str() {
+                parsed.action = Action::Jump(format!("{} {}", Self::table_of(chain), target));
+            } else if let Some(target) = expression["goto"]["target"].as_str() {
+                parsed.action = Action::Goto(format!("{} {}", Self::table_of(chain), target));
+            } else {
+                continue;
+            }
+            // Statements after a verdict are never reached
+            if parsed.action != Action::Continue {
+                break;
+            }
+        }
+
+        parsed
+    }
+
+    // "inet filter input" -> "inet filter"
+    fn table_of(chain: &str) -> &str {
+        chain.rsplit_once(' ').map_or(chain, |(table, _)| table)
+    }
+
+    fn nft_match(rule: &mut Rule, condition: &serde_json::Value) {
+        let negated = condition["op"] == "!=";
+        let left = &condition["left"];
+        let right = &condition["right"];
+
+        let values = match right.get("set").and_then(|set| set.as_array()) {
+            Some(set) => set.clone(),
+            None => vec![right.clone()],
+        };
+        let strings: Vec<&str> = values.iter().filter_map(|value| value.as_str()).collect();
+
+        if let Some(payload) = left.get("payload") {
+            let protocol = payload["protocol"].as_str().unwrap_or("");
+            match payload["field"].as_str().unwrap_or("") {
+                "dport" => {
+                    if protocol != "tcp" && protocol != "th" {
+                        rule.tcp = Some(false);
+                        return;
+                    }
+                    // Named sets ("@web_ports") can't be resolved here
+                    if values.iter().any(|value| value.is_string()) {
+                        rule.narrowed = true;
+                        return;
+                    }
+                    let ranges = values
+                        .iter()
+                        .filter_map(|value| match value.get("range") {
+                            Some(range) => {
+                                Some((range[0].as_u64()? as u16, range[1].as_u64()? as u16))
+                            }
+                            None => value.as_u64().map(|port| (port as u16, port as u16)),
+                        })
+                        .collect();
+                    rule.ports.push((ranges, negated));
+                    if protocol == "tcp" && !negated {
+                        rule.tcp.get_or_insert(true);
+                    }
+                }
+                "protocol" | "nexthdr" => {
+                    let tcp = strings.contains(&"tcp") || values.contains(&serde_json::json!(6));
+                    rule.tcp = Some(tcp != negated);
+                }
+                // Addresses, source ports, tcp flags and other header fields
+                _ => rule.narrowed = true,
+            }
+        } else if let Some(key) = left["meta"]["key"].as_str() {
+            match key {
+                "iifname" | "iif" => {
+                    if strings == ["lo"] && !negated {
+                        rule.loopback_only = true;
+                    } else if !(strings == ["lo"] && negated) {
+                        rule.narrowed = true;
+                    }
+                }
+                "l4proto" => {
+                    let tcp = strings.contains(&"tcp") || values.contains(&serde_json::json!(6));
+                    rule.tcp = Some(tcp != negated);
+                }
+                "nfproto" => match (strings.as_slice(), negated) {
+                    (["ipv4"], false) | (["ipv6"], true) => rule.family = Some(Family::V4),
+                    (["ipv6"], false) | (["ipv4"], true) => rule.family = Some(Family::V6),
+                    _ => {}
+                },
+                _ => rule.narrowed = true,
+            }
+        } else if left["ct"]["key"] == "state" {
+            // `ct state established,related` is a set, `ct state new` a string
+            let states: Vec<&str> = match right.as_array() {
+                Some(states) => states.iter().filter_map(|state| state.as_str()).collect(),
+                None => strings,
+            };
+            if negated {
+                rule.never_new |= states.contains(&"new");
+            } else if !states.contains(&"new") {
+                rule.never_new = true;
+            }
+        } else {
+            rule.narrowed = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iptables() -> Firewall {
+        Firewall::from_iptables_save(
+            Some(include_str!("../tests/fixtures/iptables-save.txt")),
+            Some(include_str!("../tests/fixtures/ip6tables-save.txt")),
+        )
+    }
+
+    fn nft() -> Firewall {
+        Firewall::from_nft_json(include_str!("../tests/fixtures/nft.json")).unwrap()
+    }
+
+    fn reachability(firewall: &Firewall, port: u16, family: Family) -> Reachability {
+        firewall.family_verdict(port, family).reachability
+    }
+
+    #[test]
+    fn iptables_drop_policy_blocks_unmatched_ports() {
+        let verdict = iptables().family_verdict(9999, Family::V4);
+        assert_eq!(verdict.reachability, Reachability::Blocked);
+        assert_eq!(verdict.rule, "iptables: policy drop of chain INPUT");
+    }
+
+    #[test]
+    fn iptables_loopback_and_established_accepts_dont_count() {
+        // Both rules come before the policy drop that decides 9999 and 3006
+        let firewall = iptables();
+        assert_eq!(
+            reachability(&firewall, 9999, Family::V4),
+            Reachability::Blocked
+        );
+        assert_eq!(
+            reachability(&firewall, 3006, Family::V4),
+            Reachability::Blocked
+        );
+    }
+
+    #[test]
+    fn iptables_port_lists_and_ranges() {
+        let firewall = iptables();
+        for port in [80, 443, 3000, 3003, 3005] {
+            assert_eq!(
+                reachability(&firewall, port, Family::V4),
+                Reachability::Reachable,
+                "{}",
+                port
+            );
+        }
+        let verdict = firewall.family_verdict(443, Family::V4);
+        assert!(
+            verdict.rule.contains("--dports 80,443,3000:3005"),
+            "{}",
+            verdict.rule
+        );
+        assert_eq!(
+            reachability(&firewall, 3006, Family::V4),
+            Reachability::Blocked
+        );
+        // Only a UDP rule mentions 53
+        assert_eq!(
+            reachability(&firewall, 53, Family::V4),
+            Reachability::Blocked
+        );
+    }
+
+    #[test]
+    fn iptables_drop_rule_names_the_rule() {
+        let verdict = iptables().family_verdict(5432, Family::V4);
+        assert_eq!(verdict.reachability, Reachability::Blocked);
+        assert!(
+            verdict.rule.contains("postgres, local only"),
+            "{}",
+            verdict.rule
+        );
+    }
+
+    #[test]
+    fn iptables_jump_and_return() {
+        let firewall = iptables();
+        // Past the negated RETURN into the ACCEPT after it
+        assert_eq!(
+            reachability(&firewall, 6650, Family::V4),
+            Reachability::Reachable
+        );
+        // In the negated range but not accepted; falls back to INPUT's policy
+        assert_eq!(
+            reachability(&firewall, 6900, Family::V4),
+            Reachability::Blocked
+        );
+        // A DROP for one source network lets the rest carry on to the ACCEPT for another
+        let verdict = firewall.family_verdict(9000, Family::V4);
+        assert_eq!(verdict.reachability, Reachability::Reachable);
+        assert!(verdict.rule.contains("192.168.1.0/24"), "{}", verdict.rule);
+    }
+
+    #[test]
+    fn iptables_goto_does_not_come_back() {
+        let firewall = iptables();
+        assert_eq!(
+            reachability(&firewall, 6400, Family::V4),
+            Reachability::Reachable
+        );
+        // `services` would accept 6650 but 6500 never gets there
+        assert_eq!(
+            reachability(&firewall, 6500, Family::V4),
+            Reachability::Blocked
+        );
+    }
+
+    #[test]
+    fn iptables_families_are_separate() {
+        let firewall = iptables();
+        assert_eq!(
+            reachability(&firewall, 8080, Family::V6),
+            Reachability::Blocked
+        );
+        assert_eq!(
+            reachability(&firewall, 9999, Family::V6),
+            Reachability::Reachable
+        );
+        // Drops only packets that aren't SYNs, so new connections get through
+        assert_eq!(
+            reachability(&firewall, 9090, Family::V6),
+            Reachability::Reachable
+        );
+    }
+
+    #[test]
+    fn iptables_missing_family_is_unknown() {
+        let firewall = Firewall::from_iptables_save(
+            Some(include_str!("../tests/fixtures/iptables-save.txt")),
+            None,
+        );
+        let verdict = firewall.family_verdict(443, Family::V6);
+        assert_eq!(verdict.reachability, Reachability::Unknown);
+        assert_eq!(verdict.rule, "no IPv6 ruleset");
+    }
+
+    #[test]
+    fn nft_drop_policy_blocks_unmatched_ports() {
+        let verdict = nft().family_verdict(3006, Family::V4);
+        assert_eq!(verdict.reachability, Reachability::Blocked);
+        assert_eq!(verdict.rule, "nftables: policy drop of inet filter input");
+    }
+
+    #[test]
+    fn nft_loopback_and_established_accepts_dont_count() {
+        let firewall = nft();
+        assert_eq!(
+            reachability(&firewall, 9999, Family::V4),
+            Reachability::Blocked
+        );
+        assert_eq!(
+            reachability(&firewall, 9999, Family::V6),
+            Reachability::Blocked
+        );
+    }
+
+    #[test]
+    fn nft_sets_and_ranges() {
+        let firewall = nft();
+        for port in [80, 443, 3000, 3005] {
+            assert_eq!(
+                reachability(&firewall, port, Family::V6),
+                Reachability::Reachable,
+                "{}",
+                port
+            );
+        }
+        let verdict = firewall.family_verdict(443, Family::V4);
+        assert_eq!(verdict.rule, "nftables: inet filter services handle 10");
+        assert_eq!(
+            reachability(&firewall, 53, Family::V4),
+            Reachability::Blocked
+        );
+    }
+
+    #[test]
+    fn nft_named_sets_may_accept_anything() {
+        let firewall = Firewall::from_nft_json(
+            r#"{"nftables": [
+                {"chain": {"family": "ip", "table": "filter", "name": "input", "type": "filter", "hook": "input", "prio": 0, "policy": "drop"}},
+                {"rule": {"family": "ip", "table": "filter", "chain": "input", "handle": 2, "expr": [
+                    {"match": {"op": "==", "left": {"payload": {"protocol": "tcp", "field": "dport"}}, "right": "@admin_ports"}},
+                    {"accept": null}]}}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            reachability(&firewall, 9000, Family::V4),
+            Reachability::Reachable
+        );
+        // A table of the `ip` family doesn't filter IPv6
+        assert_eq!(
+            reachability(&firewall, 9000, Family::V6),
+            Reachability::Reachable
+        );
+    }
+
+    #[test]
+    fn nft_jump_and_return() {
+        let firewall = nft();
+        assert_eq!(
+            reachability(&firewall, 6650, Family::V4),
+            Reachability::Reachable
+        );
+        assert_eq!(
+            reachability(&firewall, 6900, Family::V4),
+            Reachability::Blocked
+        );
+    }
+
+    #[test]
+    fn nft_drop_rule_names_the_rule() {
+        let verdict = nft().family_verdict(5432, Family::V4);
+        assert_eq!(verdict.reachability, Reachability::Blocked);
+        assert_eq!(
+            verdict.rule,
+            "nftables: inet filter input handle 6 \"postgres\""
+        );
+    }
+
+    #[test]
+    fn nft_every_base_chain_must_accept() {
+        let firewall = nft();
+        // inet filter accepts 3001, ip guard (IPv4 only) drops it
+        let verdict = firewall.family_verdict(3001, Family::V4);
+        assert_eq!(verdict.reachability, Reachability::Blocked);
+        assert_eq!(verdict.rule, "nftables: ip guard input handle 2");
+        assert_eq!(
+            reachability(&firewall, 3001, Family::V6),
+            Reachability::Reachable
+        );
+    }
+
+    #[test]
+    fn nft_family_matches() {
+        let firewall = nft();
+        assert_eq!(
+            reachability(&firewall, 8080, Family::V4),
+            Reachability::Reachable
+        );
+        assert_eq!(
+            reachability(&firewall, 8080, Family::V6),
+            Reachability::Blocked
+        );
+    }
+
+    #[test]
+    fn nft_without_input_chains_is_reachable() {
+        let firewall = Firewall::from_nft_json(r#"{"nftables": [{"metainfo": {}}]}"#).unwrap();
+        let verdict = firewall.family_verdict(22, Family::V4);
+        assert_eq!(verdict.reachability, Reachability::Reachable);
+        assert_eq!(verdict.rule, "nftables: no input filter rules");
+    }
+
+    #[test]
+    fn nft_rejects_unexpected_output() {
+        assert!(Firewall::from_nft_json("not json").is_err());
+        assert!(Firewall::from_nft_json(r#"{"ruleset": []}"#).is_err());
+    }
+
+    #[test]
+    fn verdict_by_address() {
+        let firewall = iptables();
+        assert_eq!(firewall.verdict("127.0.0.1:9999", 9999), None);
+        assert_eq!(firewall.verdict("[::1]:9999", 9999), None);
+        assert_eq!(
+            firewall.verdict("0.0.0.0:9999", 9999).unwrap().reachability,
+            Reachability::Blocked
+        );
+        // The IPv6 wildcard also takes IPv4 connections; either being open is enough
+        assert_eq!(
+            firewall.verdict("[::]:9999", 9999).unwrap().reachability,
+            Reachability::Reachable
+        );
+        assert_eq!(
+            firewall.verdict("[::]:8080", 8080).unwrap().reachability,
+            Reachability::Blocked
+        );
+        assert_eq!(
+            firewall.verdict("*:9999", 9999).unwrap().reachability,
+            Reachability::Reachable
+        );
+    }
+}
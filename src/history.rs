@@ -0,0 +1,715 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use nix::fcntl::{Flock, FlockArg};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{parse_duration, Config};
+use crate::scanner::{ChangeKind, PortEntry, SnapshotDiff, SocketKey, SocketState};
+
+// What listened where, and when: an append-only file with one JSON line per
+// listener that opens, closes or changes owner. Compaction folds events past
+// the retention into baseline records, so the file still replays to the
+// right state from its first line.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Record {
+    pub time: DateTime<Local>,
+    pub event: HistoryEvent,
+    pub entry: PortEntry,
+    // Still listening when older events were compacted away; `time` is when
+    // it started
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub baseline: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryEvent {
+    Open,
+    Close,
+    // Same socket, different process or user
+    Change,
+}
+
+impl std::fmt::Display for HistoryEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HistoryEvent::Open => write!(f, "open"),
+            HistoryEvent::Close => write!(f, "close"),
+            HistoryEvent::Change => write!(f, "change"),
+        }
+    }
+}
+
+// One listener with one owner, from open (or change) to close (or change)
+#[derive(Clone, Debug, Serialize)]
+pub struct Interval {
+    pub entry: PortEntry,
+    pub start: DateTime<Local>,
+    // None while it is still listening at the end of the history
+    pub end: Option<DateTime<Local>>,
+}
+
+impl Interval {
+    pub fn overlaps(&self, since: DateTime<Local>, until: DateTime<Local>) -> bool {
+        self.start <= until && self.end.is_none_or(|end| end >= since)
+    }
+
+    pub fn is_active_at(&self, time: DateTime<Local>) -> bool {
+        self.start <= time && self.end.is_none_or(|end| end > time)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    // In file order, which is chronological after the baseline
+    pub records: Vec<Record>,
+}
+
+// Only one process appends at a time
+pub struct Recorder {
+    path: PathBuf,
+    file: File,
+    _lock: Flock<File>,
+    // Listeners as of the last record
+    listening: Vec<PortEntry>,
+    retention: Duration,
+    max_bytes: u64,
+    last_compacted: Instant,
+}
+
+const COMPACT_INTERVAL: Duration = Duration::from_secs(3600);
+
+impl History {
+    // [history] path, or $XDG_STATE_HOME/portkill/history.ndjson defaulting
+    // to ~/.local/state
+    pub fn path() -> Option<PathBuf> {
+        if let Some(path) = &Config::global().history_path {
+            return Some(path.clone());
+        }
+
+        let state_home = std::env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state"))
+            })?;
+
+        Some(state_home.join("portkill").join("history.ndjson"))
+    }
+
+    // A missing file is an empty history; lines that don't parse (say, cut
+    // short by a crash) are skipped
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+
+        let mut records = Vec::new();
+        let mut skipped = 0;
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("{}: {}", path.display(), e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(record) => records.push(record),
+                Err(_) => skipped += 1,
+            }
+        }
+        if skipped > 0 {
+            log::warn!("Skipped {} unreadable lines in {}", skipped, path.display());
+        }
+
+        Ok(Self { records })
+    }
+
+    pub fn intervals(&self) -> Vec<Interval> {
+        let mut open: HashMap<SocketKey, Interval> = HashMap::new();
+        let mut closed = Vec::new();
+
+        for record in &self.records {
+            let key = record.entry.key();
+            match record.event {
+                HistoryEvent::Open | HistoryEvent::Change => {
+                    if let Some(mut interval) = open.remove(&key) {
+                        interval.end = Some(record.time);
+                        closed.push(interval);
+                    }
+                    open.insert(
+                        key,
+                        Interval {
+                            entry: record.entry.clone(),
+                            start: record.time,
+                            end: None,
+                        },
+                    );
+                }
+                HistoryEvent::Close => {
+                    if let Some(mut interval) = open.remove(&key) {
+                        interval.end = Some(record.time);
+                        closed.push(interval);
+                    }
+                }
+            }
+        }
+
+        closed.extend(open.into_values());
+        closed.sort_by(|a, b| {
+            a.start
+                .cmp(&b.start)
+                .then_with(|| a.entry.port.cmp(&b.entry.port))
+        });
+        closed
+    }
+
+    // Opens, closes and changes in the time range, oldest first
+    pub fn events_between(
+        &self,
+        since: DateTime<Local>,
+        until: DateTime<Local>,
+    ) -> impl Iterator<Item = &Record> {
+        self.records
+            .iter()
+            .filter(move |record| record.time >= since && record.time <= until)
+    }
+
+    // Listeners left open by `records`, with when each started
+    fn replay(records: &[Record]) -> HashMap<SocketKey, (DateTime<Local>, PortEntry)> {
+        let mut listening = HashMap::new();
+        for record in records {
+            match record.event {
+                HistoryEvent::Open | HistoryEvent::Change => {
+                    listening.insert(record.entry.key(), (record.time, record.entry.clone()));
+                }
+                HistoryEvent::Close => {
+                    listening.remove(&record.entry.key());
+                }
+            }
+        }
+        listening
+    }
+}
+
+impl Recorder {
+    // With the retention and size limit from the config
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let config = Config::global();
+        Self::open_with_limits(path, config.history_retention, config.history_max_bytes)
+    }
+
+    pub fn open_with_limits(
+        path: &Path,
+        retention: Duration,
+        max_bytes: u64,
+    ) -> Result<Self, String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+
+        let lock_path = path.with_extension("lock");
+        let lock_file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(|e| format!("{}: {}", lock_path.display(), e))?;
+        let lock = Flock::lock(lock_file, FlockArg::LockExclusiveNonblock).map_err(|_| {
+            format!(
+                "{} is already being recorded by another process",
+                path.display()
+            )
+        })?;
+
+        let history = History::load(path)?;
+        let listening = History::replay(&history.records)
+            .into_values()
+            .map(|(_, entry)| entry)
+            .collect();
+
+        let mut recorder = Self {
+            path: path.to_path_buf(),
+            file: Self::append_to(path)?,
+            _lock: lock,
+            listening,
+            retention,
+            max_bytes,
+            last_compacted: Instant::now(),
+        };
+        recorder.compact(history)?;
+        Ok(recorder)
+    }
+
+    // Appends what changed among the listeners since the last call; returns
+    // the number of events written
+    pub fn record(&mut self, entries: &[PortEntry]) -> Result<usize, String> {
+        let listening: Vec<PortEntry> = entries
+            .iter()
+            .filter(|entry| entry.state == SocketState::Listen)
+            .map(Self::slim)
+            .collect();
+        let diff = SnapshotDiff::between(&self.listening, &listening);
+
+        let time = Local::now();
+        let mut lines = String::new();
+        for change in &diff.changes {
+            let record = Record {
+                time,
+                event: match change.kind {
+                    ChangeKind::New => HistoryEvent::Open,
+                    ChangeKind::Gone => HistoryEvent::Close,
+                    ChangeKind::Changed => HistoryEvent::Change,
+                },
+                entry: change.entry.clone(),
+                baseline: false,
+            };
+            lines.push_str(&Self::line(&record)?);
+        }
+
+        if !lines.is_empty() {
+            self.file
+                .write_all(lines.as_bytes())
+                .map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        }
+        self.listening = listening;
+
+        if self.last_compacted.elapsed() >= COMPACT_INTERVAL {
+            self.compact(History::load(&self.path)?)?;
+        }
+
+        Ok(diff.changes.len())
+    }
+
+    // Drops events older than the retention, then the oldest ones until the
+    // file fits in three quarters of the size limit. Listeners still open at
+    // the cut become baseline records.
+    fn compact(&mut self, history: History) -> Result<(), String> {
+        self.last_compacted = Instant::now();
+
+        let records = history.records;
+        let lines = records
+            .iter()
+            .map(Self::line)
+            .collect::<Result<Vec<String>, String>>()?;
+
+        // None when the retention reaches back further than time does
+        let cutoff = chrono::Duration::from_std(self.retention)
+            .ok()
+            .and_then(|retention| Local::now().checked_sub_signed(retention));
+        let mut cut = records
+            .iter()
+            .position(|record| {
+                !record.baseline && cutoff.is_none_or(|cutoff| record.time >= cutoff)
+            })
+            .unwrap_or(records.len());
+
+        let target = (self.max_bytes / 4 * 3) as usize;
+        let mut size: usize = lines[cut..].iter().map(String::len).sum();
+        while size > target && cut < records.len() {
+            size -= lines[cut].len();
+            cut += 1;
+        }
+        // Never drop only baseline records: they are rebuilt the same
+        if records[..cut].iter().all(|record| record.baseline) {
+            return Ok(());
+        }
+
+        let mut baseline: Vec<(DateTime<Local>, PortEntry)> =
+            History::replay(&records[..cut]).into_values().collect();
+        baseline.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.port.cmp(&b.1.port)));
+
+        let temporary = self.path.with_extension("ndjson.tmp");
+        let mut contents = String::new();
+        for (time, entry) in baseline {
+            contents.push_str(&Self::line(&Record {
+                time,
+                event: HistoryEvent::Open,
+                entry,
+                baseline: true,
+            })?);
+        }
+        for line in &lines[cut..] {
+            contents.push_str(line);
+        }
+
+        std::fs::write(&temporary, contents)
+            .and_then(|_| std::fs::rename(&temporary, &self.path))
+            .map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        self.file = Self::append_to(&self.path)?;
+
+        log::info!("Compacted {}: dropped {} events", self.path.display(), cut);
+        Ok(())
+    }
+
+    fn append_to(path: &Path) -> Result<File, String> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn line(record: &Record) -> Result<String, String> {
+        serde_json::to_string(record)
+            .map(|line| line + "\n")
+            .map_err(|e| format!("failed to encode history record: {}", e))
+    }
+
+    // Metrics and firewall verdicts change every scan and aren't history
+    fn slim(entry: &PortEntry) -> PortEntry {
+        PortEntry {
+            metrics: None,
+            firewall: None,
+            ..entry.clone()
+        }
+    }
+}
+
+// "2h" (that long ago), "03:00" (today, or yesterday if that is still
+// ahead), "2026-10-18 03:00" or an RFC 3339 timestamp
+pub fn parse_time(text: &str, now: DateTime<Local>) -> Result<DateTime<Local>, String> {
+    let text = text.trim();
+    let invalid = || format!("invalid time '{}'", text);
+
+    if text.starts_with(|c: char| c.is_ascii_digit())
+        && text.ends_with(|c: char| c.is_ascii_alphabetic())
+    {
+        if let Ok(ago) = parse_duration(text) {
+            let ago = chrono::Duration::from_std(ago).map_err(|_| invalid())?;
+            return now.checked_sub_signed(ago).ok_or_else(invalid);
+        }
+    }
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time.with_timezone(&Local));
+    }
+
+    for format in [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+    ] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(text, format) {
+            return local(time).ok_or_else(invalid);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return local(date.and_time(NaiveTime::MIN)).ok_or_else(invalid);
+    }
+
+    for format in ["%H:%M:%S", "%H:%M"] {
+        if let Ok(time) = NaiveTime::parse_from_str(text, format) {
+            let today = local(now.date_naive().and_time(time)).ok_or_else(invalid)?;
+            if today <= now {
+                return Ok(today);
+            }
+            let yesterday = now.date_naive().pred_opt().ok_or_else(invalid)?;
+            return local(yesterday.and_time(time)).ok_or_else(invalid);
+        }
+    }
+
+    Err(invalid())
+}
+
+// The earlier of two readings when clocks fall back
+fn local(time: NaiveDateTime) -> Option<DateTime<Local>> {
+    Local.from_local_datetime(&time).earliest()
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::SocketMetrics;
+
+    const LARGE: u64 = 1024 * 1024;
+    const DAY: Duration = Duration::from_secs(86400);
+
+    // An empty directory of its own for each test
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "portkill-history-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn record(time: DateTime<Local>, event: HistoryEvent, port: u16) -> Record {
+        Record {
+            time,
+            event,
+            entry: PortEntry::listener(port, "node"),
+            baseline: false,
+        }
+    }
+
+    fn write(path: &Path, records: &[Record]) {
+        let lines: String = records
+            .iter()
+            .map(|record| Recorder::line(record).unwrap())
+            .collect();
+        std::fs::write(path, lines).unwrap();
+    }
+
+    fn summary(records: &[Record]) -> Vec<(HistoryEvent, u16, bool)> {
+        records
+            .iter()
+            .map(|record| (record.event, record.entry.port, record.baseline))
+            .collect()
+    }
+
+    // 3000 from 3h to 10m ago, 5432 from 3h to 2h ago, 8080 since 2h ago and
+    // 9000 since 30m ago
+    fn sample(now: DateTime<Local>) -> Vec<Record> {
+        let ago = |minutes| now - chrono::Duration::minutes(minutes);
+        vec![
+            record(ago(180), HistoryEvent::Open, 3000),
+            record(ago(180), HistoryEvent::Open, 5432),
+            record(ago(120), HistoryEvent::Close, 5432),
+            record(ago(120), HistoryEvent::Open, 8080),
+            record(ago(30), HistoryEvent::Open, 9000),
+            record(ago(10), HistoryEvent::Close, 3000),
+        ]
+    }
+
+    #[test]
+    fn replay_leaves_open_listeners() {
+        let now = Local::now();
+        let listening = History::replay(&sample(now));
+        let mut ports: Vec<u16> = listening.values().map(|(_, entry)| entry.port).collect();
+        ports.sort();
+        assert_eq!(ports, [8080, 9000]);
+        let key = PortEntry::listener(8080, "node").key();
+        assert_eq!(listening[&key].0, now - chrono::Duration::minutes(120));
+    }
+
+    #[test]
+    fn intervals_and_events() {
+        let now = Local::now();
+        let ago = |minutes| now - chrono::Duration::minutes(minutes);
+        let mut records = sample(now);
+        // Same socket, new owner
+        records.push(Record {
+            entry: PortEntry {
+                pid: Some(4242),
+                ..PortEntry::listener(9000, "java")
+            },
+            ..record(ago(5), HistoryEvent::Change, 9000)
+        });
+        let history = History { records };
+
+        let intervals: Vec<_> = history
+            .intervals()
+            .into_iter()
+            .map(|interval| {
+                (
+                    interval.entry.port,
+                    interval.entry.process_name,
+                    interval.start,
+                    interval.end,
+                )
+            })
+            .collect();
+        assert_eq!(
+            intervals,
+            [
+                (3000, "node".to_string(), ago(180), Some(ago(10))),
+                (5432, "node".to_string(), ago(180), Some(ago(120))),
+                (8080, "node".to_string(), ago(120), None),
+                (9000, "node".to_string(), ago(30), Some(ago(5))),
+                (9000, "java".to_string(), ago(5), None),
+            ]
+        );
+
+        let active: Vec<u16> = history
+            .intervals()
+            .iter()
+            .filter(|interval| interval.is_active_at(ago(60)))
+            .map(|interval| interval.entry.port)
+            .collect();
+        assert_eq!(active, [3000, 8080]);
+
+        let events: Vec<u16> = history
+            .events_between(ago(120), ago(30))
+            .map(|record| record.entry.port)
+            .collect();
+        assert_eq!(events, [5432, 8080, 9000]);
+    }
+
+    #[test]
+    fn compaction_folds_old_events_into_a_baseline() {
+        let dir = scratch("retention");
+        let path = dir.join("history.ndjson");
+        let now = Local::now();
+        write(&path, &sample(now));
+
+        let recorder = Recorder::open_with_limits(&path, Duration::from_secs(3600), LARGE).unwrap();
+        drop(recorder);
+
+        let history = History::load(&path).unwrap();
+        assert_eq!(
+            summary(&history.records),
+            [
+                (HistoryEvent::Open, 3000, true),
+                (HistoryEvent::Open, 8080, true),
+                (HistoryEvent::Open, 9000, false),
+                (HistoryEvent::Close, 3000, false),
+            ]
+        );
+        // Baseline records keep when the listener started
+        assert_eq!(
+            history.records[0].time,
+            now - chrono::Duration::minutes(180)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compaction_keeps_everything_within_an_endless_retention() {
+        let dir = scratch("endless");
+        let path = dir.join("history.ndjson");
+        let records = sample(Local::now());
+        write(&path, &records);
+
+        Recorder::open_with_limits(&path, Duration::MAX, LARGE).unwrap();
+        Recorder::open_with_limits(&path, Duration::from_secs(u64::MAX / 1000), LARGE).unwrap();
+
+        let history = History::load(&path).unwrap();
+        assert_eq!(summary(&history.records), summary(&records));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compaction_keeps_the_file_under_its_size_limit() {
+        let dir = scratch("size");
+        let path = dir.join("history.ndjson");
+        write(&path, &sample(Local::now()));
+
+        // Not even the newest event fits, so only the baseline is left
+        Recorder::open_with_limits(&path, DAY, 1).unwrap();
+
+        let history = History::load(&path).unwrap();
+        assert_eq!(
+            summary(&history.records),
+            [
+                (HistoryEvent::Open, 8080, true),
+                (HistoryEvent::Open, 9000, true),
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recorder_appends_changes() {
+        let dir = scratch("record");
+        let path = dir.join("state").join("history.ndjson");
+        let mut recorder = Recorder::open_with_limits(&path, DAY, LARGE).unwrap();
+
+        let web = PortEntry {
+            metrics: Some(SocketMetrics::default()),
+            ..PortEntry::listener(3000, "node")
+        };
+        let db = PortEntry::listener(5432, "postgres");
+        let connection = PortEntry {
+            state: SocketState::Established,
+            remote_addr: "127.0.0.1:50000".to_string(),
+            inode: Some(1),
+            ..web.clone()
+        };
+
+        assert_eq!(
+            recorder
+                .record(&[web.clone(), db.clone(), connection])
+                .unwrap(),
+            2
+        );
+        assert_eq!(recorder.record(&[web.clone(), db.clone()]).unwrap(), 0);
+        let restarted = PortEntry {
+            pid: Some(4242),
+            ..db.clone()
+        };
+        assert_eq!(
+            recorder.record(&[web.clone(), restarted.clone()]).unwrap(),
+            1
+        );
+        assert_eq!(recorder.record(&[restarted]).unwrap(), 1);
+
+        // The file is locked while recording
+        let second = Recorder::open_with_limits(&path, DAY, LARGE);
+        assert!(second.is_err_and(|e| e.contains("already being recorded")));
+        drop(recorder);
+
+        let history = History::load(&path).unwrap();
+        assert_eq!(
+            summary(&history.records),
+            [
+                (HistoryEvent::Open, 3000, false),
+                (HistoryEvent::Open, 5432, false),
+                (HistoryEvent::Change, 5432, false),
+                (HistoryEvent::Close, 3000, false),
+            ]
+        );
+        assert!(history.records[0].entry.metrics.is_none());
+
+        // A new recorder carries on from what the file says is listening
+        let mut recorder = Recorder::open_with_limits(&path, DAY, LARGE).unwrap();
+        assert_eq!(recorder.record(&[]).unwrap(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parse_times() {
+        let now = local(
+            NaiveDate::from_ymd_opt(2026, 10, 18)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap(),
+        )
+        .unwrap();
+        let at = |day, hour, minute| {
+            local(
+                NaiveDate::from_ymd_opt(2026, 10, day)
+                    .unwrap()
+                    .and_hms_opt(hour, minute, 0)
+                    .unwrap(),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(parse_time("2h", now), Ok(at(18, 10, 0)));
+        assert_eq!(parse_time("90m", now), Ok(at(18, 10, 30)));
+        assert_eq!(parse_time("03:00", now), Ok(at(18, 3, 0)));
+        // Still ahead today, so yesterday's
+        assert_eq!(parse_time("13:00", now), Ok(at(17, 13, 0)));
+        assert_eq!(parse_time("2026-10-01 08:15", now), Ok(at(1, 8, 15)));
+        assert_eq!(parse_time("2026-10-01", now), Ok(at(1, 0, 0)));
+        assert_eq!(
+            parse_time("2026-10-18T10:00:00Z", now),
+            Ok(DateTime::parse_from_rfc3339("2026-10-18T10:00:00Z")
+                .unwrap()
+                .with_timezone(&Local))
+        );
+
+        for text in ["yesterday", "25:00", "2026-13-01", "9999999999h"] {
+            assert_eq!(
+                parse_time(text, now),
+                Err(format!("invalid time '{}'", text)),
+                "{}",
+                text
+            );
+        }
+    }
+}
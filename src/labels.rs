@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::config::Config;
+use crate::scanner::PortEntry;
+
+// Human-readable names for ports, in order of precedence:
+//   1. labels from the config file ("billing-ui")
+//   2. what the owning process looks like ("vite", "postgres 16")
+//   3. /etc/services for the socket's transport ("http")
+//   4. a few common development ports missing from /etc/services
+#[derive(Clone, Debug, PartialEq)]
+pub struct PortLabel {
+    pub text: String,
+    pub source: LabelSource,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LabelSource {
+    Config,
+    Process,
+    Services,
+    BuiltIn,
+}
+
+impl LabelSource {
+    pub fn description(self) -> &'static str {
+        match self {
+            LabelSource::Config => "config file",
+            LabelSource::Process => "process command line",
+            LabelSource::Services => "/etc/services",
+            LabelSource::BuiltIn => "built-in list",
+        }
+    }
+}
+
+const SERVICES_PATH: &str = "/etc/services";
+
+// Canonical service name by (port, transport)
+type Services = HashMap<(u16, String), String>;
+
+const BUILT_IN_LABELS: &[(u16, &str)] = &[
+    (80, "HTTP"),
+    (443, "HTTPS"),
+    (3000, "Node/React"),
+    (3306, "MySQL"),
+    (4200, "Angular"),
+    (5000, "Flask/Django"),
+    (5173, "Vite"),
+    (5432, "PostgreSQL"),
+    (6379, "Redis"),
+    (8080, "Alt HTTP"),
+    (9000, "PHP-FPM"),
+    (27017, "MongoDB"),
+];
+
+// Executables whose name alone says what they serve
+const KNOWN_DAEMONS: &[(&str, &str)] = &[
+    ("apache2", "apache"),
+    ("httpd", "apache"),
+    ("mariadbd", "mariadb"),
+    ("memcached", "memcached"),
+    ("mongod", "mongodb"),
+    ("mysqld", "mysql"),
+    ("nginx", "nginx"),
+    ("php-fpm", "php-fpm"),
+    ("redis-server", "redis"),
+    ("sshd", "ssh"),
+];
+
+// Python servers started as `python -m <module>` or through their own script
+const PYTHON_SERVERS: &[&str] = &[
+    "uvicorn",
+    "gunicorn",
+    "hypercorn",
+    "flask",
+    "http.server",
+    "jupyter",
+];
+
+pub struct LabelResolver;
+
+impl LabelResolver {
+    pub fn resolve(entry: &PortEntry) -> Option<PortLabel> {
+        Self::resolve_all(entry).into_iter().next()
+    }
+
+    // Every label that applies, most specific first
+    pub fn resolve_all(entry: &PortEntry) -> Vec<PortLabel> {
+        Self::resolve_with(entry, Config::global(), Self::services())
+    }
+
+    fn resolve_with(entry: &PortEntry, config: &Config, services: &Services) -> Vec<PortLabel> {
+        let candidates = [
+            config
+                .label_for(entry.port)
+                .map(|text| (text.to_string(), LabelSource::Config)),
+            Self::process_label(entry).map(|text| (text, LabelSource::Process)),
+            services
+                .get(&(entry.port, entry.protocol.transport().to_string()))
+                .map(|text| (text.clone(), LabelSource::Services)),
+            BUILT_IN_LABELS
+                .iter()
+                .find(|(port, _)| *port == entry.port)
+                .map(|(_, text)| (text.to_string(), LabelSource::BuiltIn)),
+        ];
+
+        candidates
+            .into_iter()
+            .flatten()
+            .map(|(text, source)| PortLabel { text, source })
+            .collect()
+    }
+
+    // Service name from /etc/services, e.g. (5432, "tcp") -> "postgresql"
+    pub fn service_name(port: u16, transport: &str) -> Option<&'static str> {
+        Self::services()
+            .get(&(port, transport.to_string()))
+            .map(String::as_str)
+    }
+
+    fn services() -> &'static Services {
+        static SERVICES: OnceLock<Services> = OnceLock::new();
+
+        SERVICES.get_or_init(|| match std::fs::read_to_string(SERVICES_PATH) {
+            Ok(contents) => Self::parse_services(&contents),
+            Err(e) => {
+                log::warn!("Failed to read {}: {}", SERVICES_PATH, e);
+                HashMap::new()
+            }
+        })
+    }
+
+    fn parse_services(contents: &str) -> Services {
+        let mut services = HashMap::new();
+        // <name> <port>/<protocol> [aliases...] [# comment]
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+
+            let (Some(name), Some(port_protocol)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let Some((port, protocol)) = port_protocol.split_once('/') else {
+                continue;
+            };
+            let Ok(port) = port.parse::<u16>() else {
+                continue;
+            };
+
+            // The first entry for a port is its canonical name
+            services
+                .entry((port, protocol.to_lowercase()))
+                .or_insert_with(|| name.to_string());
+        }
+
+        services
+    }
+
+    // Recognizes common dev servers and daemons from the command line
+    pub fn process_label(entry: &PortEntry) -> Option<String> {
+        let args: Vec<&str> = entry.cmdline.split_whitespace().collect();
+        let executable = args
+            .first()
+            .map(|arg| Self::basename(arg))
+            .filter(|name| !name.is_empty())
+            .unwrap_or(entry.process_name.as_str());
+
+        // node_modules/.bin/vite, node_modules/next/dist/bin/next, ...
+        if let Some(package) = args.iter().find_map(|arg| Self::node_package(arg)) {
+            return Some(package.to_string());
+        }
+
+        if executable.starts_with("python") {
+            if let Some(module) = args
+                .windows(2)
+                .find(|pair| pair[0] == "-m")
+                .map(|pair| pair[1])
+            {
+                return Some(module.to_string());
+            }
+            if args.iter().any(|arg| Self::basename(arg) == "manage.py")
+                && args.contains(&"runserver")
+            {
+                return Some("django".to_string());
+            }
+        }
+
+        if let Some(server) = args
+            .iter()
+            .take(2)
+            .map(|arg| Self::basename(arg))
+            .find(|name| PYTHON_SERVERS.contains(name))
+        {
+            return Some(server.to_string());
+        }
+
+        // Debian-style /usr/lib/postgresql/16/bin/postgres
+        if executable == "postgres" || entry.process_name == "postgres" {
+            let version = entry
+                .cmdline
+                .split('/')
+                .skip_while(|segment| *segment != "postgresql")
+                .nth(1)
+                .filter(|version| version.chars().all(|c| c.is_ascii_digit() || c == '.'));
+
+            return Some(match version {
+                Some(version) => format!("postgres {}", version),
+                None => "postgres".to_string(),
+            });
+        }
+
+        KNOWN_DAEMONS
+            .iter()
+            .find(|(name, _)| *name == executable || *name == entry.process_name)
+            .map(|(_, label)| label.to_string())
+    }
+
+    fn node_package(arg: &str) -> Option<&str> {
+        let (_, rest) = arg.rsplit_once("node_modules/")?;
+        let mut segments = rest.split('/');
+
+        let package = match segments.next()? {
+            ".bin" => segments.next()?,
+            // @scope/name
+            scope if scope.starts_with('@') => segments.next()?,
+            name => name,
+        };
+
+        Some(package).filter(|package| !package.is_empty())
+    }
+
+    fn basename(path: &str) -> &str {
+        path.rsplit('/')
+            .next()
+            .unwrap_or(path)
+            .trim_end_matches(':')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::PortRange;
+
+    fn services() -> Services {
+        LabelResolver::parse_services(include_str!("../tests/fixtures/services"))
+    }
+
+    fn labels(entry: &PortEntry, config: &Config) -> Vec<(String, LabelSource)> {
+        LabelResolver::resolve_with(entry, config, &services())
+            .into_iter()
+            .map(|label| (label.text, label.source))
+            .collect()
+    }
+
+    #[test]
+    fn most_specific_label_first() {
+        let config = Config {
+            labels: vec![(PortRange::new(5432, 5432), "billing-db".to_string())],
+            ..Config::default()
+        };
+        let postgres = PortEntry {
+            cmdline: "/usr/lib/postgresql/16/bin/postgres -D /var/lib/postgresql/16/main"
+                .to_string(),
+            ..PortEntry::listener(5432, "postgres")
+        };
+
+        assert_eq!(
+            labels(&postgres, &config),
+            [
+                ("billing-db".to_string(), LabelSource::Config),
+                ("postgres 16".to_string(), LabelSource::Process),
+                ("postgresql".to_string(), LabelSource::Services),
+                ("PostgreSQL".to_string(), LabelSource::BuiltIn),
+            ]
+        );
+
+        let config = Config::default();
+        let unknown = PortEntry::listener(5432, "java");
+        assert_eq!(
+            labels(&unknown, &config)[0],
+            ("postgresql".to_string(), LabelSource::Services)
+        );
+        // Not in the services file
+        let vite = PortEntry::listener(5173, "java");
+        assert_eq!(
+            labels(&vite, &config),
+            [("Vite".to_string(), LabelSource::BuiltIn)]
+        );
+        assert!(labels(&PortEntry::listener(4, "java"), &config).is_empty());
+    }
+
+    #[test]
+    fn services_file() {
+        let services = services();
+        let name = |port, transport: &str| services.get(&(port, transport.to_string())).cloned();
+
+        assert_eq!(name(22, "tcp").as_deref(), Some("ssh"));
+        assert_eq!(name(80, "tcp").as_deref(), Some("http"));
+        assert_eq!(name(53, "udp").as_deref(), Some("domain"));
+        assert_eq!(name(514, "tcp"), None);
+        assert_eq!(services.len(), 8);
+    }
+
+    #[test]
+    fn process_labels() {
+        let label = |cmdline: &str| {
+            LabelResolver::process_label(&PortEntry {
+                cmdline: cmdline.to_string(),
+                ..PortEntry::listener(8000, "")
+            })
+        };
+
+        assert_eq!(
+            label("node /app/node_modules/.bin/vite --port 5173").as_deref(),
+            Some("vite")
+        );
+        assert_eq!(
+            label("node /app/node_modules/next/dist/bin/next dev").as_deref(),
+            Some("next")
+        );
+        assert_eq!(
+            label("python3 -m http.server 8000").as_deref(),
+            Some("http.server")
+        );
+        assert_eq!(
+            label("python manage.py runserver").as_deref(),
+            Some("django")
+        );
+        assert_eq!(
+            label("/venv/bin/uvicorn app:main").as_deref(),
+            Some("uvicorn")
+        );
+        assert_eq!(
+            label("nginx: master process /usr/sbin/nginx").as_deref(),
+            Some("nginx")
+        );
+        assert_eq!(label("./server --port 8000"), None);
+    }
+}
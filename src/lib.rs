@@ -0,0 +1,16 @@
+pub mod audit;
+pub mod config;
+pub mod connections;
+pub mod daemon;
+pub mod declared;
+pub mod firewall;
+pub mod history;
+pub mod labels;
+pub mod metrics;
+pub mod ports;
+pub mod query;
+pub mod resolver;
+pub mod rpc;
+pub mod rules;
+pub mod scanner;
+pub mod stats;
@@ -1,16 +1,44 @@
-mod scanner;
-
-use scanner::{PortEntry, SocketState};
+mod cli;
+mod recording;
+mod settings;
+mod timeline;
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use portkill::audit::{Audit, Finding, Severity};
+use portkill::config::Config;
+use portkill::connections::ListenerConnections;
+use portkill::firewall::Reachability;
+use portkill::labels::LabelResolver;
+use portkill::query::{Field, Query};
+use portkill::resolver::Resolver;
+use portkill::rpc::Client;
+use portkill::scanner::models::RX_QUEUE_WARN_BYTES;
+use portkill::scanner::{
+    self, ChangeKind, PortEntry, SnapshotDiff, SocketKey, SocketMetrics, SocketState, TcpTimer,
+};
+use portkill::stats::{Count, ListenerBacklog, SocketStats};
 
 use eframe::egui;
+use egui_extras::{Column, TableBuilder};
 use log::{info, warn};
+use recording::HistoryRecorder;
+use settings::{FilterPreset, GroupBy, Settings, StateFilter};
+use timeline::Timeline;
 
 fn main() -> eframe::Result<()> {
     env_logger::init();
+
+    if let Some(code) = cli::run(std::env::args().skip(1).collect()) {
+        std::process::exit(code);
+    }
+
     info!("PortKill starting...");
 
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
+            .with_app_id("portkill")
             .with_inner_size([1200.0, 800.0])
             .with_resizable(true),
         ..Default::default()
@@ -22,7 +50,7 @@ fn main() -> eframe::Result<()> {
         Box::new(|cc| {
             // Apply dark theme
             apply_custom_theme(&cc.egui_ctx);
-            Ok(Box::new(App::default()))
+            Ok(Box::new(App::new(cc.storage)))
         }),
     )
 }
@@ -31,13 +59,12 @@ fn apply_custom_theme(ctx: &egui::Context) {
     let mut style = (*ctx.style()).clone();
 
     // Dark theme colors based on design system
-    let bg_primary = egui::Color32::from_rgb(15, 23, 42);      // #0F172A - slate-900
-    let bg_secondary = egui::Color32::from_rgb(30, 41, 59);    // #1E293B - slate-800
-    let bg_card = egui::Color32::from_rgb(51, 65, 85);         // #334155 - slate-700
+    let bg_primary = egui::Color32::from_rgb(15, 23, 42); // #0F172A - slate-900
+    let bg_secondary = egui::Color32::from_rgb(30, 41, 59); // #1E293B - slate-800
+    let bg_card = egui::Color32::from_rgb(51, 65, 85); // #334155 - slate-700
     let text_primary = egui::Color32::from_rgb(248, 250, 252); // #F8FAFC - slate-50
-    let text_muted = egui::Color32::from_rgb(148, 163, 184);   // #94A3B8 - slate-400
-    let accent = egui::Color32::from_rgb(34, 197, 94);         // #22C55E - green-500
-    let danger = egui::Color32::from_rgb(239, 68, 68);         // #EF4444 - red-500
+    let text_muted = egui::Color32::from_rgb(148, 163, 184); // #94A3B8 - slate-400
+    let accent = egui::Color32::from_rgb(34, 197, 94); // #22C55E - green-500
 
     // Apply colors
     style.visuals.dark_mode = true;
@@ -63,89 +90,811 @@ fn apply_custom_theme(ctx: &egui::Context) {
     ctx.set_style(style);
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SortColumn {
+    Port,
+    Protocol,
+    State,
+    Pid,
+    Process,
+    User,
+    Project,
+    Address,
+    Remote,
+    Risk,
+    SendQueue,
+    RecvQueue,
+    Timer,
+    Retransmits,
+    SocketUid,
+    Backlog,
+}
+
+// Sortable columns in display order: (column, header, hover text)
+const SORT_COLUMNS: [(SortColumn, &str, &str); 10] = [
+    (SortColumn::Port, "PORT", "Port number"),
+    (SortColumn::Protocol, "PROTOCOL", "TCP or TCP6"),
+    (SortColumn::State, "STATE", "Socket state"),
+    (SortColumn::Pid, "PID", "Process ID"),
+    (SortColumn::Process, "PROCESS", "Process name"),
+    (SortColumn::User, "USER", "Process owner"),
+    (
+        SortColumn::Project,
+        "PROJECT",
+        "Checkout the process was started from",
+    ),
+    (SortColumn::Address, "ADDRESS", "Local address"),
+    (
+        SortColumn::Remote,
+        "REMOTE",
+        "Remote address of connections",
+    ),
+    (
+        SortColumn::Risk,
+        "RISK",
+        "Most severe security audit finding",
+    ),
+];
+
+// Kernel socket metrics, shown after RISK when picked in the Columns menu
+const METRIC_COLUMNS: [(SortColumn, &str, &str); 6] = [
+    (
+        SortColumn::SendQueue,
+        "SEND-Q",
+        "Bytes not yet acknowledged by the peer; the backlog limit for listeners",
+    ),
+    (
+        SortColumn::RecvQueue,
+        "RECV-Q",
+        "Bytes not yet read by the application; connections waiting to be accepted for listeners",
+    ),
+    (
+        SortColumn::Timer,
+        "TIMER",
+        "Pending TCP timer and when it fires",
+    ),
+    (
+        SortColumn::Retransmits,
+        "RETRANS",
+        "Retransmits of the oldest unacknowledged segment",
+    ),
+    (
+        SortColumn::SocketUid,
+        "UID",
+        "Socket owner as recorded by the kernel",
+    ),
+    (
+        SortColumn::Backlog,
+        "BACKLOG",
+        "How full a listener's accept queue is",
+    ),
+];
+
+impl SortColumn {
+    // Ties are broken by port so the order is stable across refreshes.
+    // `risk` looks up an entry's most severe audit finding.
+    fn compare<F>(self, a: &PortEntry, b: &PortEntry, risk: F) -> Ordering
+    where
+        F: Fn(&PortEntry) -> Option<Severity>,
+    {
+        let metric =
+            |entry: &PortEntry, field: fn(&SocketMetrics) -> u32| entry.metrics.as_ref().map(field);
+        let ordering = match self {
+            SortColumn::Port => a.port.cmp(&b.port),
+            SortColumn::Protocol => a.protocol.to_string().cmp(&b.protocol.to_string()),
+            SortColumn::State => a.state.to_string().cmp(&b.state.to_string()),
+            SortColumn::Pid => a.pid.cmp(&b.pid),
+            SortColumn::Process => a
+                .process_name
+                .to_lowercase()
+                .cmp(&b.process_name.to_lowercase()),
+            SortColumn::User => a.user.cmp(&b.user),
+            SortColumn::Project => {
+                let name = |entry: &PortEntry| {
+                    entry
+                        .project
+                        .as_ref()
+                        .map(|project| project.name.to_lowercase())
+                };
+                name(a).cmp(&name(b))
+            }
+            SortColumn::Address => {
+                let parse = |addr: &str| addr.parse::<std::net::SocketAddr>().ok();
+                parse(&a.local_addr).cmp(&parse(&b.local_addr))
+            }
+            SortColumn::Remote => {
+                let parse = |addr: &str| addr.parse::<std::net::SocketAddr>().ok();
+                parse(&a.remote_addr).cmp(&parse(&b.remote_addr))
+            }
+            SortColumn::Risk => risk(a).cmp(&risk(b)),
+            SortColumn::SendQueue => metric(a, |m| m.tx_queue).cmp(&metric(b, |m| m.tx_queue)),
+            SortColumn::RecvQueue => metric(a, |m| m.rx_queue).cmp(&metric(b, |m| m.rx_queue)),
+            SortColumn::Timer => {
+                let timer = |entry: &PortEntry| {
+                    entry
+                        .metrics
+                        .as_ref()
+                        .map(|m| (m.timer.to_string(), m.timer_expires_ms))
+                };
+                timer(a).cmp(&timer(b))
+            }
+            SortColumn::Retransmits => {
+                metric(a, |m| m.retransmits).cmp(&metric(b, |m| m.retransmits))
+            }
+            SortColumn::SocketUid => metric(a, |m| m.uid).cmp(&metric(b, |m| m.uid)),
+            SortColumn::Backlog => {
+                let percent = |entry: &PortEntry| entry.backlog().map(|backlog| backlog.percent);
+                percent(a)
+                    .partial_cmp(&percent(b))
+                    .unwrap_or(Ordering::Equal)
+            }
+        };
+
+        ordering.then_with(|| a.port.cmp(&b.port))
+    }
+}
+
+enum DisplayRow<'a> {
+    Group {
+        name: &'a str,
+        count: usize,
+        collapsed: bool,
+    },
+    Entry(&'a PortEntry),
+}
+
+// Everything the filtered rows depend on
+#[derive(Clone, PartialEq)]
+struct FilterKey {
+    snapshot: u64,
+    query: String,
+    user: Option<String>,
+    show_listening: bool,
+    show_established: bool,
+    show_all: bool,
+    flagged_only: bool,
+    group_by: GroupBy,
+    sort_column: SortColumn,
+    sort_ascending: bool,
+}
+
+// Table interactions are collected while drawing and applied afterwards
+enum RowAction {
+    Sort(SortColumn),
+    ToggleGroup(String),
+    KillGroup(String),
+    Kill(Box<PortEntry>, bool),
+    Select(SocketKey, bool),
+    SelectAll,
+    ClearSelection,
+    KillSelected(bool),
+}
+
+const QUERY_HELP: &str = "\
+Bare numbers match the port exactly; other words match the process name.
+Fields: port, pid, proto, state, user, proc, cmd, addr, raddr, project
+  port:3000-3999    range (also pid)
+  proc:~^node       regular expression
+  addr:127.0.0.1    local address (IP or IP:port)
+  project:billing   git repo or package the process runs from
+  !proc:postgres    negation
+All terms must match.";
+
+// How long new, changed and gone rows stay highlighted after a scan
+const HIGHLIGHT_DURATION: std::time::Duration = std::time::Duration::from_secs(5);
+
 struct App {
     port_entries: Vec<PortEntry>,
+    // Parsed form of `filter_text`, refreshed whenever the text changes
+    query: Result<Query, String>,
+    query_text: String,
+    has_snapshot: bool,
+    highlights: HashMap<SocketKey, (ChangeKind, std::time::Instant)>,
+    gone_entries: Vec<(PortEntry, std::time::Instant)>,
+    // Bumped whenever `port_entries` or `gone_entries` change
+    snapshot: u64,
+    // Rows passing the filters in display order, as indices into `port_entries`
+    // followed by `gone_entries`; rebuilt only when their `FilterKey` changes
+    filtered: Vec<usize>,
+    filtered_key: Option<FilterKey>,
     filter_text: String,
     show_listening: bool,
     show_established: bool,
     show_all: bool,
+    user_filter: Option<String>,
+    group_by: GroupBy,
+    collapsed_groups: HashSet<String>,
+    sort_column: SortColumn,
+    sort_ascending: bool,
     auto_refresh: bool,
     refresh_interval: std::time::Duration,
     last_refresh: std::time::Instant,
     toasts: Vec<Toast>,
     confirmation_dialog: Option<Confirmation>,
-    is_loading: bool,
+    kill_report: Option<Vec<KillResult>>,
+    // Results of confirmed kills, sent from background threads
+    kills: Vec<std::sync::mpsc::Receiver<Vec<KillResult>>>,
+    selected: HashSet<SocketKey>,
+    selection_anchor: Option<SocketKey>,
+    worker: scanner::ScanWorker,
+    scan_progress: Option<(usize, usize)>,
+    // Security audit of the current snapshot, recomputed after each scan
+    findings: HashMap<SocketKey, Vec<Finding>>,
+    flagged_only: bool,
+    presets: Vec<FilterPreset>,
+    // Name being typed for a new preset, while the inline editor is open
+    new_preset_name: Option<String>,
+    show_stats: bool,
+    // Aggregates of the current snapshot, recomputed after each scan
+    stats: Option<SocketStats>,
+    // Set while ephemeral usage is over the threshold, so it is announced once
+    ephemeral_warned: bool,
+    // Listeners (port, address) whose saturated accept queue was announced
+    saturated_backlogs: HashSet<(u16, String)>,
+    // Listener group of each listener and inbound connection, by socket
+    listener_groups: HashMap<SocketKey, String>,
+    // Connection count and peers label of each listener group
+    listener_peers: HashMap<String, (usize, String)>,
+    // Show host names from /etc/hosts and reverse DNS instead of remote IPs
+    resolve_names: bool,
+    resolver: Resolver,
+    // Kernel metric columns picked in the Columns menu
+    metric_columns: Vec<SortColumn>,
+    // Appends listener changes to the history file, with [history] record
+    recorder: Option<HistoryRecorder>,
+    timeline: Timeline,
 }
 
-impl Default for App {
-    fn default() -> Self {
+impl App {
+    fn new(storage: Option<&dyn eframe::Storage>) -> Self {
+        let settings = Settings::load(storage);
+
         let mut app = Self {
             port_entries: Vec::new(),
-            filter_text: String::new(),
-            show_listening: true,
-            show_established: false,
-            show_all: false,
-            auto_refresh: false,
-            refresh_interval: std::time::Duration::from_secs(5),
+            query: Ok(Query::default()),
+            query_text: String::new(),
+            has_snapshot: false,
+            highlights: HashMap::new(),
+            gone_entries: Vec::new(),
+            snapshot: 0,
+            filtered: Vec::new(),
+            filtered_key: None,
+            filter_text: settings.filter_text,
+            show_listening: settings.state_filter == StateFilter::Listen,
+            show_established: settings.state_filter == StateFilter::Established,
+            show_all: settings.state_filter == StateFilter::All,
+            user_filter: None,
+            group_by: settings.group_by,
+            collapsed_groups: HashSet::new(),
+            sort_column: settings.sort_column,
+            sort_ascending: settings.sort_ascending,
+            auto_refresh: settings.auto_refresh,
+            refresh_interval: std::time::Duration::from_secs(settings.refresh_interval_secs),
             last_refresh: std::time::Instant::now(),
             toasts: Vec::new(),
             confirmation_dialog: None,
-            is_loading: false,
+            kill_report: None,
+            kills: Vec::new(),
+            selected: HashSet::new(),
+            selection_anchor: None,
+            worker: scanner::ScanWorker::spawn(),
+            scan_progress: None,
+            findings: HashMap::new(),
+            flagged_only: false,
+            presets: settings.presets,
+            new_preset_name: None,
+            show_stats: settings.show_stats,
+            stats: None,
+            ephemeral_warned: false,
+            saturated_backlogs: HashSet::new(),
+            listener_groups: HashMap::new(),
+            listener_peers: HashMap::new(),
+            resolve_names: settings.resolve_names,
+            resolver: Resolver::spawn(),
+            metric_columns: settings.metric_columns,
+            recorder: Config::global().history_record.then(HistoryRecorder::spawn),
+            timeline: Timeline::new(),
         };
+        for warning in &Config::global().warnings {
+            app.toasts.push(Toast {
+                message: format!("Config: {}", warning),
+                timestamp: std::time::Instant::now(),
+                is_error: true,
+            });
+        }
         app.refresh();
         app
     }
-}
 
-impl App {
+    fn record_history(&self) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(&self.port_entries);
+        }
+    }
+
+    fn poll_history(&mut self) {
+        let Some(recorder) = &self.recorder else {
+            return;
+        };
+
+        if let Some(message) = recorder.poll() {
+            self.toasts.push(Toast {
+                message,
+                timestamp: std::time::Instant::now(),
+                is_error: true,
+            });
+            self.recorder = None;
+        }
+    }
+
+    fn settings(&self) -> Settings {
+        let state_filter = if self.show_all {
+            StateFilter::All
+        } else if self.show_established {
+            StateFilter::Established
+        } else {
+            StateFilter::Listen
+        };
+
+        Settings {
+            filter_text: self.filter_text.clone(),
+            state_filter,
+            auto_refresh: self.auto_refresh,
+            refresh_interval_secs: self.refresh_interval.as_secs(),
+            group_by: self.group_by,
+            sort_column: self.sort_column,
+            sort_ascending: self.sort_ascending,
+            presets: self.presets.clone(),
+            show_stats: self.show_stats,
+            resolve_names: self.resolve_names,
+            metric_columns: self.metric_columns.clone(),
+        }
+    }
+
+    // Saving under an existing name replaces that preset's filter
+    fn save_preset(&mut self, name: &str) {
+        let name = name.trim();
+        if name.is_empty() {
+            return;
+        }
+
+        match self.presets.iter_mut().find(|preset| preset.name == name) {
+            Some(preset) => preset.query = self.filter_text.clone(),
+            None => self.presets.push(FilterPreset {
+                name: name.to_string(),
+                query: self.filter_text.clone(),
+            }),
+        }
+    }
+
+    fn delete_preset(&mut self, name: &str) {
+        self.presets.retain(|preset| preset.name != name);
+    }
+
+    // One-click chips for saved filters, plus an inline editor to save the current one
+    fn show_preset_bar(&mut self, ui: &mut egui::Ui) {
+        let mut apply = None;
+        let mut delete = None;
+        let mut save = None;
+
+        ui.horizontal_wrapped(|ui| {
+            ui.label(egui::RichText::new("Presets:").color(egui::Color32::from_rgb(148, 163, 184)));
+
+            for preset in &self.presets {
+                let active = preset.query == self.filter_text;
+                let chip = ui
+                    .selectable_label(active, &preset.name)
+                    .on_hover_text(&preset.query);
+                if chip.clicked() {
+                    apply = Some(preset.query.clone());
+                }
+                chip.context_menu(|ui| {
+                    if ui.button("Delete preset").clicked() {
+                        delete = Some(preset.name.clone());
+                        ui.close_menu();
+                    }
+                });
+            }
+
+            match &mut self.new_preset_name {
+                Some(name) => {
+                    let input = ui.add(
+                        egui::TextEdit::singleline(name)
+                            .id(egui::Id::new("preset_name"))
+                            .hint_text("Preset name")
+                            .desired_width(140.0)
+                    );
+
+                    let submitted = input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if submitted || ui.small_button("Save").clicked() {
+                        save = Some(Some(name.clone()));
+                    } else if ui.small_button("Cancel").clicked() {
+                        save = Some(None);
+                    }
+                }
+                None => {
+                    let can_save = !self.filter_text.trim().is_empty();
+                    if ui
+                        .add_enabled(can_save, egui::Button::new("+ Save current").small())
+                        .on_hover_text("Save the current search as a preset (right-click a preset to delete it)")
+                        .clicked()
+                    {
+                        self.new_preset_name = Some(String::new());
+                        ui.memory_mut(|mem| mem.request_focus(egui::Id::new("preset_name")));
+                    }
+                }
+            }
+        });
+
+        if let Some(query) = apply {
+            self.filter_text = query;
+        }
+        if let Some(name) = delete {
+            self.delete_preset(&name);
+        }
+        if let Some(name) = save {
+            if let Some(name) = name {
+                self.save_preset(&name);
+            }
+            self.new_preset_name = None;
+        }
+    }
     fn refresh(&mut self) {
         self.last_refresh = std::time::Instant::now();
-        self.is_loading = true;
         info!("Refreshing port list...");
+        self.worker.request();
+    }
 
-        match scanner::Scanner::scan_tcp() {
-            Ok(mut tcp_entries) => {
-                match scanner::Scanner::scan_tcp6() {
-                    Ok(mut tcp6_entries) => {
-                        tcp_entries.append(&mut tcp6_entries);
-                    }
-                    Err(e) => {
-                        warn!("Failed to scan TCP6: {}", e);
+    fn sync_query(&mut self) {
+        if self.query_text != self.filter_text {
+            self.query = Query::parse(&self.filter_text);
+            self.query_text = self.filter_text.clone();
+        }
+    }
+
+    fn is_loading(&self) -> bool {
+        self.worker.is_scanning()
+    }
+
+    fn poll_scan(&mut self) {
+        for event in self.worker.poll() {
+            match event {
+                scanner::ScanEvent::Progress { done, total } => {
+                    self.scan_progress = Some((done, total));
+                }
+                scanner::ScanEvent::Finished(Ok(entries)) => {
+                    if self.has_snapshot {
+                        self.record_changes(&SnapshotDiff::between(&self.port_entries, &entries));
                     }
+                    self.port_entries = entries;
+                    self.snapshot += 1;
+                    self.has_snapshot = true;
+                    self.prune_selection();
+                    self.findings = self
+                        .port_entries
+                        .iter()
+                        .map(|entry| (entry.key(), Audit::entry(entry)))
+                        .filter(|(_, findings)| !findings.is_empty())
+                        .collect();
+                    self.update_stats();
+                    self.update_connections();
+                    self.record_history();
+                    self.scan_progress = None;
                 }
-
-                for entry in &mut tcp_entries {
-                    scanner::ProcessInfo::map_pid_to_info(entry);
+                scanner::ScanEvent::Finished(Err(e)) => {
+                    // Keep showing the last good snapshot
+                    warn!("Failed to scan TCP: {}", e);
+                    self.toasts.push(Toast {
+                        message: format!("Failed to scan ports: {}", e),
+                        timestamp: std::time::Instant::now(),
+                        is_error: true,
+                    });
+                    self.scan_progress = None;
                 }
+                scanner::ScanEvent::Cancelled => {
+                    self.scan_progress = None;
+                }
+            }
+        }
+    }
 
-                self.port_entries = tcp_entries;
-                self.is_loading = false;
+    fn update_stats(&mut self) {
+        let stats = SocketStats::collect(&self.port_entries);
+        let warning = stats.warning(Config::global().ephemeral_warn_percent);
+
+        match warning {
+            Some(warning) if !self.ephemeral_warned => {
+                self.toasts.push(Toast {
+                    message: warning,
+                    timestamp: std::time::Instant::now(),
+                    is_error: true,
+                });
+                self.ephemeral_warned = true;
             }
-            Err(e) => {
-                warn!("Failed to scan TCP: {}", e);
+            Some(_) => {}
+            None => self.ephemeral_warned = false,
+        }
+
+        // Announce each listener once when its queue crosses the threshold
+        let threshold = Config::global().backlog_warn_percent;
+        let mut saturated = HashSet::new();
+        for listener in stats
+            .backlogs
+            .iter()
+            .filter(|listener| listener.backlog.percent >= threshold)
+        {
+            let key = (listener.port, listener.local_addr.clone());
+            if !self.saturated_backlogs.contains(&key) {
                 self.toasts.push(Toast {
-                    message: format!("Failed to scan ports: {}", e),
+                    message: listener.warning(),
                     timestamp: std::time::Instant::now(),
                     is_error: true,
                 });
-                self.is_loading = false;
             }
+            saturated.insert(key);
+        }
+        self.saturated_backlogs = saturated;
+
+        self.stats = Some(stats);
+    }
+
+    fn update_connections(&mut self) {
+        self.listener_groups.clear();
+        self.listener_peers.clear();
+
+        for group in ListenerConnections::group(&self.port_entries) {
+            let process = match group.listener.pid {
+                Some(pid) => format!("{} ({})", group.listener.process_name, pid),
+                None => "unknown process".to_string(),
+            };
+            let name = format!("{} on {}", process, group.listener.local_addr);
+
+            self.listener_groups
+                .insert(group.listener.key(), name.clone());
+            for connection in &group.connections {
+                self.listener_groups.insert(connection.key(), name.clone());
+            }
+            self.listener_peers
+                .insert(name, (group.connections.len(), group.peers_label()));
         }
     }
 
-    fn apply_filters(&self) -> Vec<PortEntry> {
-        self.port_entries
+    fn visible_metric_columns(&self) -> Vec<(SortColumn, &'static str, &'static str)> {
+        METRIC_COLUMNS
+            .into_iter()
+            .filter(|(column, _, _)| self.metric_columns.contains(column))
+            .collect()
+    }
+
+    fn connection_summary(&self, entry: &PortEntry) -> Option<&(usize, String)> {
+        let name = self.listener_groups.get(&entry.key())?;
+        self.listener_peers
+            .get(name)
+            .filter(|(count, _)| *count > 0)
+    }
+
+    // Side panel with socket counts and ephemeral port usage
+    fn show_stats_panel(&self, ctx: &egui::Context) {
+        let Some(stats) = &self.stats else {
+            return;
+        };
+        let muted = egui::Color32::from_rgb(148, 163, 184);
+
+        egui::SidePanel::right("stats_panel")
+            .default_width(280.0)
+            .show(ctx, |ui| {
+                ui.add_space(16.0);
+                ui.heading("Socket Stats");
+                ui.label(egui::RichText::new(format!("{} sockets", stats.total)).color(muted));
+                ui.add_space(12.0);
+
+                let ephemeral = &stats.ephemeral;
+                let threshold = Config::global().ephemeral_warn_percent;
+                let fraction = (ephemeral.percent / 100.0) as f32;
+                let color = if ephemeral.percent >= threshold {
+                    egui::Color32::from_rgb(239, 68, 68)
+                } else if ephemeral.percent >= threshold / 2.0 {
+                    egui::Color32::from_rgb(234, 179, 8)
+                } else {
+                    egui::Color32::from_rgb(34, 197, 94)
+                };
+
+                ui.label(egui::RichText::new("Ephemeral ports").strong());
+                ui.label(
+                    egui::RichText::new(format!(
+                        "{} ({} ports)",
+                        ephemeral.range, ephemeral.capacity
+                    ))
+                    .color(muted)
+                    .size(12.0),
+                );
+                ui.add(
+                    egui::ProgressBar::new(fraction)
+                        .fill(color)
+                        .text(format!("{:.1}%", ephemeral.percent)),
+                )
+                .on_hover_text(format!(
+                    "Connections to the busiest remote endpoint as a share of \
+                     ip_local_port_range; warns at {:.0}%",
+                    threshold
+                ));
+                if let Some(remote) = &ephemeral.busiest_remote {
+                    ui.label(format!(
+                        "Busiest remote: {} ({})",
+                        remote.name, remote.count
+                    ));
+                }
+                ui.label(format!("Local ports in use: {}", ephemeral.ports_in_use));
+                ui.label(format!("In TIME_WAIT: {}", ephemeral.time_wait));
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    if !stats.backlogs.is_empty() {
+                        Self::show_backlogs(ui, &stats.backlogs);
+                    }
+                    Self::show_counts(ui, "By state", "stats_by_state", &stats.by_state);
+                    Self::show_counts(
+                        ui,
+                        "By remote endpoint",
+                        "stats_by_remote",
+                        &stats.by_remote,
+                    );
+                    Self::show_counts(ui, "By process", "stats_by_process", &stats.by_process);
+                });
+            });
+    }
+
+    fn show_backlogs(ui: &mut egui::Ui, backlogs: &[ListenerBacklog]) {
+        ui.add_space(12.0);
+        ui.label(egui::RichText::new("Accept queues").strong());
+        for listener in backlogs {
+            ui.label(
+                egui::RichText::new(format!("{} on {}", listener.process, listener.local_addr))
+                    .size(12.0),
+            );
+            let backlog = &listener.backlog;
+            ui.add(
+                egui::ProgressBar::new((backlog.percent / 100.0) as f32)
+                    .fill(Self::backlog_color(backlog.percent))
+                    .text(format!("{} of {}", backlog.queued, backlog.limit)),
+            )
+            .on_hover_text(listener.warning());
+        }
+    }
+
+    fn backlog_color(percent: f64) -> egui::Color32 {
+        let threshold = Config::global().backlog_warn_percent;
+        if percent >= threshold {
+            egui::Color32::from_rgb(239, 68, 68)
+        } else if percent >= threshold / 2.0 {
+            egui::Color32::from_rgb(234, 179, 8)
+        } else {
+            egui::Color32::from_rgb(34, 197, 94)
+        }
+    }
+
+    fn show_counts(ui: &mut egui::Ui, heading: &str, id: &str, counts: &[Count]) {
+        const TOP: usize = 10;
+
+        ui.add_space(12.0);
+        ui.label(egui::RichText::new(heading).strong());
+        egui::Grid::new(id)
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                for count in counts.iter().take(TOP) {
+                    ui.label(&count.name);
+                    ui.label(count.count.to_string());
+                    ui.end_row();
+                }
+                if counts.len() > TOP {
+                    let rest: usize = counts[TOP..].iter().map(|count| count.count).sum();
+                    ui.label(
+                        egui::RichText::new(format!("({} more)", counts.len() - TOP))
+                            .color(egui::Color32::from_rgb(148, 163, 184)),
+                    );
+                    ui.label(rest.to_string());
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn record_changes(&mut self, diff: &SnapshotDiff) {
+        let now = std::time::Instant::now();
+
+        for change in &diff.changes {
+            self.highlights
+                .insert(change.entry.key(), (change.kind, now));
+            if change.kind == ChangeKind::Gone {
+                self.gone_entries.push((change.entry.clone(), now));
+            }
+        }
+    }
+
+    fn expire_highlights(&mut self) {
+        self.highlights
+            .retain(|_, (_, since)| since.elapsed() < HIGHLIGHT_DURATION);
+        let gone = self.gone_entries.len();
+        self.gone_entries
+            .retain(|(_, since)| since.elapsed() < HIGHLIGHT_DURATION);
+        if self.gone_entries.len() != gone {
+            self.snapshot += 1;
+        }
+    }
+
+    fn change_for(&self, entry: &PortEntry) -> Option<(ChangeKind, f32)> {
+        self.highlights.get(&entry.key()).map(|(kind, since)| {
+            let remaining = 1.0 - since.elapsed().as_secs_f32() / HIGHLIGHT_DURATION.as_secs_f32();
+            (*kind, remaining.clamp(0.0, 1.0))
+        })
+    }
+
+    fn filter_key(&self) -> FilterKey {
+        FilterKey {
+            snapshot: self.snapshot,
+            query: self.query_text.clone(),
+            user: self.user_filter.clone(),
+            show_listening: self.show_listening,
+            show_established: self.show_established,
+            show_all: self.show_all,
+            flagged_only: self.flagged_only,
+            group_by: self.group_by,
+            sort_column: self.sort_column,
+            sort_ascending: self.sort_ascending,
+        }
+    }
+
+    // Filtering and sorting every frame would be wasted on an unchanged table
+    fn update_filtered(&mut self) {
+        let key = self.filter_key();
+        if self.filtered_key.as_ref() != Some(&key) {
+            self.filtered = self.filter_rows();
+            self.filtered_key = Some(key);
+        }
+    }
+
+    fn entry_at(&self, index: usize) -> &PortEntry {
+        match index.checked_sub(self.port_entries.len()) {
+            Some(gone) => &self.gone_entries[gone].0,
+            None => &self.port_entries[index],
+        }
+    }
+
+    fn filtered_entries(&self) -> impl Iterator<Item = &PortEntry> {
+        self.filtered.iter().map(|&index| self.entry_at(index))
+    }
+
+    // Gone rows are kept on screen while their highlight fades out
+    fn filter_rows(&self) -> Vec<usize> {
+        // A `state:` term in the query overrides the LISTEN/ESTABLISHED/ALL choice
+        let state_in_query = matches!(&self.query, Ok(query) if query.constrains(Field::State));
+
+        let mut rows: Vec<usize> = self
+            .port_entries
             .iter()
-            .filter(|entry| {
-                let port_match = entry.port.to_string().contains(&self.filter_text);
-                let name_match = entry
-                    .process_name
-                    .to_lowercase()
-                    .contains(&self.filter_text.to_lowercase());
+            .chain(self.gone_entries.iter().map(|(entry, _)| entry))
+            .enumerate()
+            .filter(|(_, entry)| {
+                // An invalid query is reported under the search box and filters nothing
+                let query_match = match &self.query {
+                    Ok(query) => query.matches(entry),
+                    Err(_) => true,
+                };
+
+                let user_match = self
+                    .user_filter
+                    .as_ref()
+                    .is_none_or(|user| &entry.user == user);
+
+                // Info-level findings alone don't count as flagged
+                let flag_match = !self.flagged_only
+                    || self
+                        .max_severity(entry)
+                        .is_some_and(|severity| severity >= Severity::Low);
 
-                let text_match = port_match || name_match;
+                let text_match = query_match && user_match && flag_match;
 
-                if self.show_all {
+                if self.show_all || state_in_query {
                     text_match
                 } else if self.show_listening {
                     entry.state == SocketState::Listen && text_match
@@ -155,94 +904,962 @@ impl App {
                     text_match
                 }
             })
-            .cloned()
-            .collect()
+            .map(|(index, _)| index)
+            .collect();
+
+        // Grouped by listener, each shown listener brings its connections along
+        if self.group_by == GroupBy::Listener {
+            let shown: HashSet<SocketKey> = rows
+                .iter()
+                .map(|&index| self.entry_at(index).key())
+                .collect();
+            let listeners: HashSet<&str> = rows
+                .iter()
+                .map(|&index| self.entry_at(index))
+                .filter(|entry| entry.state == SocketState::Listen)
+                .map(|entry| self.group_name(entry))
+                .collect();
+
+            let connections: Vec<usize> = self
+                .port_entries
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| {
+                    entry.state != SocketState::Listen && !shown.contains(&entry.key())
+                })
+                .filter(|(_, entry)| listeners.contains(self.group_name(entry)))
+                .map(|(index, _)| index)
+                .collect();
+            rows.extend(connections);
+        }
+
+        rows.sort_by(|&a, &b| {
+            let ordering = self
+                .sort_column
+                .compare(self.entry_at(a), self.entry_at(b), |entry| {
+                    self.max_severity(entry)
+                });
+            if self.sort_ascending {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        });
+
+        if self.group_by != GroupBy::None {
+            // Stable sort keeps the column order within each group
+            rows.sort_by(|&a, &b| {
+                self.group_name(self.entry_at(a))
+                    .cmp(self.group_name(self.entry_at(b)))
+            });
+        }
+
+        rows
     }
 
-    fn show_confirmation(&mut self, entry: &PortEntry, use_sigkill: bool) {
+    fn known_users(&self) -> Vec<String> {
+        let mut users: Vec<String> = self
+            .port_entries
+            .iter()
+            .filter(|entry| !entry.user.is_empty())
+            .map(|entry| entry.user.clone())
+            .collect();
+        users.sort();
+        users.dedup();
+        users
+    }
+
+    // One target per process, listing every selected port it holds
+    fn kill_targets(entries: &[&PortEntry]) -> Vec<KillTarget> {
+        let mut targets: Vec<KillTarget> = Vec::new();
+
+        for entry in entries {
+            let pid = match entry.pid {
+                Some(pid) => pid,
+                None => continue,
+            };
+
+            match targets.iter_mut().find(|target| target.pid == pid) {
+                Some(target) => {
+                    if !target.ports.contains(&entry.port) {
+                        target.ports.push(entry.port);
+                    }
+                }
+                None => targets.push(KillTarget {
+                    pid,
+                    process_name: entry.process_name.clone(),
+                    ports: vec![entry.port],
+                }),
+            }
+        }
+
+        targets
+    }
+
+    fn show_confirmation(
+        &mut self,
+        targets: Vec<KillTarget>,
+        protected: Vec<KillTarget>,
+        use_sigkill: bool,
+    ) {
         let signal = if use_sigkill {
-            "SIGKILL (9)"
+            "SIGKILL (9)".to_string()
         } else {
-            "SIGTERM (15)"
+            let config = Config::global();
+            let signal = format!("{} ({})", config.kill_signal, config.kill_signal as i32);
+            if config.grace_period.is_zero() {
+                signal
+            } else {
+                format!("{}, then SIGKILL after {:?}", signal, config.grace_period)
+            }
+        };
+
+        let message = match targets.as_slice() {
+            [target] => format!(
+                "Send {} to {} (PID {}) on port {}?",
+                signal,
+                target.process_name,
+                target.pid,
+                target.ports_label()
+            ),
+            _ => format!("Send {} to {} processes?", signal, targets.len()),
         };
-        let message = format!(
-            "Send {} to {} (PID {}) on port {}?",
-            signal, entry.process_name, entry.pid.unwrap_or(0), entry.port
-        );
+
+        // Whoever is still connected loses their session
+        let connections = ListenerConnections::group(&self.port_entries)
+            .into_iter()
+            .filter(|group| {
+                !group.connections.is_empty()
+                    && targets
+                        .iter()
+                        .any(|target| Some(target.pid) == group.listener.pid)
+            })
+            .map(|group| {
+                format!(
+                    "{} (PID {}) port {} has {} open connections from {}",
+                    group.listener.process_name,
+                    group.listener.pid.unwrap_or_default(),
+                    group.listener.port,
+                    group.connections.len(),
+                    group.peers_label()
+                )
+            })
+            .collect();
 
         self.confirmation_dialog = Some(Confirmation {
-            entry: entry.clone(),
+            targets,
+            protected,
             message,
+            connections,
             use_sigkill,
         });
     }
 
+    // Connecting to the daemon and waiting out grace periods would freeze the
+    // UI, so the kills are sent from a background thread
     fn confirm_kill(&mut self) {
         if let Some(confirmation) = self.confirmation_dialog.take() {
-            match if confirmation.use_sigkill {
-                scanner::Killer::kill_sigkill(confirmation.entry.pid.unwrap())
-            } else {
-                scanner::Killer::kill_sigterm(confirmation.entry.pid.unwrap())
-            } {
-                Ok(msg) => {
-                    self.toasts.push(Toast {
-                        message: msg,
-                        timestamp: std::time::Instant::now(),
-                        is_error: false,
-                    });
-                    // Refresh after kill
-                    self.refresh();
-                }
-                Err(e) => {
-                    self.toasts.push(Toast {
-                        message: format!("Failed: {}", e),
-                        timestamp: std::time::Instant::now(),
-                        is_error: true,
-                    });
-                }
+            let (results_tx, results_rx) = std::sync::mpsc::channel();
+            std::thread::Builder::new()
+                .name("portkill-kill".to_string())
+                .spawn(move || {
+                    let _ = results_tx.send(Self::kill_targets_now(confirmation));
+                })
+                .expect("failed to spawn kill thread");
+            self.kills.push(results_rx);
+        }
+    }
+
+    fn kill_targets_now(confirmation: Confirmation) -> Vec<KillResult> {
+        // Through the daemon when one runs, so its kill policy applies
+        let mut daemon = Client::connect();
+        confirmation
+            .targets
+            .into_iter()
+            .map(|target| {
+                let result = match &mut daemon {
+                    Some(client) => {
+                        client.kill(target.pid, confirmation.use_sigkill.then_some("SIGKILL"))
+                    }
+                    None if confirmation.use_sigkill => scanner::Killer::kill_sigkill(target.pid),
+                    None => scanner::Killer::terminate(target.pid)
+                        .map(|termination| termination.message),
+                };
+                KillResult { target, result }
+            })
+            .collect()
+    }
+
+    fn poll_kills(&mut self) {
+        let mut finished = Vec::new();
+        self.kills.retain(|kills| match kills.try_recv() {
+            Ok(results) => {
+                finished.push(results);
+                false
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => true,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => false,
+        });
+
+        for results in finished {
+            self.report_kills(results);
+        }
+    }
+
+    fn report_kills(&mut self, results: Vec<KillResult>) {
+        let succeeded = results.iter().filter(|kill| kill.result.is_ok()).count();
+
+        if let [kill] = results.as_slice() {
+            match &kill.result {
+                Ok(msg) => self.toasts.push(Toast {
+                    message: msg.clone(),
+                    timestamp: std::time::Instant::now(),
+                    is_error: false,
+                }),
+                Err(e) => self.toasts.push(Toast {
+                    message: format!("Failed: {}", e),
+                    timestamp: std::time::Instant::now(),
+                    is_error: true,
+                }),
             }
+        } else {
+            self.toasts.push(Toast {
+                message: format!("Signalled {} of {} processes", succeeded, results.len()),
+                timestamp: std::time::Instant::now(),
+                is_error: succeeded < results.len(),
+            });
+            self.kill_report = Some(results);
+        }
+
+        if succeeded > 0 {
+            // Refresh after kill
+            self.refresh();
         }
     }
 
     fn kill_entry(&mut self, entry: &PortEntry, use_sigkill: bool) {
-        let pid = entry.pid.unwrap();
+        let pid = match entry.pid {
+            Some(pid) => pid,
+            None => return,
+        };
 
         if !scanner::Killer::can_kill(pid) {
             self.toasts.push(Toast {
-                message: format!("Cannot kill system process (PID {})", pid),
+                message: format!("Cannot kill protected process (PID {})", pid),
+                timestamp: std::time::Instant::now(),
+                is_error: true,
+            });
+            return;
+        }
+
+        self.show_confirmation(Self::kill_targets(&[entry]), Vec::new(), use_sigkill);
+    }
+
+    fn kill_selected(&mut self, use_sigkill: bool) {
+        let entries: Vec<&PortEntry> = self
+            .port_entries
+            .iter()
+            .filter(|entry| self.selected.contains(&entry.key()))
+            .collect();
+
+        let targets = Self::kill_targets(&entries);
+        self.kill_targets_checked(targets, use_sigkill);
+    }
+
+    // Asks to kill `targets`, leaving out protected processes
+    fn kill_targets_checked(&mut self, targets: Vec<KillTarget>, use_sigkill: bool) {
+        let (targets, protected): (Vec<KillTarget>, Vec<KillTarget>) = targets
+            .into_iter()
+            .partition(|target| scanner::Killer::can_kill(target.pid));
+
+        if targets.is_empty() {
+            self.toasts.push(Toast {
+                message: "No killable processes to kill".to_string(),
                 timestamp: std::time::Instant::now(),
                 is_error: true,
             });
             return;
         }
 
-        self.show_confirmation(entry, use_sigkill);
+        self.show_confirmation(targets, protected, use_sigkill);
+    }
+
+    // Selection follows sockets across refreshes and drops the ones that closed
+    fn prune_selection(&mut self) {
+        let live: HashSet<SocketKey> = self.port_entries.iter().map(|entry| entry.key()).collect();
+        self.selected.retain(|key| live.contains(key));
+    }
+
+    fn select_all(&mut self) {
+        let gone: HashSet<SocketKey> = self
+            .gone_entries
+            .iter()
+            .map(|(entry, _)| entry.key())
+            .collect();
+        let keys: Vec<SocketKey> = self
+            .filtered_entries()
+            .filter(|entry| entry.pid.is_some())
+            .map(|entry| entry.key())
+            .filter(|key| !gone.contains(key))
+            .collect();
+        self.selected.extend(keys);
     }
 
-    fn find_well_known_label(&self, port: u16) -> Option<&'static str> {
-        match port {
-            80 => Some("HTTP"),
-            443 => Some("HTTPS"),
-            3000 => Some("Node/React"),
-            5432 => Some("PostgreSQL"),
-            3306 => Some("MySQL"),
-            6379 => Some("Redis"),
-            27017 => Some("MongoDB"),
-            8080 => Some("Alt HTTP"),
-            5000 => Some("Flask/Django"),
-            9000 => Some("PHP-FPM"),
-            _ => None,
+    // Shift-click selects everything between the last clicked row and this one
+    fn click_selection(&mut self, key: SocketKey, shift: bool, order: &[SocketKey]) {
+        if shift {
+            let anchor = self
+                .selection_anchor
+                .as_ref()
+                .and_then(|anchor| order.iter().position(|k| k == anchor));
+            let clicked = order.iter().position(|k| *k == key);
+
+            if let (Some(anchor), Some(clicked)) = (anchor, clicked) {
+                let (start, end) = (anchor.min(clicked), anchor.max(clicked));
+                self.selected.extend(order[start..=end].iter().cloned());
+                return;
+            }
+        }
+
+        if !self.selected.remove(&key) {
+            self.selected.insert(key.clone());
+        }
+        self.selection_anchor = Some(key);
+    }
+
+    fn max_severity(&self, entry: &PortEntry) -> Option<Severity> {
+        self.findings
+            .get(&entry.key())
+            .and_then(|findings| Audit::max_severity(findings))
+    }
+
+    fn severity_color(severity: Severity) -> egui::Color32 {
+        match severity {
+            Severity::High => egui::Color32::from_rgb(220, 38, 38), // red-600
+            Severity::Medium => egui::Color32::from_rgb(234, 88, 12), // orange-600
+            Severity::Low => egui::Color32::from_rgb(202, 138, 4),  // yellow-600
+            Severity::Info => egui::Color32::from_rgb(71, 85, 105), // slate-600
         }
     }
 
     fn get_state_color(&self, state: &SocketState) -> egui::Color32 {
         match state {
-            SocketState::Listen => egui::Color32::from_rgb(34, 197, 94),      // green-500
+            SocketState::Listen => egui::Color32::from_rgb(34, 197, 94), // green-500
             SocketState::Established => egui::Color32::from_rgb(234, 179, 8), // yellow-500
-            SocketState::TimeWait => egui::Color32::from_rgb(148, 163, 184),  // slate-400
-            SocketState::CloseWait => egui::Color32::from_rgb(251, 146, 60),  // orange-400
-            _ => egui::Color32::from_rgb(148, 163, 184),                      // slate-400
+            SocketState::TimeWait => egui::Color32::from_rgb(148, 163, 184), // slate-400
+            SocketState::CloseWait => egui::Color32::from_rgb(251, 146, 60), // orange-400
+            _ => egui::Color32::from_rgb(148, 163, 184),                 // slate-400
+        }
+    }
+
+    // Empty when the entry has no user or project to group by
+    fn group_name<'a>(&'a self, entry: &'a PortEntry) -> &'a str {
+        match self.group_by {
+            GroupBy::None => "",
+            GroupBy::User => &entry.user,
+            GroupBy::Project => entry
+                .project
+                .as_ref()
+                .map(|project| project.name.as_str())
+                .unwrap_or_default(),
+            // Connections no listener accepted, i.e. outgoing ones, share ""
+            GroupBy::Listener => self
+                .listener_groups
+                .get(&entry.key())
+                .map(String::as_str)
+                .unwrap_or_default(),
+        }
+    }
+
+    fn display_rows(&self) -> Vec<DisplayRow<'_>> {
+        let entries: Vec<&PortEntry> = self.filtered_entries().collect();
+
+        if self.group_by == GroupBy::None {
+            return entries.into_iter().map(DisplayRow::Entry).collect();
+        }
+
+        let mut rows = Vec::new();
+        for group in entries.chunk_by(|a, b| self.group_name(a) == self.group_name(b)) {
+            let name = self.group_name(group[0]);
+            let collapsed = self.collapsed_groups.contains(name);
+
+            rows.push(DisplayRow::Group {
+                name,
+                count: group.len(),
+                collapsed,
+            });
+            if !collapsed {
+                rows.extend(group.iter().map(|entry| DisplayRow::Entry(entry)));
+            }
         }
+        rows
+    }
+
+    fn show_table(&mut self, ui: &mut egui::Ui) {
+        let rows = self.display_rows();
+        let mut action = None;
+
+        // Row order used for shift-click range selection
+        let order: Vec<SocketKey> = rows
+            .iter()
+            .filter_map(|row| match row {
+                DisplayRow::Entry(entry) => Some(entry.key()),
+                DisplayRow::Group { .. } => None,
+            })
+            .collect();
+        let all_selected = !order.is_empty() && order.iter().all(|key| self.selected.contains(key));
+
+        if !self.selected.is_empty() {
+            self.show_selection_bar(ui, self.filtered.len(), &mut action);
+            ui.add_space(8.0);
+        }
+
+        // Leave room for the footer below the table
+        let max_height = ui.available_height() - 60.0;
+
+        let metric_columns = self.visible_metric_columns();
+        let mut table = TableBuilder::new(ui)
+            .id_salt("port_table")
+            .striped(true)
+            .resizable(true)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .min_scrolled_height(0.0)
+            .max_scroll_height(max_height)
+            .column(Column::exact(24.0))
+            .column(Column::initial(140.0).at_least(60.0))
+            .column(Column::initial(80.0).at_least(50.0))
+            .column(Column::initial(140.0).at_least(80.0))
+            .column(Column::initial(70.0).at_least(40.0))
+            .column(Column::initial(160.0).at_least(60.0).clip(true))
+            .column(Column::initial(110.0).at_least(50.0).clip(true))
+            .column(Column::initial(130.0).at_least(50.0).clip(true))
+            .column(Column::initial(190.0).at_least(80.0).clip(true))
+            .column(Column::initial(190.0).at_least(80.0).clip(true))
+            .column(Column::initial(70.0).at_least(50.0));
+        for _ in &metric_columns {
+            table = table.column(Column::initial(80.0).at_least(50.0));
+        }
+
+        table
+            .column(Column::remainder().at_least(170.0))
+            .header(28.0, |mut header| {
+                header.col(|ui| {
+                    let mut checked = all_selected;
+                    if ui
+                        .checkbox(&mut checked, "")
+                        .on_hover_text("Select all matching the filter")
+                        .clicked()
+                    {
+                        action = Some(if checked {
+                            RowAction::SelectAll
+                        } else {
+                            RowAction::ClearSelection
+                        });
+                    }
+                });
+                for (column, title, hover) in SORT_COLUMNS
+                    .into_iter()
+                    .chain(metric_columns.iter().copied())
+                {
+                    header.col(|ui| {
+                        let arrow = match (self.sort_column == column, self.sort_ascending) {
+                            (true, true) => " ▲",
+                            (true, false) => " ▼",
+                            (false, _) => "",
+                        };
+                        let title = egui::RichText::new(format!("{}{}", title, arrow))
+                            .strong()
+                            .size(13.0);
+
+                        if ui
+                            .add(egui::Button::new(title).frame(false))
+                            .on_hover_text(hover)
+                            .clicked()
+                        {
+                            action = Some(RowAction::Sort(column));
+                        }
+                    });
+                }
+                header.col(|ui| {
+                    ui.label(egui::RichText::new("ACTIONS").strong().size(13.0));
+                });
+            })
+            .body(|body| {
+                body.rows(32.0, rows.len(), |mut row| match rows[row.index()] {
+                    DisplayRow::Group {
+                        name,
+                        count,
+                        collapsed,
+                    } => {
+                        row.col(|_| {});
+                        row.col(|ui| {
+                            let icon = if collapsed { "▸" } else { "▾" };
+                            let title = match (name.is_empty(), self.group_by) {
+                                (true, GroupBy::Project) => "no project",
+                                (true, GroupBy::Listener) => "no listener (outgoing)",
+                                (true, _) => "unknown",
+                                (false, _) => name,
+                            };
+                            let label =
+                                egui::RichText::new(format!("{} {} ({})", icon, title, count))
+                                    .strong();
+
+                            let mut response = ui.add(egui::Button::new(label).frame(false));
+                            if let Some((connections, peers)) = self.listener_peers.get(name) {
+                                response = response.on_hover_text(match connections {
+                                    0 => "No connections".to_string(),
+                                    _ => format!("{} connections from {}", connections, peers),
+                                });
+                            }
+                            if response.clicked() {
+                                action = Some(RowAction::ToggleGroup(name.to_string()));
+                            }
+                        });
+                        for _ in 2..=SORT_COLUMNS.len() + metric_columns.len() {
+                            row.col(|_| {});
+                        }
+                        row.col(|ui| {
+                            if !name.is_empty()
+                                && ui
+                                    .add(
+                                        egui::Button::new("Kill all")
+                                            .fill(egui::Color32::from_rgb(185, 28, 28)),
+                                    )
+                                    .on_hover_text(format!("Kill every process in {}", name))
+                                    .clicked()
+                            {
+                                action = Some(RowAction::KillGroup(name.to_string()));
+                            }
+                        });
+                    }
+                    DisplayRow::Entry(entry) => {
+                        self.entry_cells(&mut row, entry, &metric_columns, &mut action)
+                    }
+                });
+            });
+
+        match action {
+            Some(RowAction::Sort(column)) => {
+                if self.sort_column == column {
+                    self.sort_ascending = !self.sort_ascending;
+                } else {
+                    self.sort_column = column;
+                    self.sort_ascending = true;
+                }
+            }
+            Some(RowAction::ToggleGroup(name)) => {
+                let was_collapsed = self.collapsed_groups.remove(&name);
+                if !was_collapsed {
+                    self.collapsed_groups.insert(name);
+                }
+            }
+            Some(RowAction::KillGroup(name)) => {
+                let gone: HashSet<SocketKey> = self
+                    .gone_entries
+                    .iter()
+                    .map(|(entry, _)| entry.key())
+                    .collect();
+                let entries: Vec<&PortEntry> = self
+                    .filtered_entries()
+                    .filter(|entry| self.group_name(entry) == name && !gone.contains(&entry.key()))
+                    .collect();
+                let targets = Self::kill_targets(&entries);
+                self.kill_targets_checked(targets, false);
+            }
+            Some(RowAction::Kill(entry, use_sigkill)) => self.kill_entry(&entry, use_sigkill),
+            Some(RowAction::Select(key, shift)) => self.click_selection(key, shift, &order),
+            Some(RowAction::SelectAll) => self.select_all(),
+            Some(RowAction::ClearSelection) => {
+                self.selected.clear();
+                self.selection_anchor = None;
+            }
+            Some(RowAction::KillSelected(use_sigkill)) => self.kill_selected(use_sigkill),
+            None => {}
+        }
+    }
+
+    fn show_selection_bar(
+        &self,
+        ui: &mut egui::Ui,
+        matching: usize,
+        action: &mut Option<RowAction>,
+    ) {
+        egui::Frame::new()
+            .inner_margin(egui::vec2(12.0, 8.0))
+            .fill(egui::Color32::from_rgb(51, 65, 85))
+            .corner_radius(6.0)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(
+                        egui::RichText::new(format!("{} selected", self.selected.len())).strong(),
+                    );
+
+                    if ui
+                        .button(format!("Select all matching ({})", matching))
+                        .clicked()
+                    {
+                        *action = Some(RowAction::SelectAll);
+                    }
+                    if ui.button("Clear").clicked() {
+                        *action = Some(RowAction::ClearSelection);
+                    }
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui
+                            .add(
+                                egui::Button::new("SIGKILL selected")
+                                    .fill(egui::Color32::from_rgb(127, 29, 29)),
+                            )
+                            .clicked()
+                        {
+                            *action = Some(RowAction::KillSelected(true));
+                        }
+                        if ui
+                            .add(
+                                egui::Button::new("Kill selected")
+                                    .fill(egui::Color32::from_rgb(185, 28, 28)),
+                            )
+                            .clicked()
+                        {
+                            *action = Some(RowAction::KillSelected(false));
+                        }
+                    });
+                });
+            });
+    }
+
+    fn entry_cells(
+        &self,
+        row: &mut egui_extras::TableRow<'_, '_>,
+        entry: &PortEntry,
+        metric_columns: &[(SortColumn, &str, &str)],
+        action: &mut Option<RowAction>,
+    ) {
+        let labels = LabelResolver::resolve_all(entry);
+        let state_color = self.get_state_color(&entry.state);
+        let change = self.change_for(entry);
+        let is_gone = matches!(change, Some((ChangeKind::Gone, _)));
+        let health_issues = entry.health_issues();
+
+        let highlight = change.map(|(kind, strength)| {
+            let tint = match kind {
+                ChangeKind::New => egui::Color32::from_rgb(21, 128, 61), // green-700
+                ChangeKind::Changed => egui::Color32::from_rgb(161, 98, 7), // yellow-700
+                ChangeKind::Gone => egui::Color32::from_rgb(127, 29, 29), // red-900
+            };
+            tint.gamma_multiply(0.6 * strength)
+        });
+        let paint_highlight = |ui: &mut egui::Ui| {
+            if let Some(fill) = highlight {
+                ui.painter().rect_filled(ui.max_rect(), 0.0, fill);
+            }
+        };
+
+        // Selection (shift-click selects a range)
+        row.col(|ui| {
+            paint_highlight(ui);
+            if is_gone || entry.pid.is_none() {
+                return;
+            }
+
+            let key = entry.key();
+            let mut checked = self.selected.contains(&key);
+            if ui.checkbox(&mut checked, "").clicked() {
+                let shift = ui.input(|i| i.modifiers.shift);
+                *action = Some(RowAction::Select(key, shift));
+            }
+        });
+
+        // Port
+        row.col(|ui| {
+            paint_highlight(ui);
+            ui.label(
+                egui::RichText::new(format!("{}", entry.port))
+                    .size(14.0)
+                    .color(egui::Color32::from_rgb(34, 197, 94)),
+            );
+            if let Some(label) = labels.first() {
+                let tooltip = labels
+                    .iter()
+                    .map(|label| format!("{} (from {})", label.text, label.source.description()))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                ui.label(
+                    egui::RichText::new(format!("({})", label.text))
+                        .size(12.0)
+                        .color(egui::Color32::from_rgb(148, 163, 184)),
+                )
+                .on_hover_text(tooltip);
+            }
+        });
+
+        // Protocol
+        row.col(|ui| {
+            paint_highlight(ui);
+            ui.label(format!("{}", entry.protocol));
+        });
+
+        // State with color indicator
+        row.col(|ui| {
+            paint_highlight(ui);
+            ui.label(egui::RichText::new("●").color(state_color));
+            ui.label(format!("{}", entry.state));
+            if entry.state == SocketState::Listen {
+                if let Some((connections, peers)) = self.connection_summary(entry) {
+                    ui.label(
+                        egui::RichText::new(format!("{} conn", connections))
+                            .size(11.0)
+                            .color(egui::Color32::from_rgb(56, 189, 248)),
+                    )
+                    .on_hover_text(format!("Connected: {}", peers));
+                }
+            }
+            if let Some(backlog) = entry.backlog() {
+                if backlog.percent >= Config::global().backlog_warn_percent {
+                    egui::Frame::new()
+                        .fill(Self::backlog_color(backlog.percent))
+                        .corner_radius(4.0)
+                        .inner_margin(egui::vec2(6.0, 1.0))
+                        .show(ui, |ui| {
+                            ui.label(
+                                egui::RichText::new(format!("BACKLOG {:.0}%", backlog.percent))
+                                    .size(11.0)
+                                    .strong()
+                                    .color(egui::Color32::WHITE),
+                            );
+                        })
+                        .response
+                        .on_hover_text(format!(
+                            "{} of {} connections wait to be accepted; \
+                             the port accepts handshakes but the application doesn't respond",
+                            backlog.queued, backlog.limit
+                        ));
+                }
+            }
+            if !health_issues.is_empty() {
+                ui.label(egui::RichText::new("⚠").color(egui::Color32::from_rgb(251, 146, 60)))
+                    .on_hover_text(health_issues.join("\n"));
+            }
+        });
+
+        // PID
+        row.col(|ui| {
+            paint_highlight(ui);
+            match entry.pid {
+                Some(pid) => ui.label(format!("{}", pid)),
+                None => ui.label("-"),
+            };
+        });
+
+        // Process
+        row.col(|ui| {
+            paint_highlight(ui);
+            let process = ui.label(
+                egui::RichText::new(&entry.process_name)
+                    .color(egui::Color32::from_rgb(248, 250, 252)),
+            );
+            if !entry.cmdline.is_empty() {
+                process.on_hover_text(&entry.cmdline);
+            }
+        });
+
+        // User (effective), flagged when it differs from the real user
+        row.col(|ui| {
+            paint_highlight(ui);
+            let user_label = ui.label(
+                egui::RichText::new(&entry.user).color(egui::Color32::from_rgb(148, 163, 184)),
+            );
+            if entry.is_setuid() {
+                let hover = format!(
+                    "setuid: effective user {}, real user {}",
+                    entry.user, entry.real_user
+                );
+                user_label.on_hover_text(&hover);
+                ui.label(egui::RichText::new("⚑").color(egui::Color32::from_rgb(234, 179, 8)))
+                    .on_hover_text(hover);
+            }
+        });
+
+        // Project
+        row.col(|ui| {
+            paint_highlight(ui);
+            if let Some(project) = &entry.project {
+                ui.label(
+                    egui::RichText::new(&project.name)
+                        .color(egui::Color32::from_rgb(148, 163, 184)),
+                )
+                .on_hover_text(&project.root);
+            }
+        });
+
+        // Address, tagged with whether the firewall lets other hosts in
+        row.col(|ui| {
+            paint_highlight(ui);
+            ui.label(
+                egui::RichText::new(&entry.local_addr)
+                    .color(egui::Color32::from_rgb(148, 163, 184)),
+            );
+            if let Some(verdict) = &entry.firewall {
+                let (tag, color) = match verdict.reachability {
+                    Reachability::Reachable => ("OPEN", egui::Color32::from_rgb(249, 115, 22)),
+                    Reachability::Blocked => ("FW", egui::Color32::from_rgb(34, 197, 94)),
+                    Reachability::Unknown => ("?", egui::Color32::from_rgb(100, 116, 139)),
+                };
+                ui.label(egui::RichText::new(tag).size(10.0).strong().color(color))
+                    .on_hover_text(format!(
+                        "Firewall: {}\n{}",
+                        verdict.reachability, verdict.rule
+                    ));
+            }
+        });
+
+        // Remote address; listeners have none
+        row.col(|ui| {
+            paint_highlight(ui);
+            if entry.remote_addr.ends_with(":0") {
+                ui.label(egui::RichText::new("-").color(egui::Color32::from_rgb(100, 116, 139)));
+                return;
+            }
+
+            let text = if self.resolve_names {
+                self.resolver.display(&entry.remote_addr)
+            } else {
+                entry.remote_addr.clone()
+            };
+            let response =
+                ui.label(egui::RichText::new(&text).color(egui::Color32::from_rgb(148, 163, 184)));
+            if text != entry.remote_addr {
+                response.on_hover_text(&entry.remote_addr);
+            }
+        });
+
+        // Risk badge
+        row.col(|ui| {
+            paint_highlight(ui);
+            let Some(findings) = self.findings.get(&entry.key()) else {
+                return;
+            };
+            let Some(severity) = Audit::max_severity(findings) else {
+                return;
+            };
+
+            let tooltip = findings
+                .iter()
+                .map(|finding| format!("[{}] {}", finding.severity, finding.message))
+                .collect::<Vec<_>>()
+                .join("\n");
+            egui::Frame::new()
+                .fill(Self::severity_color(severity))
+                .corner_radius(4.0)
+                .inner_margin(egui::vec2(6.0, 1.0))
+                .show(ui, |ui| {
+                    ui.label(
+                        egui::RichText::new(severity.to_string().to_uppercase())
+                            .size(11.0)
+                            .strong()
+                            .color(egui::Color32::WHITE),
+                    );
+                })
+                .response
+                .on_hover_text(tooltip);
+        });
+
+        // Kernel metrics
+        for &(column, _, _) in metric_columns {
+            row.col(|ui| {
+                paint_highlight(ui);
+                let Some(metrics) = &entry.metrics else {
+                    ui.label(
+                        egui::RichText::new("-").color(egui::Color32::from_rgb(100, 116, 139)),
+                    );
+                    return;
+                };
+
+                let listening = entry.state == SocketState::Listen;
+                let (text, unhealthy) = match column {
+                    SortColumn::SendQueue => (metrics.tx_queue.to_string(), false),
+                    SortColumn::RecvQueue => (
+                        metrics.rx_queue.to_string(),
+                        metrics.rx_queue > 0
+                            && (listening || metrics.rx_queue >= RX_QUEUE_WARN_BYTES),
+                    ),
+                    SortColumn::Timer => match metrics.timer {
+                        TcpTimer::None => ("-".to_string(), false),
+                        timer => (format!("{} {}ms", timer, metrics.timer_expires_ms), false),
+                    },
+                    SortColumn::Retransmits => (
+                        metrics.retransmits.to_string(),
+                        !listening && metrics.retransmits > 0,
+                    ),
+                    SortColumn::Backlog => match entry.backlog() {
+                        Some(backlog) => (
+                            format!(
+                                "{}/{} ({:.0}%)",
+                                backlog.queued, backlog.limit, backlog.percent
+                            ),
+                            backlog.percent >= Config::global().backlog_warn_percent,
+                        ),
+                        None => ("-".to_string(), false),
+                    },
+                    _ => (metrics.uid.to_string(), false),
+                };
+
+                let color = if unhealthy {
+                    egui::Color32::from_rgb(248, 113, 113)
+                } else {
+                    egui::Color32::from_rgb(203, 213, 225)
+                };
+                let label = ui.label(egui::RichText::new(text).monospace().color(color));
+                if unhealthy {
+                    label.on_hover_text(health_issues.join("\n"));
+                }
+            });
+        }
+
+        // Actions
+        row.col(|ui| {
+            paint_highlight(ui);
+
+            if is_gone {
+                ui.label(
+                    egui::RichText::new("Gone")
+                        .color(egui::Color32::from_rgb(248, 113, 113))
+                        .size(12.0),
+                );
+                return;
+            }
+
+            let pid = match entry.pid {
+                Some(pid) => pid,
+                None => return,
+            };
+
+            if scanner::Killer::can_kill(pid) {
+                // Configured signal (SIGTERM by default)
+                let kill_btn = ui.add_sized(
+                    [70.0, 24.0],
+                    egui::Button::new("Kill").fill(egui::Color32::from_rgb(185, 28, 28)),
+                );
+                if kill_btn.clicked() {
+                    *action = Some(RowAction::Kill(Box::new(entry.clone()), false));
+                }
+
+                // SIGKILL button
+                let sigkill_btn = ui.add_sized(
+                    [75.0, 24.0],
+                    egui::Button::new("SIGKILL").fill(egui::Color32::from_rgb(127, 29, 29)),
+                );
+                if sigkill_btn.clicked() {
+                    *action = Some(RowAction::Kill(Box::new(entry.clone()), true));
+                }
+            } else {
+                ui.label(
+                    egui::RichText::new("System Process")
+                        .color(egui::Color32::from_rgb(100, 116, 139))
+                        .size(12.0),
+                );
+            }
+        });
     }
 
     fn handle_keyboard_shortcuts(&mut self, ctx: &egui::Context) {
@@ -260,6 +1877,12 @@ impl App {
         if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
             if self.confirmation_dialog.is_some() {
                 self.confirmation_dialog = None;
+            } else if self.kill_report.is_some() {
+                self.kill_report = None;
+            } else if self.timeline.open {
+                self.timeline.open = false;
+            } else if self.new_preset_name.is_some() {
+                self.new_preset_name = None;
             } else if !self.filter_text.is_empty() {
                 self.filter_text.clear();
             }
@@ -274,31 +1897,73 @@ struct Toast {
     is_error: bool,
 }
 
+#[derive(Clone)]
+struct KillTarget {
+    pid: u32,
+    process_name: String,
+    ports: Vec<u16>,
+}
+
+impl KillTarget {
+    fn ports_label(&self) -> String {
+        let ports: Vec<String> = self.ports.iter().map(|port| port.to_string()).collect();
+        ports.join(", ")
+    }
+}
+
+struct KillResult {
+    target: KillTarget,
+    result: Result<String, String>,
+}
+
 #[derive(Clone)]
 struct Confirmation {
-    entry: PortEntry,
+    targets: Vec<KillTarget>,
+    // Selected processes left out because `Killer::can_kill` refuses them
+    protected: Vec<KillTarget>,
     message: String,
+    // Listeners of the targets that still have clients connected
+    connections: Vec<String>,
     use_sigkill: bool,
 }
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Collect results from the background scan
+        self.poll_scan();
+        self.poll_kills();
+        self.poll_history();
+        self.expire_highlights();
+
         // Handle keyboard shortcuts
         self.handle_keyboard_shortcuts(ctx);
 
-        // Auto-refresh
-        if self.auto_refresh && self.last_refresh.elapsed() >= self.refresh_interval {
+        // Auto-refresh (skipped while a scan is still running)
+        if self.auto_refresh
+            && !self.is_loading()
+            && self.last_refresh.elapsed() >= self.refresh_interval
+        {
             self.refresh();
         }
 
+        if self.show_stats {
+            self.show_stats_panel(ctx);
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            let filtered = self.apply_filters();
+            self.sync_query();
+            self.update_filtered();
 
             // Header
             ui.add_space(16.0);
             ui.horizontal(|ui| {
                 ui.heading(egui::RichText::new("PortKill").size(28.0));
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.toggle_value(&mut self.show_stats, "Stats")
+                        .on_hover_text("Socket counts and ephemeral port usage");
+                    ui.toggle_value(&mut self.timeline.open, "History")
+                        .on_hover_text("What listened on which ports over time");
+                    ui.add_space(8.0);
                     ui.label(
                         egui::RichText::new(format!("Ports: {}", self.port_entries.len()))
                             .color(egui::Color32::from_rgb(148, 163, 184))
@@ -311,19 +1976,19 @@ impl eframe::App for App {
             ui.add_space(16.0);
 
             // Search and controls
-            egui::Frame::none()
+            egui::Frame::new()
                 .inner_margin(12.0)
                 .fill(egui::Color32::from_rgb(30, 41, 59))
-                .rounding(8.0)
+                .corner_radius(8.0)
                 .show(ui, |ui| {
                     ui.horizontal(|ui| {
                         ui.label("🔍");
                         let search_response = ui.add_sized(
-                            [350.0, 24.0],
+                            [450.0, 24.0],
                             egui::TextEdit::singleline(&mut self.filter_text)
-                                .hint_text("Search by port number or process name...")
+                                .hint_text("3000, node, port:3000-3999 state:listen proc:~node !user:root")
                                 .id(egui::Id::new("search_box")),
-                        );
+                        ).on_hover_text(QUERY_HELP);
 
                         if search_response.has_focus() {
                             ui.label(egui::RichText::new("Esc to clear")
@@ -342,18 +2007,35 @@ impl eframe::App for App {
                         }
 
                         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                            ui.checkbox(&mut self.auto_refresh, "Auto-refresh (5s)");
+                            let mut secs = self.refresh_interval.as_secs();
+                            let interval = ui.add(
+                                egui::DragValue::new(&mut secs)
+                                    .range(Settings::MIN_REFRESH_INTERVAL_SECS..=Settings::MAX_REFRESH_INTERVAL_SECS)
+                                    .suffix("s")
+                            ).on_hover_text("Auto-refresh interval");
+                            if interval.changed() {
+                                self.refresh_interval = std::time::Duration::from_secs(secs);
+                            }
+                            ui.checkbox(&mut self.auto_refresh, "Auto-refresh every");
                         });
                     });
+
+                    if let Err(e) = &self.query {
+                        ui.label(
+                            egui::RichText::new(format!("Invalid filter: {}", e))
+                                .color(egui::Color32::from_rgb(248, 113, 113))
+                                .size(12.0)
+                        );
+                    }
                 });
 
             ui.add_space(12.0);
 
             // Filters
-            egui::Frame::none()
+            egui::Frame::new()
                 .inner_margin(12.0)
                 .fill(egui::Color32::from_rgb(30, 41, 59))
-                .rounding(8.0)
+                .corner_radius(8.0)
                 .show(ui, |ui| {
                     ui.horizontal(|ui| {
                         ui.label(egui::RichText::new("Filter:").color(egui::Color32::from_rgb(148, 163, 184)));
@@ -376,30 +2058,101 @@ impl eframe::App for App {
                             self.show_all = true;
                         }
 
+                        ui.add_space(16.0);
+                        ui.label(egui::RichText::new("User:").color(egui::Color32::from_rgb(148, 163, 184)));
+
+                        let users = self.known_users();
+                        egui::ComboBox::from_id_salt("user_filter")
+                            .selected_text(self.user_filter.as_deref().unwrap_or("All users"))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.user_filter, None, "All users");
+                                for user in users {
+                                    let label = user.clone();
+                                    ui.selectable_value(&mut self.user_filter, Some(user), label);
+                                }
+                            });
+
+                        ui.checkbox(&mut self.flagged_only, "Flagged only")
+                            .on_hover_text("Only listeners with security audit findings");
+
+                        ui.checkbox(&mut self.resolve_names, "Resolve names")
+                            .on_hover_text("Show remote hosts by name from /etc/hosts and reverse DNS");
+
+                        ui.menu_button("Columns", |ui| {
+                            for (column, title, hover) in METRIC_COLUMNS {
+                                let mut shown = self.metric_columns.contains(&column);
+                                if ui.checkbox(&mut shown, title).on_hover_text(hover).changed() {
+                                    self.metric_columns.retain(|&c| c != column);
+                                    if shown {
+                                        self.metric_columns.push(column);
+                                    }
+                                }
+                            }
+                        });
+
+                        ui.label(egui::RichText::new("Group by:").color(egui::Color32::from_rgb(148, 163, 184)));
+                        egui::ComboBox::from_id_salt("group_by")
+                            .selected_text(match self.group_by {
+                                GroupBy::None => "None",
+                                GroupBy::User => "User",
+                                GroupBy::Project => "Project",
+                                GroupBy::Listener => "Listener",
+                            })
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.group_by, GroupBy::None, "None");
+                                ui.selectable_value(&mut self.group_by, GroupBy::User, "User");
+                                ui.selectable_value(&mut self.group_by, GroupBy::Project, "Project");
+                                ui.selectable_value(&mut self.group_by, GroupBy::Listener, "Listener")
+                                    .on_hover_text("Listening sockets with the connections they accepted");
+                            });
+
                         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                            if self.is_loading {
-                                ui.spinner();
+                            if self.is_loading() {
+                                if ui.small_button("Cancel").clicked() {
+                                    self.worker.cancel();
+                                }
+
+                                match self.scan_progress {
+                                    Some((done, total)) if total > 0 => {
+                                        ui.add(
+                                            egui::ProgressBar::new(done as f32 / total as f32)
+                                                .desired_width(120.0)
+                                                .text(format!("{}/{} processes", done, total)),
+                                        );
+                                    }
+                                    _ => {
+                                        ui.spinner();
+                                    }
+                                }
                                 ui.label("Scanning...");
                             }
                         });
                     });
+
+                    ui.add_space(4.0);
+                    self.show_preset_bar(ui);
                 });
 
             ui.add_space(16.0);
 
             // Table
-            if filtered.is_empty() {
+            if self.filtered.is_empty() {
                 ui.add_space(40.0);
                 ui.vertical_centered(|ui| {
                     ui.label(egui::RichText::new("No ports found")
                         .size(18.0)
                         .color(egui::Color32::from_rgb(148, 163, 184)));
                     ui.add_space(8.0);
-                    if !self.filter_text.is_empty() || self.show_listening || self.show_established {
+                    if !self.filter_text.is_empty()
+                        || self.show_listening
+                        || self.show_established
+                        || self.user_filter.is_some()
+                    {
                         ui.label("Try adjusting your filters or search terms");
                         ui.add_space(12.0);
                         if ui.button("Clear Filters").clicked() {
                             self.filter_text.clear();
+                            self.user_filter = None;
                             self.show_listening = true;
                             self.show_established = false;
                             self.show_all = false;
@@ -407,138 +2160,7 @@ impl eframe::App for App {
                     }
                 });
             } else {
-                // Table header
-                egui::Frame::none()
-                    .inner_margin(egui::vec2(12.0, 8.0))
-                    .fill(egui::Color32::from_rgb(51, 65, 85))
-                    .rounding(6.0)
-                    .show(ui, |ui| {
-                        ui.horizontal(|ui| {
-                            ui.label(egui::RichText::new("PORT").strong().size(13.0))
-                                .on_hover_text("Port number");
-                            ui.add_space(20.0);
-
-                            ui.label(egui::RichText::new("PROTOCOL").strong().size(13.0))
-                                .on_hover_text("TCP or TCP6");
-                            ui.add_space(20.0);
-
-                            ui.label(egui::RichText::new("STATE").strong().size(13.0))
-                                .on_hover_text("Socket state");
-                            ui.add_space(30.0);
-
-                            ui.label(egui::RichText::new("PID").strong().size(13.0))
-                                .on_hover_text("Process ID");
-                            ui.add_space(20.0);
-
-                            ui.label(egui::RichText::new("PROCESS").strong().size(13.0))
-                                .on_hover_text("Process name");
-                            ui.add_space(90.0);
-
-                            ui.label(egui::RichText::new("USER").strong().size(13.0))
-                                .on_hover_text("Process owner");
-                            ui.add_space(40.0);
-
-                            ui.label(egui::RichText::new("ACTIONS").strong().size(13.0));
-                        });
-                    });
-
-                ui.add_space(8.0);
-
-                // Table rows
-                egui::ScrollArea::vertical()
-                    .max_height(ui.available_height() - 60.0)
-                    .show(ui, |ui| {
-                        for entry in &filtered {
-                            let well_known = self.find_well_known_label(entry.port);
-                            let state_color = self.get_state_color(&entry.state);
-
-                            egui::Frame::none()
-                                .inner_margin(egui::vec2(12.0, 10.0))
-                                .fill(egui::Color32::from_rgb(30, 41, 59))
-                                .rounding(6.0)
-                                .show(ui, |ui| {
-                                    ui.horizontal(|ui| {
-                                        // Port
-                                        ui.label(
-                                            egui::RichText::new(format!("{}", entry.port))
-                                                .size(14.0)
-                                                .color(egui::Color32::from_rgb(34, 197, 94))
-                                        );
-                                        if let Some(label) = well_known {
-                                            ui.label(
-                                                egui::RichText::new(format!("({})", label))
-                                                    .size(12.0)
-                                                    .color(egui::Color32::from_rgb(148, 163, 184))
-                                            );
-                                        }
-                                        ui.add_space(20.0);
-
-                                        // Protocol
-                                        ui.label(format!("{}", entry.protocol));
-                                        ui.add_space(20.0);
-
-                                        // State with color indicator
-                                        ui.label(egui::RichText::new("●").color(state_color));
-                                        ui.label(format!("{}", entry.state));
-                                        ui.add_space(20.0);
-
-                                        // PID
-                                        ui.label(format!("{}", entry.pid.unwrap_or(0)));
-                                        ui.add_space(20.0);
-
-                                        // Process
-                                        ui.label(
-                                            egui::RichText::new(&entry.process_name)
-                                                .color(egui::Color32::from_rgb(248, 250, 252))
-                                        );
-                                        ui.add_space(20.0);
-
-                                        // User
-                                        ui.label(
-                                            egui::RichText::new(&entry.user)
-                                                .color(egui::Color32::from_rgb(148, 163, 184))
-                                        );
-
-                                        // Actions (right-aligned)
-                                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                                            let can_kill = scanner::Killer::can_kill(entry.pid.unwrap());
-
-                                            if can_kill {
-                                                // SIGKILL button
-                                                let sigkill_btn = ui.add_sized(
-                                                    [75.0, 28.0],
-                                                    egui::Button::new("SIGKILL")
-                                                        .fill(egui::Color32::from_rgb(127, 29, 29))
-                                                );
-                                                if sigkill_btn.clicked() {
-                                                    self.kill_entry(entry, true);
-                                                }
-
-                                                ui.add_space(6.0);
-
-                                                // SIGTERM button
-                                                let kill_btn = ui.add_sized(
-                                                    [70.0, 28.0],
-                                                    egui::Button::new("Kill")
-                                                        .fill(egui::Color32::from_rgb(185, 28, 28))
-                                                );
-                                                if kill_btn.clicked() {
-                                                    self.kill_entry(entry, false);
-                                                }
-                                            } else {
-                                                ui.label(
-                                                    egui::RichText::new("System Process")
-                                                        .color(egui::Color32::from_rgb(100, 116, 139))
-                                                        .size(12.0)
-                                                );
-                                            }
-                                        });
-                                    });
-                                });
-
-                            ui.add_space(6.0);
-                        }
-                    });
+                self.show_table(ui);
 
                 ui.add_space(12.0);
 
@@ -547,7 +2169,7 @@ impl eframe::App for App {
                 ui.add_space(8.0);
                 ui.horizontal(|ui| {
                     ui.label(
-                        egui::RichText::new(format!("Showing {} of {} ports", filtered.len(), self.port_entries.len()))
+                        egui::RichText::new(format!("Showing {} of {} ports", self.filtered.len(), self.port_entries.len()))
                             .color(egui::Color32::from_rgb(148, 163, 184))
                     );
 
@@ -578,11 +2200,14 @@ impl eframe::App for App {
                 };
 
                 egui::Area::new(egui::Id::new(format!("toast_{}", index)))
-                    .anchor(egui::Align2::RIGHT_BOTTOM, [-20.0, -20.0 - (index as f32 * 60.0)])
+                    .anchor(
+                        egui::Align2::RIGHT_BOTTOM,
+                        [-20.0, -20.0 - (index as f32 * 60.0)],
+                    )
                     .show(ctx, |ui| {
-                        egui::Frame::none()
+                        egui::Frame::new()
                             .fill(bg_color)
-                            .rounding(8.0)
+                            .corner_radius(8.0)
                             .inner_margin(egui::vec2(16.0, 12.0))
                             .show(ui, |ui| {
                                 ui.horizontal(|ui| {
@@ -600,33 +2225,81 @@ impl eframe::App for App {
         }
 
         // Confirmation dialog
-        if self.confirmation_dialog.is_some() {
-            let message = self.confirmation_dialog.as_ref().unwrap().message.clone();
+        if let Some(confirmation) = self.confirmation_dialog.clone() {
             let mut should_cancel = false;
             let mut should_confirm = false;
 
             egui::Window::new("⚠ Confirm Action")
                 .collapsible(false)
                 .resizable(false)
-                .fixed_size([450.0, 150.0])
+                .default_width(450.0)
                 .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
                 .show(ctx, |ui| {
                     ui.add_space(8.0);
-                    ui.label(egui::RichText::new(&message).size(15.0));
+                    ui.label(egui::RichText::new(&confirmation.message).size(15.0));
+
+                    for connections in &confirmation.connections {
+                        ui.add_space(4.0);
+                        ui.label(
+                            egui::RichText::new(connections)
+                                .color(egui::Color32::from_rgb(234, 179, 8))
+                                .size(13.0),
+                        );
+                    }
+
+                    if confirmation.targets.len() > 1 {
+                        ui.add_space(8.0);
+                        egui::ScrollArea::vertical()
+                            .max_height(240.0)
+                            .show(ui, |ui| {
+                                for target in &confirmation.targets {
+                                    ui.label(format!(
+                                        "{} (PID {}) - port {}",
+                                        target.process_name,
+                                        target.pid,
+                                        target.ports_label()
+                                    ));
+                                }
+                            });
+                    }
+
+                    if !confirmation.protected.is_empty() {
+                        ui.add_space(8.0);
+                        let skipped: Vec<String> = confirmation
+                            .protected
+                            .iter()
+                            .map(|target| format!("{} (PID {})", target.process_name, target.pid))
+                            .collect();
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "Skipping system processes: {}",
+                                skipped.join(", ")
+                            ))
+                            .color(egui::Color32::from_rgb(148, 163, 184))
+                            .size(12.0),
+                        );
+                    }
+
                     ui.add_space(16.0);
 
                     ui.horizontal(|ui| {
-                        if ui.add_sized([100.0, 32.0], egui::Button::new("Cancel")).clicked() {
+                        if ui
+                            .add_sized([100.0, 32.0], egui::Button::new("Cancel"))
+                            .clicked()
+                        {
                             should_cancel = true;
                         }
 
                         ui.add_space(8.0);
 
-                        if ui.add_sized(
-                            [100.0, 32.0],
-                            egui::Button::new("Confirm")
-                                .fill(egui::Color32::from_rgb(185, 28, 28))
-                        ).clicked() {
+                        if ui
+                            .add_sized(
+                                [100.0, 32.0],
+                                egui::Button::new("Confirm")
+                                    .fill(egui::Color32::from_rgb(185, 28, 28)),
+                            )
+                            .clicked()
+                        {
                             should_confirm = true;
                         }
                     });
@@ -640,9 +2313,81 @@ impl eframe::App for App {
             }
         }
 
+        self.timeline.show(ctx);
+
+        // Per-target results of a bulk kill
+        let mut close_report = false;
+        if let Some(report) = &self.kill_report {
+            egui::Window::new("Kill Results")
+                .collapsible(false)
+                .resizable(true)
+                .default_width(450.0)
+                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .show(ctx, |ui| {
+                    egui::ScrollArea::vertical()
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            for kill in report {
+                                ui.horizontal(|ui| {
+                                    match &kill.result {
+                                        Ok(_) => ui.label(
+                                            egui::RichText::new("✓")
+                                                .color(egui::Color32::from_rgb(34, 197, 94)),
+                                        ),
+                                        Err(_) => ui.label(
+                                            egui::RichText::new("⚠")
+                                                .color(egui::Color32::from_rgb(239, 68, 68)),
+                                        ),
+                                    };
+                                    ui.label(format!(
+                                        "{} (PID {}) - port {}",
+                                        kill.target.process_name,
+                                        kill.target.pid,
+                                        kill.target.ports_label()
+                                    ));
+                                    if let Err(e) = &kill.result {
+                                        ui.label(
+                                            egui::RichText::new(e)
+                                                .color(egui::Color32::from_rgb(148, 163, 184))
+                                                .size(12.0),
+                                        );
+                                    }
+                                });
+                            }
+                        });
+
+                    ui.add_space(8.0);
+                    if ui
+                        .add_sized([100.0, 32.0], egui::Button::new("Close"))
+                        .clicked()
+                    {
+                        close_report = true;
+                    }
+                });
+        }
+        if close_report {
+            self.kill_report = None;
+        }
+
         // Request repaint for animations
-        if !self.toasts.is_empty() || self.is_loading {
+        if !self.toasts.is_empty()
+            || self.is_loading()
+            || !self.kills.is_empty()
+            || !self.highlights.is_empty()
+        {
             ctx.request_repaint();
+        } else if self.resolve_names && self.resolver.has_pending() {
+            // Pick up reverse DNS answers as they arrive
+            ctx.request_repaint_after(std::time::Duration::from_millis(250));
+        } else if self.auto_refresh {
+            ctx.request_repaint_after(
+                self.refresh_interval
+                    .saturating_sub(self.last_refresh.elapsed()),
+            );
         }
     }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.settings().save(storage);
+    }
 }
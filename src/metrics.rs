@@ -0,0 +1,427 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+
+use crate::scanner::{Killer, PortEntry, SocketState};
+
+// Prometheus text exposition of a scan, for `portkill metrics` and the
+// daemon's --metrics endpoint:
+//
+//   portkill_sockets{protocol="tcp",state="LISTEN"} 12
+//   portkill_listeners{process="node",user="dev"} 3
+//   portkill_time_wait_sockets 40
+//   portkill_kills_total{signal="SIGTERM",result="ok"} 2
+//   portkill_scan_duration_seconds 0.012
+//
+// Kills are those sent by the process serving the metrics.
+pub struct Scrape<'a> {
+    pub entries: &'a [PortEntry],
+    pub scanned_at: Option<DateTime<Local>>,
+    pub scan_duration: Option<Duration>,
+    pub scans: u64,
+    pub scan_errors: u64,
+}
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl Scrape<'_> {
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        let mut sockets: BTreeMap<(String, String), u64> = BTreeMap::new();
+        let mut listeners: BTreeMap<(String, String), u64> = BTreeMap::new();
+        for entry in self.entries {
+            *sockets
+                .entry((
+                    entry.protocol.to_string().to_lowercase(),
+                    entry.state.to_string(),
+                ))
+                .or_default() += 1;
+            if entry.state == SocketState::Listen {
+                let process = match entry.pid {
+                    Some(_) => entry.process_name.clone(),
+                    None => "unknown".to_string(),
+                };
+                *listeners.entry((process, entry.user.clone())).or_default() += 1;
+            }
+        }
+        let time_wait = self
+            .entries
+            .iter()
+            .filter(|entry| entry.state == SocketState::TimeWait)
+            .count();
+
+        header(
+            &mut out,
+            "portkill_sockets",
+            "gauge",
+            "Sockets by protocol and state",
+        );
+        for ((protocol, state), count) in &sockets {
+            sample(
+                &mut out,
+                "portkill_sockets",
+                &[("protocol", protocol), ("state", state)],
+                *count as f64,
+            );
+        }
+
+        header(
+            &mut out,
+            "portkill_listeners",
+            "gauge",
+            "Listening sockets by process and user",
+        );
+        for ((process, user), count) in &listeners {
+            sample(
+                &mut out,
+                "portkill_listeners",
+                &[("process", process), ("user", user)],
+                *count as f64,
+            );
+        }
+
+        header(
+            &mut out,
+            "portkill_time_wait_sockets",
+            "gauge",
+            "Sockets in TIME_WAIT",
+        );
+        sample(
+            &mut out,
+            "portkill_time_wait_sockets",
+            &[],
+            time_wait as f64,
+        );
+
+        header(
+            &mut out,
+            "portkill_kills_total",
+            "counter",
+            "Signals sent to processes, by signal and whether they were delivered",
+        );
+        for (signal, delivered, count) in Killer::kill_counts() {
+            let result = if delivered { "ok" } else { "failed" };
+            sample(
+                &mut out,
+                "portkill_kills_total",
+                &[("signal", &signal), ("result", result)],
+                count as f64,
+            );
+        }
+
+        header(
+            &mut out,
+            "portkill_scans_total",
+            "counter",
+            "Completed scans",
+        );
+        sample(&mut out, "portkill_scans_total", &[], self.scans as f64);
+        header(
+            &mut out,
+            "portkill_scan_errors_total",
+            "counter",
+            "Scans that failed",
+        );
+        sample(
+            &mut out,
+            "portkill_scan_errors_total",
+            &[],
+            self.scan_errors as f64,
+        );
+
+        if let Some(duration) = self.scan_duration {
+            header(
+                &mut out,
+                "portkill_scan_duration_seconds",
+                "gauge",
+                "Duration of the latest scan",
+            );
+            sample(
+                &mut out,
+                "portkill_scan_duration_seconds",
+                &[],
+                duration.as_secs_f64(),
+            );
+        }
+        if let Some(scanned_at) = self.scanned_at {
+            header(
+                &mut out,
+                "portkill_last_scan_timestamp_seconds",
+                "gauge",
+                "Unix time of the latest scan",
+            );
+            sample(
+                &mut out,
+                "portkill_last_scan_timestamp_seconds",
+                &[],
+                scanned_at.timestamp_millis() as f64 / 1000.0,
+            );
+        }
+
+        out
+    }
+}
+
+// A minimal HTTP endpoint answering GET /metrics
+pub struct MetricsServer {
+    listener: TcpListener,
+}
+
+impl MetricsServer {
+    pub fn bind(address: &str) -> Result<Self, String> {
+        let listener = TcpListener::bind(address).map_err(|e| format!("{}: {}", address, e))?;
+        Ok(Self { listener })
+    }
+
+    pub fn local_addr(&self) -> String {
+        self.listener
+            .local_addr()
+            .map(|address| address.to_string())
+            .unwrap_or_default()
+    }
+
+    // Serves scrapes until the process exits, each on its own thread so a
+    // stalled client can't hold up the next scrape; `render` runs per scrape
+    pub fn serve(self, render: impl Fn() -> String + Sync) {
+        std::thread::scope(|scope| {
+            for stream in self.listener.incoming() {
+                let render = &render;
+                scope.spawn(move || {
+                    let result = stream
+                        .map_err(|e| e.to_string())
+                        .and_then(|stream| Self::answer(stream, render));
+                    if let Err(e) = result {
+                        log::debug!("Metrics request failed: {}", e);
+                    }
+                });
+            }
+        });
+    }
+
+    fn answer(stream: TcpStream, render: &impl Fn() -> String) -> Result<(), String> {
+        stream
+            .set_read_timeout(Some(REQUEST_TIMEOUT))
+            .and_then(|_| stream.set_write_timeout(Some(REQUEST_TIMEOUT)))
+            .map_err(|e| e.to_string())?;
+        let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+
+        let mut request_line = String::new();
+        reader
+            .read_line(&mut request_line)
+            .map_err(|e| e.to_string())?;
+        // Skip the headers; no request we answer has a body
+        loop {
+            let mut header = String::new();
+            match reader.read_line(&mut header) {
+                Ok(0) => break,
+                Ok(_) if header.trim().is_empty() => break,
+                Ok(_) => {}
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or_default();
+        let path = parts.next().unwrap_or_default();
+        let path = path.split('?').next().unwrap_or_default();
+
+        let (status, content_type, body) = match (method, path) {
+            ("GET", "/metrics") => ("200 OK", CONTENT_TYPE, render()),
+            ("GET", "/") => (
+                "200 OK",
+                "text/plain; charset=utf-8",
+                "portkill metrics are at /metrics\n".to_string(),
+            ),
+            ("GET", _) => (
+                "404 Not Found",
+                "text/plain; charset=utf-8",
+                "not found\n".to_string(),
+            ),
+            _ => (
+                "405 Method Not Allowed",
+                "text/plain; charset=utf-8",
+                "only GET is supported\n".to_string(),
+            ),
+        };
+
+        let mut stream = stream;
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        )
+        .map_err(|e| e.to_string())
+    }
+}
+
+// node_exporter's textfile collector reads *.prom files, so the file is
+// replaced by a rename rather than rewritten in place
+pub fn write_textfile(path: &Path, text: &str) -> Result<(), String> {
+    let temporary = path.with_extension("prom.tmp");
+    std::fs::write(&temporary, text).map_err(|e| format!("{}: {}", temporary.display(), e))?;
+    std::fs::rename(&temporary, path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: f64) {
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(label, value)| format!("{}=\"{}\"", label, escape(value)))
+        .collect();
+    if labels.is_empty() {
+        let _ = writeln!(out, "{} {}", name, value);
+    } else {
+        let _ = writeln!(out, "{}{{{}}} {}", name, labels.join(","), value);
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    use chrono::TimeZone;
+
+    use crate::scanner::Protocol;
+
+    fn get(address: &str, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn renders_counts_by_label() {
+        let connection = |port, state| PortEntry {
+            state,
+            remote_addr: "10.0.0.2:50000".to_string(),
+            inode: Some(u32::from(port) + 1),
+            ..PortEntry::listener(port, "node")
+        };
+        let entries = vec![
+            PortEntry {
+                pid: Some(4242),
+                ..PortEntry::listener(3000, "node")
+            },
+            PortEntry {
+                pid: Some(4242),
+                protocol: Protocol::Tcp6,
+                local_addr: "[::]:3000".to_string(),
+                ..PortEntry::listener(3000, "node")
+            },
+            // Process names are free text
+            PortEntry {
+                pid: Some(4343),
+                user: "svc\\ops".to_string(),
+                ..PortEntry::listener(9000, "my \"app\"\n")
+            },
+            PortEntry::listener(22, "sshd"),
+            connection(3000, SocketState::Established),
+            connection(3001, SocketState::TimeWait),
+            connection(3002, SocketState::TimeWait),
+        ];
+        let text = Scrape {
+            entries: &entries,
+            scanned_at: Some(Local.timestamp_opt(1_800_000_000, 500_000_000).unwrap()),
+            scan_duration: None,
+            scans: 0,
+            scan_errors: 0,
+        }
+        .render();
+
+        let lines: Vec<&str> = text.lines().collect();
+        for expected in [
+            "# HELP portkill_sockets Sockets by protocol and state",
+            "# TYPE portkill_sockets gauge",
+            "portkill_sockets{protocol=\"tcp\",state=\"LISTEN\"} 3",
+            "portkill_sockets{protocol=\"tcp6\",state=\"LISTEN\"} 1",
+            "portkill_sockets{protocol=\"tcp\",state=\"ESTABLISHED\"} 1",
+            "portkill_sockets{protocol=\"tcp\",state=\"TIME_WAIT\"} 2",
+            "portkill_listeners{process=\"node\",user=\"dev\"} 2",
+            "portkill_listeners{process=\"my \\\"app\\\"\\n\",user=\"svc\\\\ops\"} 1",
+            // Listeners of processes we can't see are grouped together
+            "portkill_listeners{process=\"unknown\",user=\"dev\"} 1",
+            "portkill_time_wait_sockets 2",
+            "# TYPE portkill_kills_total counter",
+            "portkill_scans_total 0",
+            "portkill_last_scan_timestamp_seconds 1800000000.5",
+        ] {
+            assert!(lines.contains(&expected), "missing {}\n{}", expected, text);
+        }
+        assert!(!text.contains("portkill_scan_duration_seconds"));
+    }
+
+    #[test]
+    fn serves_the_rendered_scrape() {
+        let server = MetricsServer::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr();
+        std::thread::spawn(move || {
+            let entries = vec![
+                PortEntry {
+                    pid: Some(4242),
+                    ..PortEntry::listener(8080, "node")
+                },
+                PortEntry {
+                    pid: Some(4343),
+                    ..PortEntry::listener(5432, "postgres")
+                },
+            ];
+            server.serve(|| {
+                Scrape {
+                    entries: &entries,
+                    scanned_at: None,
+                    scan_duration: Some(Duration::from_millis(12)),
+                    scans: 3,
+                    scan_errors: 1,
+                }
+                .render()
+            })
+        });
+
+        // A client that never sends its request mustn't hold up the scrape
+        let _stalled = TcpStream::connect(&address).unwrap();
+
+        let response = get(&address, "/metrics");
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+        assert!(head.contains(&format!("Content-Type: {}", CONTENT_TYPE)));
+        assert!(head.contains(&format!("Content-Length: {}", body.len())));
+
+        assert!(body.contains("# TYPE portkill_sockets gauge\n"));
+        assert!(body.contains("portkill_sockets{protocol=\"tcp\",state=\"LISTEN\"} 2\n"));
+        assert!(body.contains("portkill_listeners{process=\"node\",user=\"dev\"} 1\n"));
+        assert!(body.contains("portkill_listeners{process=\"postgres\",user=\"dev\"} 1\n"));
+        assert!(body.contains("portkill_scans_total 3\n"));
+        assert!(body.contains("portkill_scan_errors_total 1\n"));
+        assert!(body.contains("portkill_scan_duration_seconds 0.012\n"));
+        assert!(!body.contains("portkill_last_scan_timestamp_seconds"));
+
+        assert!(get(&address, "/other").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
@@ -0,0 +1,306 @@
+use std::io::Error;
+use std::net::{Ipv6Addr, SocketAddrV6, TcpListener};
+use std::os::fd::{AsRawFd, OwnedFd};
+
+use nix::sys::socket::{
+    bind, setsockopt, socket, sockopt, AddressFamily, SockFlag, SockType, SockaddrIn6,
+};
+
+use crate::scanner::{PortEntry, SocketState};
+
+// Ports commonly claimed by development services. Skipped by the free-port finder
+// so a port handed out today doesn't collide with a database started tomorrow.
+const WELL_KNOWN_DEV_PORTS: &[u16] = &[
+    1433, 1521, 2375, 2376, 3000, 3001, 3306, 4200, 5000, 5173, 5432, 5672, 6379, 8000, 8080, 8081,
+    8443, 8888, 9000, 9090, 9200, 9300, 11211, 15672, 27017,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl PortRange {
+    pub fn new(start: u16, end: u16) -> Self {
+        Self { start, end }
+    }
+
+    // "8080" or "3000-3999"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let parse_port = |port: &str| {
+            port.trim()
+                .parse::<u16>()
+                .map_err(|_| format!("invalid port '{}'", port))
+        };
+
+        let (start, end) = match text.split_once('-') {
+            Some((start, end)) => (parse_port(start)?, parse_port(end)?),
+            None => {
+                let port = parse_port(text)?;
+                (port, port)
+            }
+        };
+
+        if start > end {
+            return Err(format!("invalid port range '{}'", text));
+        }
+
+        Ok(Self { start, end })
+    }
+
+    pub fn contains(&self, port: u16) -> bool {
+        (self.start..=self.end).contains(&port)
+    }
+}
+
+impl std::fmt::Display for PortRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+// The kernel's range for outgoing connections' local ports
+pub fn ephemeral_port_range() -> Option<PortRange> {
+    let contents = std::fs::read_to_string("/proc/sys/net/ipv4/ip_local_port_range").ok()?;
+    let mut bounds = contents
+        .split_whitespace()
+        .map(|bound| bound.parse::<u16>());
+
+    match (bounds.next()?, bounds.next()?) {
+        (Ok(start), Ok(end)) if start <= end => Some(PortRange::new(start, end)),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FreePortOptions {
+    // Defaults to the unprivileged ports below the ephemeral range, which the
+    // kernel will never pick for an outgoing connection
+    pub range: Option<PortRange>,
+    pub count: usize,
+    pub avoid_well_known: bool,
+    pub avoid_time_wait: bool,
+}
+
+impl Default for FreePortOptions {
+    fn default() -> Self {
+        Self {
+            range: None,
+            count: 1,
+            avoid_well_known: true,
+            avoid_time_wait: true,
+        }
+    }
+}
+
+impl FreePortOptions {
+    pub fn effective_range(&self) -> PortRange {
+        if let Some(range) = self.range {
+            return range;
+        }
+
+        match ephemeral_port_range() {
+            Some(ephemeral) if ephemeral.start > 1024 => PortRange::new(1024, ephemeral.start - 1),
+            _ => PortRange::new(1024, 32767),
+        }
+    }
+
+    fn is_candidate(&self, port: u16, entries: &[PortEntry]) -> bool {
+        if self.avoid_well_known && (port < 1024 || WELL_KNOWN_DEV_PORTS.contains(&port)) {
+            return false;
+        }
+
+        !entries.iter().any(|entry| {
+            entry.port == port && (self.avoid_time_wait || entry.state != SocketState::TimeWait)
+        })
+    }
+}
+
+// Up to `options.count` ports unused according to `entries` that could also be
+// bound just now. Another process may still take them before the caller does;
+// use `reserve_free_ports` when that matters.
+pub fn find_free_ports(entries: &[PortEntry], options: &FreePortOptions) -> Vec<u16> {
+    bind_free_ports(entries, options)
+        .iter()
+        .filter_map(|listener| listener.local_addr().ok())
+        .map(|addr| addr.port())
+        .collect()
+}
+
+// Like `find_free_ports`, but keeps each port bound so nothing can take it
+// before it is handed to its user. Fails when fewer than `count` are available.
+pub fn reserve_free_ports(
+    entries: &[PortEntry],
+    options: &FreePortOptions,
+) -> Result<Vec<TcpListener>, Error> {
+    let listeners = bind_free_ports(entries, options);
+
+    if listeners.len() < options.count {
+        return Err(Error::new(
+            std::io::ErrorKind::AddrNotAvailable,
+            format!(
+                "only {} of {} requested ports are free in {}",
+                listeners.len(),
+                options.count,
+                options.effective_range()
+            ),
+        ));
+    }
+
+    Ok(listeners)
+}
+
+fn bind_free_ports(entries: &[PortEntry], options: &FreePortOptions) -> Vec<TcpListener> {
+    let range = options.effective_range();
+    let mut listeners = Vec::with_capacity(options.count);
+
+    for port in range.start..=range.end {
+        if listeners.len() >= options.count {
+            break;
+        }
+
+        if !options.is_candidate(port, entries) {
+            continue;
+        }
+
+        // Binding the wildcard address also fails when any specific address
+        // holds the port, catching sockets the scan can't see (other namespaces)
+        if let Ok(listener) = TcpListener::bind(("0.0.0.0", port)) {
+            if ipv6_port_free(port) {
+                listeners.push(listener);
+            }
+        }
+    }
+
+    listeners
+}
+
+// An IPv6-only listener doesn't stop the IPv4 bind above, so the IPv6 wildcard
+// is tried too. V6ONLY keeps it from clashing with our own IPv4 listener.
+fn ipv6_port_free(port: u16) -> bool {
+    let Ok(socket) = ipv6_only_socket() else {
+        // No IPv6 on this host
+        return true;
+    };
+    let address = SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, port, 0, 0);
+    bind(socket.as_raw_fd(), &SockaddrIn6::from(address)).is_ok()
+}
+
+fn ipv6_only_socket() -> nix::Result<OwnedFd> {
+    let socket = socket(
+        AddressFamily::Inet6,
+        SockType::Stream,
+        SockFlag::SOCK_CLOEXEC,
+        None,
+    )?;
+    setsockopt(&socket, sockopt::Ipv6V6Only, &true)?;
+    // As std's TcpListener does, so sockets in TIME_WAIT don't count
+    setsockopt(&socket, sockopt::ReuseAddr, &true)?;
+    Ok(socket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::sys::socket::{getsockname, listen, Backlog};
+
+    #[test]
+    fn parse_port_range() {
+        assert_eq!(PortRange::parse("8080"), Ok(PortRange::new(8080, 8080)));
+        assert_eq!(
+            PortRange::parse("3000-3999"),
+            Ok(PortRange::new(3000, 3999))
+        );
+        assert_eq!(
+            PortRange::parse(" 3000 - 3999 "),
+            Ok(PortRange::new(3000, 3999))
+        );
+        assert_eq!(PortRange::parse("0-65535"), Ok(PortRange::new(0, 65535)));
+
+        assert_eq!(
+            PortRange::parse("70000"),
+            Err("invalid port '70000'".to_string())
+        );
+        assert_eq!(
+            PortRange::parse("http"),
+            Err("invalid port 'http'".to_string())
+        );
+        assert_eq!(
+            PortRange::parse("3000-"),
+            Err("invalid port ''".to_string())
+        );
+        assert_eq!(
+            PortRange::parse("3999-3000"),
+            Err("invalid port range '3999-3000'".to_string())
+        );
+    }
+
+    #[test]
+    fn range_contains_its_bounds() {
+        let range = PortRange::new(3000, 3999);
+        assert!(range.contains(3000));
+        assert!(range.contains(3999));
+        assert!(!range.contains(2999));
+        assert!(!range.contains(4000));
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for text in ["8080", "3000-3999"] {
+            assert_eq!(PortRange::parse(text).unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn candidates_skip_taken_and_well_known_ports() {
+        let entries = vec![
+            PortEntry::listener(20000, "node"),
+            PortEntry {
+                state: SocketState::TimeWait,
+                ..PortEntry::listener(20001, "node")
+            },
+        ];
+        let options = FreePortOptions::default();
+        assert!(!options.is_candidate(80, &entries));
+        assert!(!options.is_candidate(5432, &entries));
+        assert!(!options.is_candidate(20000, &entries));
+        assert!(!options.is_candidate(20001, &entries));
+        assert!(options.is_candidate(20002, &entries));
+
+        let options = FreePortOptions {
+            avoid_well_known: false,
+            avoid_time_wait: false,
+            ..FreePortOptions::default()
+        };
+        assert!(options.is_candidate(5432, &entries));
+        assert!(!options.is_candidate(20000, &entries));
+        assert!(options.is_candidate(20001, &entries));
+    }
+
+    #[test]
+    fn ports_held_on_ipv6_only_are_taken() {
+        let Ok(socket) = ipv6_only_socket() else {
+            return;
+        };
+        let any = SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, 0, 0, 0);
+        bind(socket.as_raw_fd(), &SockaddrIn6::from(any)).unwrap();
+        listen(&socket, Backlog::new(1).unwrap()).unwrap();
+        let port = getsockname::<SockaddrIn6>(socket.as_raw_fd())
+            .unwrap()
+            .port();
+
+        let options = FreePortOptions {
+            range: Some(PortRange::new(port, port)),
+            avoid_well_known: false,
+            ..FreePortOptions::default()
+        };
+        assert!(find_free_ports(&[], &options).is_empty());
+        drop(socket);
+        assert_eq!(find_free_ports(&[], &options), [port]);
+    }
+}
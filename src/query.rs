@@ -0,0 +1,410 @@
+use std::net::{IpAddr, SocketAddr};
+
+use regex_lite::Regex;
+
+use crate::scanner::PortEntry;
+
+// Filter expressions shared by the GUI search box and the CLI `--filter` flag.
+//
+//   port:3000-3999 state:listen user:dev proc:~node addr:127.0.0.1 !proc:postgres
+//   project:billing
+//
+// Terms are separated by whitespace and must all match. `field:value` matches
+// exactly, `field:~pattern` as a regular expression, and `port`/`pid` also
+// accept `start-end` ranges. A leading `!` negates a term. Bare numbers match
+// the port exactly; other bare words match part of the process name.
+#[derive(Clone, Debug, Default)]
+pub struct Query {
+    terms: Vec<Term>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+    Port,
+    Pid,
+    Proto,
+    State,
+    User,
+    Proc,
+    Cmd,
+    Addr,
+    RemoteAddr,
+    Project,
+    // A term without a field name
+    Any,
+}
+
+impl Field {
+    const NAMES: [(&'static str, Field); 10] = [
+        ("port", Field::Port),
+        ("pid", Field::Pid),
+        ("proto", Field::Proto),
+        ("state", Field::State),
+        ("user", Field::User),
+        ("proc", Field::Proc),
+        ("cmd", Field::Cmd),
+        ("addr", Field::Addr),
+        ("raddr", Field::RemoteAddr),
+        ("project", Field::Project),
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(field_name, _)| name.eq_ignore_ascii_case(field_name))
+            .map(|(_, field)| *field)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Term {
+    negated: bool,
+    field: Field,
+    matcher: Matcher,
+}
+
+#[derive(Clone, Debug)]
+enum Matcher {
+    Exact(String),
+    Range(u32, u32),
+    Regex(Regex),
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let terms = Self::tokenize(text)?
+            .iter()
+            .map(|token| Self::parse_term(token))
+            .collect::<Result<Vec<Term>, String>>()?;
+
+        Ok(Self { terms })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    // Whether the query says anything about `field`, so callers can drop their
+    // own defaults (e.g. "LISTEN only") when the user asked explicitly
+    pub fn constrains(&self, field: Field) -> bool {
+        self.terms.iter().any(|term| term.field == field)
+    }
+
+    pub fn matches(&self, entry: &PortEntry) -> bool {
+        self.terms
+            .iter()
+            .all(|term| term.matches(entry) != term.negated)
+    }
+
+    // Whitespace-separated tokens; double quotes group words with spaces
+    fn tokenize(text: &str) -> Result<Vec<String>, String> {
+        let mut tokens = Vec::new();
+        let mut current = String::new();
+        let mut in_quotes = false;
+
+        for c in text.chars() {
+            match c {
+                '"' => in_quotes = !in_quotes,
+                c if c.is_whitespace() && !in_quotes => {
+                    if !current.is_empty() {
+                        tokens.push(std::mem::take(&mut current));
+                    }
+                }
+                c => current.push(c),
+            }
+        }
+
+        if in_quotes {
+            return Err("unterminated quote".to_string());
+        }
+        if !current.is_empty() {
+            tokens.push(current);
+        }
+
+        Ok(tokens)
+    }
+
+    fn parse_term(token: &str) -> Result<Term, String> {
+        let (negated, token) = match token.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, token),
+        };
+
+        // IPv6 literals contain ':' too, so only a known name counts as a field
+        let (field, value) = match token.split_once(':') {
+            Some((name, value))
+                if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphabetic()) =>
+            {
+                match Field::from_name(name) {
+                    Some(field) => (field, value),
+                    None => {
+                        let names: Vec<&str> = Field::NAMES.iter().map(|(name, _)| *name).collect();
+                        return Err(format!(
+                            "unknown field '{}' (expected one of: {})",
+                            name,
+                            names.join(", ")
+                        ));
+                    }
+                }
+            }
+            _ => (Field::Any, token),
+        };
+
+        if value.is_empty() {
+            return Err(format!("missing value in '{}'", token));
+        }
+
+        let matcher = match value.strip_prefix('~') {
+            Some(pattern) => Matcher::Regex(
+                Regex::new(pattern).map_err(|e| format!("invalid pattern '{}': {}", pattern, e))?,
+            ),
+            None if matches!(field, Field::Port | Field::Pid) => Self::parse_range(value)?,
+            None if field == Field::Any && value.chars().all(|c| c.is_ascii_digit()) => {
+                // A bare number is a port; "80" should not match 8080
+                return Ok(Term {
+                    negated,
+                    field: Field::Port,
+                    matcher: Self::parse_range(value)?,
+                });
+            }
+            None => Matcher::Exact(value.to_lowercase()),
+        };
+
+        Ok(Term {
+            negated,
+            field,
+            matcher,
+        })
+    }
+
+    fn parse_range(value: &str) -> Result<Matcher, String> {
+        let parse = |number: &str| {
+            number
+                .parse::<u32>()
+                .map_err(|_| format!("invalid number '{}'", number))
+        };
+
+        match value.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (parse(start)?, parse(end)?);
+                if start > end {
+                    return Err(format!("invalid range '{}'", value));
+                }
+                Ok(Matcher::Range(start, end))
+            }
+            None => {
+                let number = parse(value)?;
+                Ok(Matcher::Range(number, number))
+            }
+        }
+    }
+}
+
+impl Term {
+    fn matches(&self, entry: &PortEntry) -> bool {
+        match self.field {
+            Field::Port => self.matches_number(Some(u32::from(entry.port))),
+            Field::Pid => self.matches_number(entry.pid),
+            Field::Proto => self.matches_text(&entry.protocol.to_string()),
+            Field::State => match &self.matcher {
+                // Accept "time_wait", "time-wait" and "timewait" alike
+                Matcher::Exact(state) => {
+                    Self::normalize_state(state) == Self::normalize_state(&entry.state.to_string())
+                }
+                _ => self.matches_text(&entry.state.to_string()),
+            },
+            Field::User => self.matches_text(&entry.user),
+            Field::Proc => self.matches_text(&entry.process_name),
+            Field::Cmd => match &self.matcher {
+                Matcher::Exact(text) => entry.cmdline.to_lowercase().contains(text),
+                _ => self.matches_text(&entry.cmdline),
+            },
+            Field::Addr => self.matches_address(&entry.local_addr),
+            Field::RemoteAddr => self.matches_address(&entry.remote_addr),
+            Field::Project => entry
+                .project
+                .as_ref()
+                .is_some_and(|project| self.matches_text(&project.name)),
+            Field::Any => match &self.matcher {
+                Matcher::Exact(text) => entry.process_name.to_lowercase().contains(text),
+                _ => self.matches_text(&entry.process_name),
+            },
+        }
+    }
+
+    fn matches_number(&self, number: Option<u32>) -> bool {
+        let number = match number {
+            Some(number) => number,
+            None => return false,
+        };
+
+        match &self.matcher {
+            Matcher::Range(start, end) => (*start..=*end).contains(&number),
+            Matcher::Regex(regex) => regex.is_match(&number.to_string()),
+            Matcher::Exact(text) => number.to_string() == *text,
+        }
+    }
+
+    fn matches_text(&self, text: &str) -> bool {
+        match &self.matcher {
+            Matcher::Exact(expected) => text.to_lowercase() == *expected,
+            Matcher::Regex(regex) => regex.is_match(text),
+            Matcher::Range(..) => false,
+        }
+    }
+
+    // An exact address matches either the full "ip:port" or just the IP
+    fn matches_address(&self, address: &str) -> bool {
+        match &self.matcher {
+            Matcher::Exact(expected) => {
+                let actual = address.parse::<SocketAddr>().ok();
+
+                if let Ok(expected) = expected.parse::<SocketAddr>() {
+                    actual == Some(expected)
+                } else if let Ok(expected) = expected.trim_matches(['[', ']']).parse::<IpAddr>() {
+                    actual.is_some_and(|actual| actual.ip() == expected)
+                } else {
+                    address.to_lowercase() == *expected
+                }
+            }
+            _ => self.matches_text(address),
+        }
+    }
+
+    fn normalize_state(state: &str) -> String {
+        state
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::{Project, SocketState};
+
+    fn matches(query: &str, entry: &PortEntry) -> bool {
+        Query::parse(query).unwrap().matches(entry)
+    }
+
+    fn node() -> PortEntry {
+        PortEntry {
+            pid: Some(4242),
+            local_addr: "127.0.0.1:8080".to_string(),
+            cmdline: "node ./node_modules/.bin/vite --port 8080".to_string(),
+            project: Some(Project {
+                name: "billing".to_string(),
+                root: "/src/billing".to_string(),
+            }),
+            ..PortEntry::listener(8080, "node")
+        }
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        let query = Query::parse("  ").unwrap();
+        assert!(query.is_empty());
+        assert!(query.matches(&node()));
+    }
+
+    #[test]
+    fn parse_errors() {
+        for (query, error) in [
+            ("colour:red", "unknown field 'colour'"),
+            ("proc:", "missing value in 'proc:'"),
+            ("\"proc:node", "unterminated quote"),
+            ("port:3999-3000", "invalid range '3999-3000'"),
+            ("port:http", "invalid number 'http'"),
+            ("proc:~(", "invalid pattern '('"),
+        ] {
+            let message = Query::parse(query).unwrap_err();
+            assert!(message.starts_with(error), "{}: {}", query, message);
+        }
+    }
+
+    #[test]
+    fn ports_and_pids() {
+        let entry = node();
+        assert!(matches("port:8080", &entry));
+        assert!(matches("port:8000-8999", &entry));
+        assert!(!matches("port:3000-3999", &entry));
+        assert!(matches("pid:4242", &entry));
+        assert!(!matches("pid:1-100", &entry));
+        assert!(matches("port:~^80", &entry));
+        // A bare number is the whole port
+        assert!(matches("8080", &entry));
+        assert!(!matches("80", &entry));
+
+        let unknown = PortEntry {
+            pid: None,
+            ..node()
+        };
+        assert!(!matches("pid:0-99999", &unknown));
+    }
+
+    #[test]
+    fn text_fields() {
+        let entry = node();
+        assert!(matches("proc:node user:DEV proto:tcp", &entry));
+        assert!(!matches("proc:no", &entry));
+        assert!(matches("proc:~^no", &entry));
+        // Bare words and cmd: match part of the text
+        assert!(matches("od", &entry));
+        assert!(matches("cmd:vite", &entry));
+        assert!(matches("project:billing", &entry));
+        assert!(!matches(
+            "project:billing",
+            &PortEntry::listener(8080, "node")
+        ));
+    }
+
+    #[test]
+    fn states_ignore_punctuation() {
+        let entry = PortEntry {
+            state: SocketState::TimeWait,
+            ..node()
+        };
+        for query in ["state:time_wait", "state:time-wait", "state:TIMEWAIT"] {
+            assert!(matches(query, &entry), "{}", query);
+        }
+        assert!(!matches("state:listen", &entry));
+        assert!(matches("state:~WAIT$", &entry));
+    }
+
+    #[test]
+    fn addresses() {
+        let entry = node();
+        assert!(matches("addr:127.0.0.1", &entry));
+        assert!(matches("addr:127.0.0.1:8080", &entry));
+        assert!(!matches("addr:127.0.0.1:9090", &entry));
+        assert!(matches("raddr:0.0.0.0", &entry));
+
+        let v6 = PortEntry {
+            local_addr: "[::1]:8080".to_string(),
+            ..node()
+        };
+        // IPv6 literals aren't taken for a field name
+        assert!(matches("addr:::1", &v6));
+        assert!(matches("addr:[::1]", &v6));
+        assert!(matches("addr:[::1]:8080", &v6));
+        assert!(matches("::1", &PortEntry::listener(8080, "x::1")));
+    }
+
+    #[test]
+    fn negation_and_quotes() {
+        let entry = node();
+        assert!(matches("!proc:postgres port:8080", &entry));
+        assert!(!matches("!proc:node", &entry));
+        assert!(matches("cmd:\"vite --port\"", &entry));
+    }
+
+    #[test]
+    fn constrains() {
+        let query = Query::parse("state:established 8080").unwrap();
+        assert!(query.constrains(Field::State));
+        assert!(query.constrains(Field::Port));
+        assert!(!query.constrains(Field::Proc));
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use log::{info, warn};
+use portkill::history::{History, Recorder};
+use portkill::rpc::Client;
+use portkill::scanner::{PortEntry, SocketState};
+
+// Appends the GUI's scans to the history file from a background thread.
+// Asking the daemon whether it records already, opening the file (which
+// compacts it) and the hourly compaction are all too slow for the UI thread.
+pub struct HistoryRecorder {
+    scans: Sender<Vec<PortEntry>>,
+    errors: Receiver<String>,
+}
+
+impl HistoryRecorder {
+    pub fn spawn() -> Self {
+        let (scan_tx, scan_rx) = mpsc::channel::<Vec<PortEntry>>();
+        let (error_tx, error_rx) = mpsc::channel();
+
+        thread::Builder::new()
+            .name("portkill-history".to_string())
+            .spawn(move || {
+                let mut recorder = match Self::open() {
+                    Ok(Some(recorder)) => recorder,
+                    Ok(None) => return,
+                    Err(e) => {
+                        let _ = error_tx.send(format!("History not recorded: {}", e));
+                        return;
+                    }
+                };
+
+                // A recorder that fails once is dropped rather than retried every scan
+                while let Ok(entries) = scan_rx.recv() {
+                    if let Err(e) = recorder.record(&entries) {
+                        warn!("Failed to record history: {}", e);
+                        let _ = error_tx.send(format!("History recording stopped: {}", e));
+                        return;
+                    }
+                }
+            })
+            .expect("failed to spawn history thread");
+
+        Self {
+            scans: scan_tx,
+            errors: error_rx,
+        }
+    }
+
+    // None when the daemon records; its recorder holds the lock, ours would only fail
+    fn open() -> Result<Option<Recorder>, String> {
+        let daemon_records = Client::connect()
+            .and_then(|mut client| client.status().ok())
+            .is_some_and(|status| status.recording);
+        if daemon_records {
+            info!("History is recorded by the daemon");
+            return Ok(None);
+        }
+
+        let path = History::path().ok_or("cannot locate the history file; set [history] path")?;
+        Recorder::open(&path).map(Some)
+    }
+
+    pub fn record(&self, entries: &[PortEntry]) {
+        let listening = entries
+            .iter()
+            .filter(|entry| entry.state == SocketState::Listen)
+            .cloned()
+            .collect();
+        // Fails once the thread has stopped, which it already reported
+        let _ = self.scans.send(listening);
+    }
+
+    // Why recording stopped, reported once
+    pub fn poll(&self) -> Option<String> {
+        self.errors.try_recv().ok()
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Host names for remote addresses: /etc/hosts first, then reverse DNS on
+// background threads. `name` never blocks; it answers from the cache and
+// queues a lookup for addresses it hasn't seen.
+pub struct Resolver {
+    shared: Arc<Shared>,
+    requests: Sender<IpAddr>,
+}
+
+type LookupFn = dyn Fn(&IpAddr) -> io::Result<String> + Send + Sync;
+
+struct Shared {
+    cache: Mutex<HashMap<IpAddr, Lookup>>,
+    // Read once at startup
+    hosts: HashMap<IpAddr, String>,
+    lookup: Arc<LookupFn>,
+    timing: Timing,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Timing {
+    timeout: Duration,
+    resolved_ttl: Duration,
+    failed_ttl: Duration,
+}
+
+#[derive(Clone, Debug)]
+enum Lookup {
+    // With the expired name, shown until the new answer arrives
+    Pending(Option<String>),
+    Resolved(String, Instant),
+    // No PTR record, a resolver error or a timeout
+    Failed(Instant),
+}
+
+const HOSTS_FILE: &str = "/etc/hosts";
+
+const TIMING: Timing = Timing {
+    // A slow resolver must not hold up the lookups queued behind it
+    timeout: Duration::from_secs(3),
+    resolved_ttl: Duration::from_secs(600),
+    failed_ttl: Duration::from_secs(60),
+};
+const WORKERS: usize = 4;
+
+impl Resolver {
+    pub fn spawn() -> Self {
+        Self::with_lookup(Path::new(HOSTS_FILE), TIMING, dns_lookup::lookup_addr)
+    }
+
+    // Resolves with `lookup` instead of getnameinfo, after the hosts file at
+    // `hosts_path`
+    fn with_lookup<L>(hosts_path: &Path, timing: Timing, lookup: L) -> Self
+    where
+        L: Fn(&IpAddr) -> io::Result<String> + Send + Sync + 'static,
+    {
+        let (request_tx, request_rx) = mpsc::channel::<IpAddr>();
+        let request_rx = Arc::new(Mutex::new(request_rx));
+        let shared = Arc::new(Shared {
+            cache: Mutex::new(HashMap::new()),
+            hosts: Self::read_hosts(hosts_path),
+            lookup: Arc::new(lookup),
+            timing,
+        });
+
+        for index in 0..WORKERS {
+            let request_rx = Arc::clone(&request_rx);
+            let shared = Arc::clone(&shared);
+
+            thread::Builder::new()
+                .name(format!("portkill-dns-{}", index))
+                .spawn(move || Self::work(&request_rx, &shared))
+                .expect("failed to spawn resolver thread");
+        }
+
+        Self {
+            shared,
+            requests: request_tx,
+        }
+    }
+
+    fn work(requests: &Mutex<Receiver<IpAddr>>, shared: &Shared) {
+        loop {
+            let request = requests.lock().unwrap_or_else(|e| e.into_inner()).recv();
+            let Ok(ip) = request else {
+                break;
+            };
+
+            // getnameinfo can't be cancelled; a lookup that times out finishes
+            // on its own thread and its answer is dropped
+            let (result_tx, result_rx) = mpsc::channel();
+            let lookup = Arc::clone(&shared.lookup);
+            thread::spawn(move || {
+                let _ = result_tx.send(lookup(&ip));
+            });
+
+            let lookup = match result_rx.recv_timeout(shared.timing.timeout) {
+                // Without a PTR record some resolvers echo the address back
+                Ok(Ok(name)) if name.parse::<IpAddr>().is_err() => {
+                    Lookup::Resolved(name, Instant::now())
+                }
+                Ok(_) => Lookup::Failed(Instant::now()),
+                Err(_) => {
+                    log::debug!("Reverse lookup of {} timed out", ip);
+                    Lookup::Failed(Instant::now())
+                }
+            };
+
+            shared
+                .cache
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(ip, lookup);
+        }
+    }
+
+    // The host name of `ip` if known by now
+    pub fn name(&self, ip: IpAddr) -> Option<String> {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_canonical(),
+            ip => ip,
+        };
+        if ip.is_unspecified() {
+            return None;
+        }
+        if let Some(name) = self.shared.hosts.get(&ip) {
+            return Some(name.clone());
+        }
+
+        let mut cache = self.shared.cache.lock().unwrap_or_else(|e| e.into_inner());
+        let timing = &self.shared.timing;
+        let stale = match cache.get(&ip) {
+            Some(Lookup::Pending(stale)) => return stale.clone(),
+            Some(Lookup::Resolved(name, at)) if at.elapsed() < timing.resolved_ttl => {
+                return Some(name.clone())
+            }
+            Some(Lookup::Failed(at)) if at.elapsed() < timing.failed_ttl => return None,
+            Some(Lookup::Resolved(name, _)) => Some(name.clone()),
+            Some(Lookup::Failed(_)) | None => None,
+        };
+
+        if self.requests.send(ip).is_ok() {
+            cache.insert(ip, Lookup::Pending(stale.clone()));
+        }
+        stale
+    }
+
+    // "db.internal:5432" for "10.0.0.5:5432", or the address as given
+    pub fn display(&self, addr: &str) -> String {
+        match addr.parse::<SocketAddr>() {
+            Ok(socket) => match self.name(socket.ip()) {
+                Some(name) => format!("{}:{}", name, socket.port()),
+                None => addr.to_string(),
+            },
+            Err(_) => addr.to_string(),
+        }
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.shared
+            .cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .any(|lookup| matches!(lookup, Lookup::Pending(_)))
+    }
+
+    // For the CLI: queues every address, then waits for the answers or `timeout`
+    pub fn prefetch(&self, ips: impl IntoIterator<Item = IpAddr>, timeout: Duration) {
+        for ip in ips {
+            self.name(ip);
+        }
+
+        let started = Instant::now();
+        while self.has_pending() && started.elapsed() < timeout {
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    // The first name listed for each address
+    fn read_hosts(path: &Path) -> HashMap<IpAddr, String> {
+        let mut hosts = HashMap::new();
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            return hosts;
+        };
+
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+            let (Some(ip), Some(name)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            if let Ok(ip) = ip.parse::<IpAddr>() {
+                hosts.entry(ip).or_insert_with(|| name.to_string());
+            }
+        }
+
+        hosts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const WAIT: Duration = Duration::from_secs(5);
+
+    fn hosts_file(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "portkill-resolver-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn ip(text: &str) -> IpAddr {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn hosts_file_answers_without_a_lookup() {
+        let path = hosts_file(
+            "hosts",
+            "# static table\n\
+             10.0.0.5\tdb.internal db  # primary\n\
+             10.0.0.5 other\n\
+             2001:db8::7 cache.internal\n\
+             not-an-address ignored\n",
+        );
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&lookups);
+        let resolver = Resolver::with_lookup(&path, TIMING, move |_: &IpAddr| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok("resolved.example".to_string())
+        });
+
+        assert_eq!(
+            resolver.name(ip("10.0.0.5")).as_deref(),
+            Some("db.internal")
+        );
+        assert_eq!(
+            resolver.name(ip("::ffff:10.0.0.5")).as_deref(),
+            Some("db.internal")
+        );
+        assert_eq!(
+            resolver.display("[2001:db8::7]:6379"),
+            "cache.internal:6379"
+        );
+        assert_eq!(resolver.name(ip("0.0.0.0")), None);
+        assert_eq!(lookups.load(Ordering::SeqCst), 0);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn expired_names_are_shown_until_looked_up_again() {
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&lookups);
+        let timing = Timing {
+            resolved_ttl: Duration::from_millis(100),
+            ..TIMING
+        };
+        let resolver =
+            Resolver::with_lookup(Path::new("/nonexistent"), timing, move |_: &IpAddr| {
+                let lookup = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                Ok(format!("host-{}.example", lookup))
+            });
+        let addr = ip("10.0.0.9");
+
+        // Queued, nothing known yet
+        assert_eq!(resolver.name(addr), None);
+        resolver.prefetch([addr], WAIT);
+        assert_eq!(resolver.name(addr).as_deref(), Some("host-1.example"));
+
+        thread::sleep(Duration::from_millis(150));
+        assert_eq!(resolver.name(addr).as_deref(), Some("host-1.example"));
+        resolver.prefetch([], WAIT);
+        assert_eq!(resolver.name(addr).as_deref(), Some("host-2.example"));
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn failures_are_cached_for_their_ttl() {
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&lookups);
+        let timing = Timing {
+            failed_ttl: Duration::from_millis(100),
+            ..TIMING
+        };
+        // Without a PTR record some resolvers echo the address back
+        let resolver =
+            Resolver::with_lookup(Path::new("/nonexistent"), timing, move |ip: &IpAddr| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(ip.to_string())
+            });
+        let addr = ip("10.0.0.9");
+
+        resolver.prefetch([addr], WAIT);
+        assert_eq!(resolver.name(addr), None);
+        assert!(!resolver.has_pending());
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+
+        thread::sleep(Duration::from_millis(150));
+        resolver.prefetch([addr], WAIT);
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn slow_lookups_time_out() {
+        let timing = Timing {
+            timeout: Duration::from_millis(50),
+            ..TIMING
+        };
+        let resolver = Resolver::with_lookup(Path::new("/nonexistent"), timing, |_: &IpAddr| {
+            thread::sleep(Duration::from_secs(1));
+            Ok("late.example".to_string())
+        });
+        let addr = ip("10.0.0.9");
+
+        let started = Instant::now();
+        resolver.prefetch([addr], WAIT);
+
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(!resolver.has_pending());
+        assert_eq!(resolver.display("10.0.0.9:443"), "10.0.0.9:443");
+    }
+}
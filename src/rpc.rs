@@ -0,0 +1,414 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use nix::sys::socket::{getsockopt, sockopt};
+use nix::unistd::getuid;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use crate::history::HistoryEvent;
+use crate::scanner::PortEntry;
+
+// JSON-RPC 2.0 between `portkill daemon` and its clients, one JSON object per
+// line over a UNIX socket.
+//
+//   status                      -> Status; clients call it first and refuse a
+//                                  daemon whose `api` differs from API_VERSION
+//   list      {filter?}         -> {scanned_at, entries}
+//   subscribe {filter?, all?}   -> {subscribed: true}, then an "event"
+//                                  notification per change and an "action"
+//                                  one per rule that fired (rules::Outcome);
+//                                  the connection takes no further requests
+//   kill      {pid, signal?}    -> {message}; without a signal the configured
+//                                  one, with its grace period
+pub const API_VERSION: u32 = 1;
+
+// Application errors; the -326xx codes are JSON-RPC's own
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const NOT_PERMITTED: i64 = 1;
+pub const KILL_FAILED: i64 = 2;
+pub const NO_SCAN_YET: i64 = 3;
+
+// Opt out of using a running daemon, e.g. to compare with a local scan
+pub const NO_DAEMON_ENV: &str = "PORTKILL_NO_DAEMON";
+
+const CALL_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Status {
+    pub api: u32,
+    pub version: String,
+    pub pid: u32,
+    pub scanned_at: Option<DateTime<Local>>,
+    pub sockets: usize,
+    pub subscribers: usize,
+    // Whether the daemon appends to the history file
+    pub recording: bool,
+    // Configured [[rules]]
+    #[serde(default)]
+    pub rules: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Event {
+    pub time: DateTime<Local>,
+    pub event: HistoryEvent,
+    pub entry: PortEntry,
+    // The entry before a change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<PortEntry>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Request {
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+// [daemon] socket, or portkill.sock in the user's private runtime directory
+// ($XDG_RUNTIME_DIR or /run/user/<uid>), or in ~/.local/state/portkill. Never a
+// shared directory such as /tmp, where another user could bind it first.
+pub fn socket_path() -> Option<PathBuf> {
+    if let Some(path) = &Config::global().daemon_socket {
+        return Some(path.clone());
+    }
+
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| {
+            Some(PathBuf::from(format!("/run/user/{}", getuid()))).filter(|path| path.is_dir())
+        });
+    if let Some(runtime_dir) = runtime_dir {
+        return Some(runtime_dir.join("portkill.sock"));
+    }
+
+    let state_home = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state"))
+        })?;
+    Some(state_home.join("portkill").join("daemon.sock"))
+}
+
+pub fn response(id: &Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+pub fn error(id: &Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+pub fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+pub struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+    next_id: u64,
+}
+
+// Events of a `subscribe` call, until the daemon goes away. Other
+// notifications, such as rule actions, are skipped.
+pub struct Subscription {
+    reader: BufReader<UnixStream>,
+}
+
+impl Client {
+    // The running daemon, if there is one speaking our API version. Anything
+    // else means the caller should scan by itself.
+    pub fn connect() -> Option<Self> {
+        if std::env::var_os(NO_DAEMON_ENV).is_some() {
+            return None;
+        }
+
+        let path = socket_path()?;
+        if !path.exists() {
+            return None;
+        }
+        match Self::connect_to(&path) {
+            Ok(client) => Some(client),
+            Err(e) => {
+                log::debug!("Not using the daemon: {}", e);
+                None
+            }
+        }
+    }
+
+    // Only a daemon run by this user is trusted with our scans and kills; its
+    // socket is owner-only, so nobody else could use it anyway
+    pub fn connect_to(path: &Path) -> Result<Self, String> {
+        let uid = getuid().as_raw();
+
+        let owner = std::fs::metadata(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?
+            .uid();
+        if owner != uid {
+            return Err(format!(
+                "{} belongs to UID {}, not to this user",
+                path.display(),
+                owner
+            ));
+        }
+
+        let stream = UnixStream::connect(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let peer = getsockopt(&stream, sockopt::PeerCredentials)
+            .map_err(|e| format!("peer credentials: {}", e))?
+            .uid();
+        if peer != uid {
+            return Err(format!(
+                "the process on {} runs as UID {}, not as this user",
+                path.display(),
+                peer
+            ));
+        }
+        stream
+            .set_read_timeout(Some(CALL_TIMEOUT))
+            .map_err(|e| e.to_string())?;
+        let writer = stream.try_clone().map_err(|e| e.to_string())?;
+
+        let mut client = Self {
+            reader: BufReader::new(stream),
+            writer,
+            next_id: 1,
+        };
+
+        let status = client.status()?;
+        if status.api != API_VERSION {
+            return Err(format!(
+                "the daemon speaks API v{}, this portkill v{}",
+                status.api, API_VERSION
+            ));
+        }
+        Ok(client)
+    }
+
+    pub fn status(&mut self) -> Result<Status, String> {
+        serde_json::from_value(self.call("status", Value::Null)?)
+            .map_err(|e| format!("unexpected status reply from the daemon: {}", e))
+    }
+
+    pub fn call(&mut self, method: &str, params: Value) -> Result<Value, String> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        writeln!(self.writer, "{}", request).map_err(|e| format!("daemon: {}", e))?;
+
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => return Err("daemon closed the connection".to_string()),
+            Ok(_) => {}
+            Err(e) => return Err(format!("daemon: {}", e)),
+        }
+
+        let mut reply: Value = serde_json::from_str(&line)
+            .map_err(|e| format!("invalid reply from the daemon: {}", e))?;
+        if let Some(error) = reply.get("error") {
+            return Err(error["message"]
+                .as_str()
+                .unwrap_or("unknown error")
+                .to_string());
+        }
+        Ok(reply["result"].take())
+    }
+
+    // The daemon's latest scan
+    pub fn list(&mut self) -> Result<Vec<PortEntry>, String> {
+        let mut result = self.call("list", Value::Null)?;
+        serde_json::from_value(result["entries"].take())
+            .map_err(|e| format!("invalid list reply from the daemon: {}", e))
+    }
+
+    // Sent by the daemon, subject to its protection rules
+    pub fn kill(&mut self, pid: u32, signal: Option<&str>) -> Result<String, String> {
+        let result = self.call("kill", json!({ "pid": pid, "signal": signal }))?;
+        Ok(result["message"].as_str().unwrap_or_default().to_string())
+    }
+
+    pub fn subscribe(mut self, filter: Option<&str>, all: bool) -> Result<Subscription, String> {
+        self.call("subscribe", json!({ "filter": filter, "all": all }))?;
+
+        // Events may be minutes apart
+        self.reader
+            .get_ref()
+            .set_read_timeout(None)
+            .map_err(|e| e.to_string())?;
+        Ok(Subscription {
+            reader: self.reader,
+        })
+    }
+}
+
+impl Iterator for Subscription {
+    type Item = Result<Event, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) => {
+                    let mut message: Value = match serde_json::from_str(&line) {
+                        Ok(message) => message,
+                        Err(e) => {
+                            return Some(Err(format!("invalid event from the daemon: {}", e)))
+                        }
+                    };
+                    if message["method"] != "event" {
+                        continue;
+                    }
+                    return Some(
+                        serde_json::from_value(message["params"].take())
+                            .map_err(|e| format!("invalid event from the daemon: {}", e)),
+                    );
+                }
+                Err(e) => return Some(Err(format!("daemon: {}", e))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    // Answers every request on a fresh socket with `reply(request)`, which
+    // returns the lines to send back
+    fn fake_daemon<F>(name: &str, reply: F) -> PathBuf
+    where
+        F: Fn(&Value) -> Vec<Value> + Send + 'static,
+    {
+        let dir =
+            std::env::temp_dir().join(format!("portkill-rpc-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("daemon.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut writer = stream.unwrap();
+                let reader = BufReader::new(writer.try_clone().unwrap());
+                for line in reader.lines() {
+                    let request: Value = serde_json::from_str(&line.unwrap()).unwrap();
+                    for message in reply(&request) {
+                        writeln!(writer, "{}", message).unwrap();
+                    }
+                }
+            }
+        });
+        path
+    }
+
+    fn status(api: u32) -> Value {
+        json!({
+            "api": api,
+            "version": "0.0.0",
+            "pid": 1,
+            "scanned_at": null,
+            "sockets": 0,
+            "subscribers": 0,
+            "recording": false,
+        })
+    }
+
+    fn event(port: u16) -> Value {
+        notification(
+            "event",
+            json!(Event {
+                time: Local::now(),
+                event: HistoryEvent::Open,
+                entry: PortEntry::listener(port, "node"),
+                previous: None,
+            }),
+        )
+    }
+
+    #[test]
+    fn refuses_a_daemon_of_another_api_version() {
+        let path = fake_daemon("api", |request| {
+            vec![response(&request["id"], status(API_VERSION + 1))]
+        });
+
+        let error = Client::connect_to(&path).err().unwrap();
+        assert_eq!(
+            error,
+            format!(
+                "the daemon speaks API v{}, this portkill v{}",
+                API_VERSION + 1,
+                API_VERSION
+            )
+        );
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn error_replies_carry_their_message() {
+        let path = fake_daemon("errors", |request| {
+            let id = &request["id"];
+            vec![match request["method"].as_str() {
+                Some("status") => response(id, status(API_VERSION)),
+                Some("kill") => error(id, NOT_PERMITTED, "PID 1 is a system or protected process"),
+                _ => error(id, METHOD_NOT_FOUND, "unknown method"),
+            }]
+        });
+
+        let mut client = Client::connect_to(&path).unwrap();
+        assert_eq!(
+            client.kill(1, None).err().unwrap(),
+            "PID 1 is a system or protected process"
+        );
+        // Each call takes the next id
+        assert_eq!(
+            client.call("restart", Value::Null).err().unwrap(),
+            "unknown method"
+        );
+        assert_eq!(client.next_id, 4);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn subscriptions_yield_events_only() {
+        let path = fake_daemon("subscribe", |request| {
+            let id = &request["id"];
+            match request["method"].as_str() {
+                Some("subscribe") => vec![
+                    response(id, json!({ "subscribed": true })),
+                    event(3000),
+                    notification("action", json!({ "rule": "stale" })),
+                    event(3001),
+                ],
+                _ => vec![response(id, status(API_VERSION))],
+            }
+        });
+
+        let subscription = Client::connect_to(&path)
+            .unwrap()
+            .subscribe(None, false)
+            .unwrap();
+        let ports: Vec<u16> = subscription
+            .take(2)
+            .map(|event| event.unwrap().entry.port)
+            .collect();
+        assert_eq!(ports, vec![3000, 3001]);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,750 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveTime, TimeZone};
+use nix::sys::signal::Signal;
+use nix::unistd::geteuid;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::connections::ListenerConnections;
+use crate::query::{Field, Query};
+use crate::scanner::{Killer, PortEntry, SocketKey, SocketState, UserCache};
+
+// Automatic actions from the [[rules]] in the config, run by `portkill daemon`
+// and `portkill watch --rules` after every scan:
+//
+//   [[rules]]
+//   name = "stale vite"
+//   filter = "port:5173 cmd:~node.*vite"
+//   listening_for = "8h"
+//   max_connections = 0
+//   action = "signal"
+//
+// A rule fires once per socket; it may fire again after the socket closes and
+// is opened anew. Like `watch`, rules only see LISTEN sockets unless their
+// filter names a state.
+#[derive(Clone, Debug)]
+pub struct Rule {
+    pub name: String,
+    pub filter: String,
+    pub query: Query,
+    pub listening_for: Option<Duration>,
+    pub process_age: Option<Duration>,
+    // ESTABLISHED connections accepted by the listener
+    pub min_connections: Option<usize>,
+    pub max_connections: Option<usize>,
+    pub hours: Option<TimeWindow>,
+    pub action: Action,
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    Notify,
+    // None for the [kill] signal and grace period
+    Signal(Option<Signal>),
+    // None for the unit the process runs in
+    StopUnit(Option<String>),
+}
+
+// Local time of day, wrapping past midnight when `end` is before `start`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+// One firing of a rule, as written to the action log
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Outcome {
+    pub time: DateTime<Local>,
+    pub rule: String,
+    pub action: String,
+    pub dry_run: bool,
+    pub ok: bool,
+    pub message: String,
+    pub entry: PortEntry,
+}
+
+pub struct RuleEngine {
+    rules: Vec<Rule>,
+    dry_run: bool,
+    log: Option<PathBuf>,
+    first_seen: HashMap<SocketKey, DateTime<Local>>,
+    fired: HashSet<(usize, SocketKey)>,
+    evaluated: bool,
+}
+
+// Linux reports process start times in USER_HZ ticks, which is 100 on every
+// architecture's userspace ABI
+const USER_HZ: i64 = 100;
+
+impl Action {
+    pub fn parse(name: &str, signal: Option<Signal>, unit: Option<String>) -> Result<Self, String> {
+        let action = match name {
+            "notify" => Action::Notify,
+            "signal" => Action::Signal(signal),
+            "stop-unit" => Action::StopUnit(unit.clone()),
+            _ => {
+                return Err(format!(
+                    "unknown action '{}', expected notify, signal or stop-unit",
+                    name
+                ))
+            }
+        };
+
+        if signal.is_some() && !matches!(action, Action::Signal(_)) {
+            return Err("'signal' only applies to action = \"signal\"".to_string());
+        }
+        if unit.is_some() && !matches!(action, Action::StopUnit(_)) {
+            return Err("'unit' only applies to action = \"stop-unit\"".to_string());
+        }
+        Ok(action)
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Notify => write!(f, "notify"),
+            Action::Signal(Some(signal)) => write!(f, "signal {}", signal),
+            Action::Signal(None) => write!(f, "signal {}", Config::global().kill_signal),
+            Action::StopUnit(Some(unit)) => write!(f, "stop-unit {}", unit),
+            Action::StopUnit(None) => write!(f, "stop-unit"),
+        }
+    }
+}
+
+impl TimeWindow {
+    // "22:00-06:00", or whole hours such as "9-17"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid time window '{}', expected e.g. 22:00-06:00", text);
+        let (start, end) = text.split_once('-').ok_or_else(invalid)?;
+        let time = |part: &str| {
+            let part = match part.trim() {
+                "24" | "24:00" => "00:00",
+                part => part,
+            };
+            NaiveTime::parse_from_str(part, "%H:%M")
+                .or_else(|_| NaiveTime::parse_from_str(&format!("{}:00", part), "%H:%M"))
+                .map_err(|_| invalid())
+        };
+
+        Ok(Self {
+            start: time(start)?,
+            end: time(end)?,
+        })
+    }
+
+    // The same start and end, as in 0-24, is the whole day
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start == self.end {
+            true
+        } else if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl std::fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let end = match self.end.format("%H:%M").to_string() {
+            midnight if midnight == "00:00" => "24:00".to_string(),
+            end => end,
+        };
+        write!(f, "{}-{}", self.start.format("%H:%M"), end)
+    }
+}
+
+impl Rule {
+    fn applies_to(&self, entry: &PortEntry) -> bool {
+        let state_match = self.query.constrains(Field::State) || entry.state == SocketState::Listen;
+        state_match && self.query.matches(entry)
+    }
+
+    // The conditions in words, for `portkill rules`
+    pub fn conditions(&self) -> String {
+        let mut conditions = vec![if self.filter.is_empty() {
+            "any listener".to_string()
+        } else {
+            self.filter.clone()
+        }];
+        if let Some(duration) = self.listening_for {
+            conditions.push(format!("listening >= {}", format_duration(duration)));
+        }
+        if let Some(duration) = self.process_age {
+            conditions.push(format!("process age >= {}", format_duration(duration)));
+        }
+        if let Some(min) = self.min_connections {
+            conditions.push(format!("connections >= {}", min));
+        }
+        if let Some(max) = self.max_connections {
+            conditions.push(format!("connections <= {}", max));
+        }
+        if let Some(hours) = self.hours {
+            conditions.push(format!("between {}", hours));
+        }
+        conditions.join(", ")
+    }
+}
+
+impl RuleEngine {
+    // The configured rules, logging to [actions] log or the default path
+    pub fn from_config(dry_run: bool) -> Self {
+        let config = Config::global();
+        Self::new(
+            config.rules.clone(),
+            dry_run || config.actions_dry_run,
+            Self::log_path(),
+        )
+    }
+
+    pub fn new(rules: Vec<Rule>, dry_run: bool, log: Option<PathBuf>) -> Self {
+        Self {
+            rules,
+            dry_run,
+            log,
+            first_seen: HashMap::new(),
+            fired: HashSet::new(),
+            evaluated: false,
+        }
+    }
+
+    // [actions] log, or actions.ndjson next to the history file
+    pub fn log_path() -> Option<PathBuf> {
+        if let Some(path) = &Config::global().actions_log {
+            return Some(path.clone());
+        }
+
+        let state_home = std::env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state"))
+            })?;
+
+        Some(state_home.join("portkill").join("actions.ndjson"))
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // Checks the rules against a scan and carries out what fires. The returned
+    // outcomes have also been appended to the action log.
+    pub fn evaluate(&mut self, entries: &[PortEntry]) -> Vec<Outcome> {
+        let now = Local::now();
+        self.track(entries, now);
+        if self.rules.is_empty() {
+            return Vec::new();
+        }
+
+        let connections: HashMap<SocketKey, usize> = ListenerConnections::group(entries)
+            .into_iter()
+            .map(|group| {
+                let established = group
+                    .connections
+                    .iter()
+                    .filter(|connection| connection.state == SocketState::Established)
+                    .count();
+                (group.listener.key(), established)
+            })
+            .collect();
+        let boot_time = Self::boot_time();
+
+        let mut outcomes = Vec::new();
+        // A process with several matching sockets is signalled or stopped once
+        let mut acted: HashSet<(usize, u32)> = HashSet::new();
+
+        for (index, rule) in self.rules.iter().enumerate() {
+            if rule.hours.is_some_and(|hours| !hours.contains(now.time())) {
+                continue;
+            }
+
+            for entry in entries.iter().filter(|entry| rule.applies_to(entry)) {
+                let key = entry.key();
+                if self.fired.contains(&(index, key.clone())) {
+                    continue;
+                }
+
+                let listening_since = self.first_seen.get(&key).copied().unwrap_or(now);
+                if rule
+                    .listening_for
+                    .is_some_and(|duration| elapsed(listening_since, now) < duration)
+                {
+                    continue;
+                }
+                if let Some(duration) = rule.process_age {
+                    let started = entry
+                        .pid
+                        .and_then(|pid| Self::process_started(pid, boot_time));
+                    if started.is_none_or(|started| elapsed(started, now) < duration) {
+                        continue;
+                    }
+                }
+                let count = connections.get(&key).copied().unwrap_or(0);
+                if rule.min_connections.is_some_and(|min| count < min)
+                    || rule.max_connections.is_some_and(|max| count > max)
+                {
+                    continue;
+                }
+
+                let process_action = rule.action != Action::Notify;
+                if process_action {
+                    // Signals and units need to know the process
+                    let Some(pid) = entry.pid else {
+                        continue;
+                    };
+                    if !acted.insert((index, pid)) {
+                        self.fired.insert((index, key));
+                        continue;
+                    }
+                }
+
+                self.fired.insert((index, key));
+                let dry_run = self.dry_run || rule.dry_run;
+                outcomes.push(Self::act(rule, entry, dry_run, now));
+            }
+        }
+
+        for outcome in &outcomes {
+            if let Err(e) = self.append(outcome) {
+                log::warn!("Failed to write the action log: {}", e);
+            }
+        }
+        outcomes
+    }
+
+    // Remembers when each socket was first seen and forgets closed ones. The
+    // sockets of the very first scan are taken to be as old as their process.
+    fn track(&mut self, entries: &[PortEntry], now: DateTime<Local>) {
+        let boot_time = (!self.evaluated).then(Self::boot_time).flatten();
+        let current: HashSet<SocketKey> = entries.iter().map(PortEntry::key).collect();
+        self.first_seen.retain(|key, _| current.contains(key));
+        self.fired.retain(|(_, key)| current.contains(key));
+
+        for entry in entries {
+            self.first_seen.entry(entry.key()).or_insert_with(|| {
+                boot_time
+                    .zip(entry.pid)
+                    .and_then(|(boot_time, pid)| Self::process_started(pid, Some(boot_time)))
+                    .unwrap_or(now)
+            });
+        }
+        self.evaluated = true;
+    }
+
+    fn act(rule: &Rule, entry: &PortEntry, dry_run: bool, now: DateTime<Local>) -> Outcome {
+        let pid = entry.pid.unwrap_or_default();
+        let owner = format!(
+            "{} (PID {}) on {}",
+            entry.process_name, pid, entry.local_addr
+        );
+
+        let result = match &rule.action {
+            Action::Notify => Ok(format!(
+                "{} {} on {}",
+                entry.process_name,
+                match entry.pid {
+                    Some(pid) => format!("(PID {})", pid),
+                    None => "(unknown process)".to_string(),
+                },
+                entry.local_addr
+            )),
+            _ if !Killer::can_kill(pid) => Err(format!(
+                "{} is a system or protected process; not touched",
+                owner
+            )),
+            Action::Signal(signal) if dry_run => Ok(format!(
+                "Would send {} to {}",
+                signal.unwrap_or(Config::global().kill_signal),
+                owner
+            )),
+            Action::Signal(Some(signal)) => Killer::kill(pid, *signal),
+            // The daemon and `watch` outlive the grace period
+            Action::Signal(None) => Killer::terminate(pid).map(|termination| termination.message),
+            Action::StopUnit(unit) => match unit.clone().or_else(|| Self::unit_of(pid)) {
+                None => Err(format!("{} doesn't run in a systemd service", owner)),
+                Some(unit) if dry_run => Ok(format!("Would stop {} for {}", unit, owner)),
+                Some(unit) => Self::stop_unit(&unit, pid),
+            },
+        };
+
+        let (ok, message) = match result {
+            Ok(message) => (true, message),
+            Err(message) => (false, message),
+        };
+        Outcome {
+            time: now,
+            rule: rule.name.clone(),
+            action: rule.action.to_string(),
+            dry_run,
+            ok,
+            message,
+            entry: entry.clone(),
+        }
+    }
+
+    // The .service of the process' cgroup, e.g. 0::/system.slice/dev-db.service
+    fn unit_of(pid: u32) -> Option<String> {
+        let cgroup = std::fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()?;
+        let path = cgroup.lines().find_map(|line| line.strip_prefix("0::"))?;
+        path.rsplit('/')
+            .find(|part| part.ends_with(".service") && !part.starts_with("user@"))
+            .map(str::to_string)
+    }
+
+    fn stop_unit(unit: &str, pid: u32) -> Result<String, String> {
+        let manager = std::fs::read_to_string(format!("/proc/{}/cgroup", pid))
+            .ok()
+            .and_then(|cgroup| Self::user_manager(&cgroup));
+        let args = Self::systemctl_args(unit, manager, geteuid().as_raw()).inspect_err(|e| {
+            log::warn!("Not stopping {}: {}", unit, e);
+        })?;
+
+        let output = Command::new("systemctl")
+            .args(&args)
+            .output()
+            .map_err(|e| format!("systemctl: {}", e))?;
+
+        if output.status.success() {
+            Ok(format!("Stopped {}", unit))
+        } else {
+            Err(format!(
+                "systemctl stop {} failed: {}",
+                unit,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+
+    // UID of the user manager a cgroup runs under, from
+    // 0::/user.slice/user-1000.slice/user@1000.service/app.slice/dev.service
+    fn user_manager(cgroup: &str) -> Option<u32> {
+        let path = cgroup.lines().find_map(|line| line.strip_prefix("0::"))?;
+        path.split('/')
+            .find_map(|part| part.strip_prefix("user@")?.strip_suffix(".service"))?
+            .parse()
+            .ok()
+    }
+
+    // A user's units are stopped through their own manager: plain --user when
+    // that is us, --user --machine=<user>@ from root. Anyone else can't.
+    fn systemctl_args(unit: &str, manager: Option<u32>, euid: u32) -> Result<Vec<String>, String> {
+        let mut args = Vec::new();
+        match manager {
+            None => {}
+            Some(uid) if uid == euid => args.push("--user".to_string()),
+            Some(uid) if euid == 0 => {
+                args.push("--user".to_string());
+                args.push(format!("--machine={}@", UserCache::name_for_uid(uid)));
+            }
+            Some(uid) => {
+                return Err(format!(
+                    "{} belongs to the user manager of UID {}, which only that user or root can control",
+                    unit, uid
+                ))
+            }
+        }
+        args.push("stop".to_string());
+        args.push(unit.to_string());
+        Ok(args)
+    }
+
+    fn append(&self, outcome: &Outcome) -> Result<(), String> {
+        let Some(path) = &self.log else {
+            return Ok(());
+        };
+        Self::append_to(path, outcome)
+    }
+
+    fn append_to(path: &Path, outcome: &Outcome) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let line = serde_json::to_string(outcome).map_err(|e| e.to_string())?;
+        writeln!(file, "{}", line).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn boot_time() -> Option<i64> {
+        let stat = std::fs::read_to_string("/proc/stat").ok()?;
+        stat.lines()
+            .find_map(|line| line.strip_prefix("btime "))?
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    fn process_started(pid: u32, boot_time: Option<i64>) -> Option<DateTime<Local>> {
+        let ticks = i64::try_from(Killer::start_time(pid)?).ok()?;
+        let millis = (boot_time? * 1000).checked_add(ticks * 1000 / USER_HZ)?;
+        Local.timestamp_millis_opt(millis).single()
+    }
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} [{}] {}{}: {}",
+            self.time.format("%Y-%m-%d %H:%M:%S"),
+            self.rule,
+            if self.dry_run { "dry run, " } else { "" },
+            self.action,
+            self.message
+        )?;
+        if !self.ok {
+            write!(f, " (failed)")?;
+        }
+        Ok(())
+    }
+}
+
+fn elapsed(since: DateTime<Local>, now: DateTime<Local>) -> Duration {
+    (now - since).to_std().unwrap_or_default()
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        s if s > 0 && s % 86400 == 0 => format!("{}d", s / 86400),
+        s if s > 0 && s % 3600 == 0 => format!("{}h", s / 3600),
+        s if s > 0 && s % 60 == 0 => format!("{}m", s / 60),
+        _ => format!("{:?}", duration),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn notify(name: &str, filter: &str) -> Rule {
+        Rule {
+            name: name.to_string(),
+            filter: filter.to_string(),
+            query: Query::parse(filter).unwrap(),
+            listening_for: None,
+            process_age: None,
+            min_connections: None,
+            max_connections: None,
+            hours: None,
+            action: Action::Notify,
+            dry_run: false,
+        }
+    }
+
+    fn fired(engine: &mut RuleEngine, entries: &[PortEntry]) -> Vec<(String, u16)> {
+        engine
+            .evaluate(entries)
+            .into_iter()
+            .map(|outcome| (outcome.rule, outcome.entry.port))
+            .collect()
+    }
+
+    #[test]
+    fn time_window_parse() {
+        let window = TimeWindow::parse("22:00-06:30").unwrap();
+        assert_eq!((window.start, window.end), (at(22, 0), at(6, 30)));
+        assert_eq!(
+            TimeWindow::parse("9-17").unwrap().to_string(),
+            "09:00-17:00"
+        );
+        assert_eq!(
+            TimeWindow::parse(" 0 - 24 ").unwrap().to_string(),
+            "00:00-24:00"
+        );
+
+        for text in ["22:00", "25-6", "9-17:99", "nine-five", ""] {
+            assert!(TimeWindow::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn time_window_contains() {
+        let office = TimeWindow::parse("9-17").unwrap();
+        assert!(office.contains(at(9, 0)));
+        assert!(office.contains(at(16, 59)));
+        assert!(!office.contains(at(17, 0)));
+        assert!(!office.contains(at(8, 59)));
+
+        let night = TimeWindow::parse("22:00-06:00").unwrap();
+        assert!(night.contains(at(22, 0)));
+        assert!(night.contains(at(0, 0)));
+        assert!(night.contains(at(5, 59)));
+        assert!(!night.contains(at(6, 0)));
+        assert!(!night.contains(at(12, 0)));
+
+        let always = TimeWindow::parse("0-24").unwrap();
+        assert!(always.contains(at(0, 0)));
+        assert!(always.contains(at(23, 59)));
+    }
+
+    #[test]
+    fn fires_once_per_socket() {
+        let mut engine =
+            RuleEngine::new(vec![notify("dev servers", "port:3000-3999")], false, None);
+        let vite = PortEntry::listener(3000, "vite");
+        let postgres = PortEntry::listener(5432, "postgres");
+
+        assert_eq!(
+            fired(&mut engine, &[vite.clone(), postgres.clone()]),
+            [("dev servers".to_string(), 3000)]
+        );
+        assert!(fired(&mut engine, &[vite.clone(), postgres.clone()]).is_empty());
+
+        // Another socket on the same port is a new chance
+        let next = PortEntry {
+            inode: Some(1),
+            ..vite.clone()
+        };
+        assert_eq!(fired(&mut engine, &[vite.clone(), next.clone()]).len(), 1);
+
+        // So is the same socket after it closed and came back
+        assert!(fired(&mut engine, std::slice::from_ref(&next)).is_empty());
+        assert_eq!(fired(&mut engine, &[vite, next]).len(), 1);
+    }
+
+    #[test]
+    fn rules_fire_independently() {
+        let mut engine = RuleEngine::new(
+            vec![notify("any", ""), notify("node", "proc:node")],
+            false,
+            None,
+        );
+        let entries = vec![
+            PortEntry::listener(3000, "node"),
+            PortEntry::listener(5432, "postgres"),
+            // Only LISTEN sockets unless the filter names a state
+            PortEntry {
+                state: SocketState::Established,
+                remote_addr: "10.0.0.2:50000".to_string(),
+                inode: Some(1),
+                ..PortEntry::listener(3000, "node")
+            },
+        ];
+
+        let mut outcomes = fired(&mut engine, &entries);
+        outcomes.sort();
+        assert_eq!(
+            outcomes,
+            [
+                ("any".to_string(), 3000),
+                ("any".to_string(), 5432),
+                ("node".to_string(), 3000)
+            ]
+        );
+        assert!(fired(&mut engine, &entries).is_empty());
+    }
+
+    #[test]
+    fn waits_for_listening_for() {
+        let rule = Rule {
+            listening_for: Some(Duration::from_secs(3600)),
+            ..notify("stale", "")
+        };
+        let mut engine = RuleEngine::new(vec![rule], false, None);
+        // Without a process the socket is taken to be new
+        assert!(fired(&mut engine, &[PortEntry::listener(3000, "node")]).is_empty());
+    }
+
+    #[test]
+    fn connection_limits() {
+        let listener = PortEntry::listener(3000, "node");
+        let connection = PortEntry {
+            state: SocketState::Established,
+            local_addr: "127.0.0.1:3000".to_string(),
+            remote_addr: "127.0.0.1:50000".to_string(),
+            inode: Some(1),
+            ..listener.clone()
+        };
+        let idle = Rule {
+            max_connections: Some(0),
+            ..notify("idle", "")
+        };
+        let busy = Rule {
+            min_connections: Some(1),
+            ..notify("busy", "")
+        };
+
+        let mut engine = RuleEngine::new(vec![idle.clone(), busy.clone()], false, None);
+        assert_eq!(
+            fired(&mut engine, &[listener.clone(), connection]),
+            [("busy".to_string(), 3000)]
+        );
+        let mut engine = RuleEngine::new(vec![idle, busy], false, None);
+        assert_eq!(
+            fired(&mut engine, &[listener]),
+            [("idle".to_string(), 3000)]
+        );
+    }
+
+    #[test]
+    fn durations_in_words() {
+        assert_eq!(format_duration(Duration::from_secs(2 * 86400)), "2d");
+        assert_eq!(format_duration(Duration::from_secs(8 * 3600)), "8h");
+        assert_eq!(format_duration(Duration::from_secs(90 * 60)), "90m");
+        assert_eq!(format_duration(Duration::from_secs(90)), "90s");
+    }
+
+    #[test]
+    fn user_manager_from_the_cgroup() {
+        assert_eq!(
+            RuleEngine::user_manager(
+                "0::/user.slice/user-1000.slice/user@1000.service/app.slice/dev.service\n"
+            ),
+            Some(1000)
+        );
+        assert_eq!(
+            RuleEngine::user_manager("0::/system.slice/postgresql.service\n"),
+            None
+        );
+        // cgroup v1 hierarchies are ignored
+        assert_eq!(
+            RuleEngine::user_manager("1:name=systemd:/user.slice/user@1000.service/dev.service\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn user_units_are_stopped_through_their_manager() {
+        let args = |manager, euid| RuleEngine::systemctl_args("dev.service", manager, euid);
+
+        assert_eq!(args(None, 1000).unwrap(), ["stop", "dev.service"]);
+        assert_eq!(args(None, 0).unwrap(), ["stop", "dev.service"]);
+        assert_eq!(
+            args(Some(1000), 1000).unwrap(),
+            ["--user", "stop", "dev.service"]
+        );
+        assert_eq!(
+            args(Some(1000), 0).unwrap(),
+            [
+                "--user".to_string(),
+                format!("--machine={}@", UserCache::name_for_uid(1000)),
+                "stop".to_string(),
+                "dev.service".to_string(),
+            ]
+        );
+        let error = args(Some(1000), 1001).unwrap_err();
+        assert!(error.contains("UID 1000"), "{}", error);
+    }
+}
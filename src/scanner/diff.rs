@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{PortEntry, Protocol};
+
+// Identifies one socket across scans. The inode distinguishes a socket that was
+// closed and reopened on the same address pair between two scans.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SocketKey {
+    pub protocol: Protocol,
+    pub local_addr: String,
+    pub remote_addr: String,
+    pub inode: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    New,
+    Gone,
+    Changed,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EntryChange {
+    pub kind: ChangeKind,
+    pub entry: PortEntry,
+    // The entry as seen in the previous scan, for `Changed`
+    pub previous: Option<PortEntry>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotDiff {
+    pub changes: Vec<EntryChange>,
+}
+
+impl SnapshotDiff {
+    pub fn between(previous: &[PortEntry], current: &[PortEntry]) -> Self {
+        let before: HashMap<SocketKey, &PortEntry> =
+            previous.iter().map(|entry| (entry.key(), entry)).collect();
+        let after: HashMap<SocketKey, &PortEntry> =
+            current.iter().map(|entry| (entry.key(), entry)).collect();
+
+        let mut changes = Vec::new();
+
+        for entry in current {
+            match before.get(&entry.key()) {
+                None => changes.push(EntryChange {
+                    kind: ChangeKind::New,
+                    entry: entry.clone(),
+                    previous: None,
+                }),
+                Some(old) if Self::has_changed(old, entry) => changes.push(EntryChange {
+                    kind: ChangeKind::Changed,
+                    entry: entry.clone(),
+                    previous: Some((*old).clone()),
+                }),
+                Some(_) => {}
+            }
+        }
+
+        for entry in previous {
+            if !after.contains_key(&entry.key()) {
+                changes.push(EntryChange {
+                    kind: ChangeKind::Gone,
+                    entry: entry.clone(),
+                    previous: None,
+                });
+            }
+        }
+
+        Self { changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn of_kind(&self, kind: ChangeKind) -> impl Iterator<Item = &EntryChange> {
+        self.changes
+            .iter()
+            .filter(move |change| change.kind == kind)
+    }
+
+    // State transitions and ownership changes; process details such as the
+    // cmdline are not compared
+    fn has_changed(old: &PortEntry, new: &PortEntry) -> bool {
+        old.state != new.state || old.pid != new.pid || old.user != new.user
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::SocketState;
+
+    fn kinds(diff: &SnapshotDiff) -> Vec<(ChangeKind, u16)> {
+        diff.changes
+            .iter()
+            .map(|change| (change.kind, change.entry.port))
+            .collect()
+    }
+
+    #[test]
+    fn new_gone_and_unchanged() {
+        let before = vec![
+            PortEntry::listener(3000, "node"),
+            PortEntry::listener(5432, "postgres"),
+        ];
+        let after = vec![
+            PortEntry::listener(3000, "node"),
+            PortEntry::listener(8080, "java"),
+        ];
+
+        let diff = SnapshotDiff::between(&before, &after);
+        assert_eq!(
+            kinds(&diff),
+            [(ChangeKind::New, 8080), (ChangeKind::Gone, 5432)]
+        );
+        assert_eq!(diff.of_kind(ChangeKind::Gone).count(), 1);
+        assert!(SnapshotDiff::between(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn state_and_owner_changes() {
+        let before = vec![
+            PortEntry::listener(3000, "node"),
+            PortEntry::listener(3001, "node"),
+            PortEntry::listener(3002, "node"),
+            PortEntry::listener(3003, "node"),
+        ];
+        let after = vec![
+            PortEntry {
+                state: SocketState::CloseWait,
+                ..before[0].clone()
+            },
+            PortEntry {
+                pid: Some(4242),
+                ..before[1].clone()
+            },
+            PortEntry {
+                user: "root".to_string(),
+                ..before[2].clone()
+            },
+            // The command line isn't compared
+            PortEntry {
+                cmdline: "node server.js".to_string(),
+                ..before[3].clone()
+            },
+        ];
+
+        let diff = SnapshotDiff::between(&before, &after);
+        assert_eq!(
+            kinds(&diff),
+            [
+                (ChangeKind::Changed, 3000),
+                (ChangeKind::Changed, 3001),
+                (ChangeKind::Changed, 3002)
+            ]
+        );
+        let previous = diff.changes[0].previous.as_ref().unwrap();
+        assert_eq!(previous.state, SocketState::Listen);
+    }
+
+    #[test]
+    fn reopened_socket_is_new() {
+        let before = vec![PortEntry::listener(3000, "node")];
+        let after = vec![PortEntry {
+            inode: Some(99),
+            ..before[0].clone()
+        }];
+
+        let diff = SnapshotDiff::between(&before, &after);
+        assert_eq!(
+            kinds(&diff),
+            [(ChangeKind::New, 3000), (ChangeKind::Gone, 3000)]
+        );
+    }
+}
@@ -1,11 +1,47 @@
+pub mod diff;
 pub mod models;
 pub mod process_info;
+pub mod project;
+#[cfg(target_os = "linux")]
+pub mod sock_diag;
+pub mod user_cache;
+pub mod worker;
 
-pub use models::{PortEntry, Protocol, Scanner, SocketState};
+pub use diff::{ChangeKind, EntryChange, SnapshotDiff, SocketKey};
+pub use models::{Backlog, PortEntry, Protocol, Scanner, SocketMetrics, SocketState, TcpTimer};
 pub use process_info::ProcessInfo;
+pub use project::Project;
+pub use user_cache::UserCache;
+pub use worker::{ScanEvent, ScanWorker};
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::config::Config;
 
 pub struct Killer;
 
+// A signal sent by `Killer::terminate`, with the SIGKILL that may follow. Dropping
+// it leaves the escalation to a background thread, which only lives as long as
+// this process; short-lived callers must `wait` for it.
+pub struct Termination {
+    pub message: String,
+    escalation: Option<std::thread::JoinHandle<Option<Result<String, String>>>>,
+}
+
+impl Termination {
+    // Blocks until the process exits or the grace period is over. Returns the
+    // SIGKILL's outcome if one had to be sent.
+    pub fn wait(self) -> Option<Result<String, String>> {
+        self.escalation?.join().ok().flatten()
+    }
+}
+
+const ESCALATION_POLL: std::time::Duration = std::time::Duration::from_millis(100);
+
+// Signals sent by this process, by signal name and whether they were delivered
+static KILLS: Mutex<BTreeMap<(String, bool), u64>> = Mutex::new(BTreeMap::new());
+
 impl Killer {
     pub fn kill(pid: u32, signal: nix::sys::signal::Signal) -> Result<String, String> {
         use nix::sys::signal;
@@ -15,7 +51,14 @@ impl Killer {
         let process_name = Self::get_process_name(pid);
 
         // Use nix::sys::signal::kill directly
-        match signal::kill(Pid::from_raw(pid as i32), signal) {
+        let result = signal::kill(Pid::from_raw(pid as i32), signal);
+        *KILLS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry((signal.to_string(), result.is_ok()))
+            .or_default() += 1;
+
+        match result {
             Ok(_) => Ok(format!(
                 "Successfully sent {} to {} (PID {})",
                 signal, process_name, pid
@@ -29,7 +72,10 @@ impl Killer {
                 } else if e == nix::errno::Errno::ESRCH {
                     Err(format!("Process {} (PID {}) not found", process_name, pid))
                 } else {
-                    Err(format!("Failed to kill {} (PID {}): {}", process_name, pid, e))
+                    Err(format!(
+                        "Failed to kill {} (PID {}): {}",
+                        process_name, pid, e
+                    ))
                 }
             }
         }
@@ -63,6 +109,16 @@ impl Killer {
         format!("PID {}", pid)
     }
 
+    // (signal, delivered, count) for every kill since the process started
+    pub fn kill_counts() -> Vec<(String, bool, u64)> {
+        KILLS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|((signal, delivered), count)| (signal.clone(), *delivered, *count))
+            .collect()
+    }
+
     pub fn kill_sigterm(pid: u32) -> Result<String, String> {
         Self::kill(pid, nix::sys::signal::Signal::SIGTERM)
     }
@@ -71,19 +127,95 @@ impl Killer {
         Self::kill(pid, nix::sys::signal::Signal::SIGKILL)
     }
 
+    // Sends the configured signal. With a grace period, the process is sent
+    // SIGKILL from a background thread if it is still running once the period
+    // is over; see `Termination`.
+    pub fn terminate(pid: u32) -> Result<Termination, String> {
+        let config = Config::global();
+        let signal = config.kill_signal;
+        let started = Self::start_time(pid);
+
+        let message = Self::kill(pid, signal)?;
+        if signal == nix::sys::signal::Signal::SIGKILL || config.grace_period.is_zero() {
+            return Ok(Termination {
+                message,
+                escalation: None,
+            });
+        }
+
+        let grace_period = config.grace_period;
+        let escalation = std::thread::spawn(move || {
+            // None when the grace period is too long to ever run out
+            let deadline = std::time::Instant::now().checked_add(grace_period);
+            // The PID may be reused by an unrelated process in the meantime
+            let still_running = || started.is_some() && Self::start_time(pid) == started;
+            while deadline.is_none_or(|deadline| std::time::Instant::now() < deadline) {
+                if !still_running() {
+                    return None;
+                }
+                std::thread::sleep(ESCALATION_POLL.min(grace_period));
+            }
+            if !still_running() {
+                return None;
+            }
+
+            let result = Self::kill_sigkill(pid);
+            match &result {
+                Ok(message) => log::info!("Grace period expired: {}", message),
+                Err(e) => log::warn!("Grace period expired: {}", e),
+            }
+            Some(result)
+        });
+
+        Ok(Termination {
+            message: format!(
+                "{}; SIGKILL in {:?} if still running",
+                message, grace_period
+            ),
+            escalation: Some(escalation),
+        })
+    }
+
+    // Distinguishes a process from a later one that reuses its PID; clock ticks
+    // since boot
+    pub fn start_time(pid: u32) -> Option<u64> {
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        // The command name may contain spaces, so count fields after its closing ')'
+        let (_, rest) = stat.rsplit_once(')')?;
+        rest.split_whitespace().nth(19)?.parse().ok()
+    }
+
     pub fn can_kill(pid: u32) -> bool {
         // Never kill PID 1
         if pid == 1 {
             return false;
         }
 
-        // Check if it's a system process
+        // Check if it's a system process or protected by the config
         #[cfg(target_os = "linux")]
         {
             if let Ok(contents) = std::fs::read_to_string(format!("/proc/{}/status", pid)) {
                 if contents.contains("Name:\tsystemd") || contents.contains("Name:\tinit") {
                     return false;
                 }
+
+                let field = |name: &str| {
+                    contents
+                        .lines()
+                        .find_map(|line| line.strip_prefix(name))
+                        .map(str::trim)
+                };
+                let process_name = field("Name:").unwrap_or_default();
+                // Uid:\t<real>\t<effective>\t<saved>\t<filesystem>
+                let user = field("Uid:")
+                    .and_then(|uids| uids.split_whitespace().nth(1))
+                    .and_then(|euid| euid.parse::<u32>().ok())
+                    .map(UserCache::name_for_uid)
+                    .unwrap_or_default();
+
+                if Config::global().is_protected(process_name, &user) {
+                    return false;
+                }
             }
         }
 
@@ -94,8 +226,14 @@ impl Killer {
                 .args(&["-p", &pid.to_string(), "-o", "comm="])
                 .output()
             {
-                let name = String::from_utf8_lossy(&output.stdout).to_lowercase();
-                if name.contains("kernel") || name.contains("launchd") {
+                let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                let lowercase = name.to_lowercase();
+                if lowercase.contains("kernel") || lowercase.contains("launchd") {
+                    return false;
+                }
+
+                let process_name = name.rsplit('/').next().unwrap_or_default();
+                if Config::global().is_protected(process_name, "") {
                     return false;
                 }
             }
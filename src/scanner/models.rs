@@ -1,11 +1,26 @@
 use std::io::{BufRead, BufReader, Error};
 
-#[derive(Clone, Debug, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+use super::{ProcessInfo, Project, SocketKey};
+use crate::firewall::FirewallVerdict;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
 pub enum Protocol {
     Tcp,
     Tcp6,
 }
 
+impl Protocol {
+    // Transport name as used by /etc/services
+    pub fn transport(&self) -> &'static str {
+        match self {
+            Protocol::Tcp | Protocol::Tcp6 => "tcp",
+        }
+    }
+}
+
 impl std::fmt::Display for Protocol {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -15,7 +30,8 @@ impl std::fmt::Display for Protocol {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum SocketState {
     Established,
     Listen,
@@ -31,12 +47,84 @@ impl std::fmt::Display for SocketState {
             SocketState::Listen => write!(f, "LISTEN"),
             SocketState::TimeWait => write!(f, "TIME_WAIT"),
             SocketState::CloseWait => write!(f, "CLOSE_WAIT"),
+            // The remaining states from include/net/tcp_states.h
+            SocketState::Other(0x02) => write!(f, "SYN_SENT"),
+            SocketState::Other(0x03) => write!(f, "SYN_RECV"),
+            SocketState::Other(0x04) => write!(f, "FIN_WAIT1"),
+            SocketState::Other(0x05) => write!(f, "FIN_WAIT2"),
+            SocketState::Other(0x07) => write!(f, "CLOSE"),
+            SocketState::Other(0x09) => write!(f, "LAST_ACK"),
+            SocketState::Other(0x0B) => write!(f, "CLOSING"),
             SocketState::Other(code) => write!(f, "Other({})", code),
         }
     }
 }
 
-#[derive(Clone, Debug)]
+// Which TCP timer is pending, the `tr` column of /proc/net/tcp
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TcpTimer {
+    #[default]
+    None,
+    // Also covers loss probes and reordering timeouts
+    Retransmit,
+    KeepAlive,
+    TimeWait,
+    ZeroWindowProbe,
+}
+
+impl std::fmt::Display for TcpTimer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TcpTimer::None => write!(f, "-"),
+            TcpTimer::Retransmit => write!(f, "retransmit"),
+            TcpTimer::KeepAlive => write!(f, "keepalive"),
+            TcpTimer::TimeWait => write!(f, "time_wait"),
+            TcpTimer::ZeroWindowProbe => write!(f, "zero_window_probe"),
+        }
+    }
+}
+
+// Kernel bookkeeping from /proc/net/tcp; lsof doesn't report it
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SocketMetrics {
+    // Bytes sent but not yet acknowledged. For listeners, the backlog limit
+    // from sock_diag, 0 when it couldn't be queried.
+    pub tx_queue: u32,
+    // Bytes received but not yet read. For listeners, connections waiting
+    // to be accepted.
+    pub rx_queue: u32,
+    pub timer: TcpTimer,
+    pub timer_expires_ms: u64,
+    // Retransmissions of the oldest unacknowledged segment
+    pub retransmits: u32,
+    // Unanswered zero-window or keepalive probes
+    pub probes: u32,
+    // Socket owner as recorded by the kernel, known even without the process
+    pub uid: u32,
+}
+
+// A listener's accept queue: connections the kernel completed that the
+// application hasn't accept()ed yet, against the listen() backlog
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Backlog {
+    pub queued: u32,
+    pub limit: u32,
+    pub percent: f64,
+}
+
+impl Backlog {
+    // Same test as the kernel's sk_acceptq_is_full(): the queue takes one
+    // connection beyond `limit`, and new handshakes are dropped after that
+    pub fn is_full(&self) -> bool {
+        self.queued > self.limit
+    }
+}
+
+// Unread data above this is worth pointing out; a few KiB come and go
+pub const RX_QUEUE_WARN_BYTES: u32 = 64 * 1024;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PortEntry {
     pub port: u16,
     pub protocol: Protocol,
@@ -48,11 +136,166 @@ pub struct PortEntry {
     pub process_name: String,
     pub cmdline: String,
     pub user: String,
+    pub real_user: String,
+    pub uid: Option<u32>,
+    pub euid: Option<u32>,
+    pub project: Option<Project>,
+    // Listeners only; None for loopback addresses
+    pub firewall: Option<FirewallVerdict>,
+    pub metrics: Option<SocketMetrics>,
+}
+
+impl PortEntry {
+    pub fn key(&self) -> SocketKey {
+        SocketKey {
+            protocol: self.protocol.clone(),
+            local_addr: self.local_addr.clone(),
+            remote_addr: self.remote_addr.clone(),
+            inode: self.inode,
+        }
+    }
+
+    // Listeners only, from Recv-Q (queued) and Send-Q (backlog limit); None
+    // while the limit is unknown
+    pub fn backlog(&self) -> Option<Backlog> {
+        if self.state != SocketState::Listen {
+            return None;
+        }
+        let metrics = self
+            .metrics
+            .as_ref()
+            .filter(|metrics| metrics.tx_queue > 0)?;
+        let percent = (metrics.rx_queue as f64 * 100.0 / metrics.tx_queue as f64).min(100.0);
+
+        Some(Backlog {
+            queued: metrics.rx_queue,
+            limit: metrics.tx_queue,
+            percent,
+        })
+    }
+
+    // Signs the socket is stuck: a listener whose application isn't accepting,
+    // unread data piling up, or a peer that stopped answering
+    pub fn health_issues(&self) -> Vec<String> {
+        let Some(metrics) = &self.metrics else {
+            return Vec::new();
+        };
+        let mut issues = Vec::new();
+
+        if let Some(backlog) = self.backlog() {
+            if backlog.is_full() {
+                issues.push(format!(
+                    "Accept queue full ({} of {}): new connections are dropped until the application accepts",
+                    backlog.queued, backlog.limit
+                ));
+            } else if backlog.queued > 0 {
+                issues.push(format!(
+                    "{} of {} backlog slots hold connections the application hasn't accepted",
+                    backlog.queued, backlog.limit
+                ));
+            }
+            return issues;
+        }
+        if self.state == SocketState::Listen {
+            if metrics.rx_queue > 0 {
+                issues.push(format!(
+                    "{} connections the application hasn't accepted",
+                    metrics.rx_queue
+                ));
+            }
+            return issues;
+        }
+
+        if metrics.rx_queue >= RX_QUEUE_WARN_BYTES {
+            issues.push(format!(
+                "{} bytes received but not read by the application",
+                metrics.rx_queue
+            ));
+        }
+        if metrics.retransmits > 0 {
+            issues.push(format!(
+                "{} retransmits without an acknowledgement; the peer may be gone",
+                metrics.retransmits
+            ));
+        }
+        if metrics.probes > 0 {
+            issues.push(format!(
+                "{} probes left unanswered by the peer",
+                metrics.probes
+            ));
+        }
+
+        issues
+    }
+
+    // A setuid process runs with an effective UID different from the user who started it
+    pub fn is_setuid(&self) -> bool {
+        matches!((self.uid, self.euid), (Some(uid), Some(euid)) if uid != euid)
+    }
 }
 
 pub struct Scanner;
 
 impl Scanner {
+    // Full scan: TCP and TCP6 sockets with owning process information
+    pub fn scan_all() -> Result<Vec<PortEntry>, Error> {
+        Self::scan_all_with_progress(|_, _| true).map(Option::unwrap_or_default)
+    }
+
+    // Like `scan_all`, reporting process-mapping progress as (done, total).
+    // Returns Ok(None) when `progress` returns false to cancel the scan.
+    pub fn scan_all_with_progress<F>(progress: F) -> Result<Option<Vec<PortEntry>>, Error>
+    where
+        F: FnMut(usize, usize) -> bool,
+    {
+        let mut entries = Self::scan_sockets()?;
+
+        if ProcessInfo::map_all(&mut entries, progress) {
+            Ok(Some(entries))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // TCP and TCP6 sockets without process information; much cheaper than
+    // `scan_all` when only addresses and states are needed
+    pub fn scan_sockets() -> Result<Vec<PortEntry>, Error> {
+        let mut entries = Self::scan_tcp()?;
+
+        match Self::scan_tcp6() {
+            Ok(mut tcp6_entries) => entries.append(&mut tcp6_entries),
+            Err(e) => log::warn!("Failed to scan TCP6: {}", e),
+        }
+
+        #[cfg(target_os = "linux")]
+        Self::read_accept_queues(&mut entries);
+
+        Ok(entries)
+    }
+
+    // Fills in the backlog limit of listeners, which /proc/net/tcp leaves out
+    #[cfg(target_os = "linux")]
+    fn read_accept_queues(entries: &mut [PortEntry]) {
+        let queues = match super::sock_diag::SockDiag::accept_queues() {
+            Ok(queues) => queues,
+            Err(e) => {
+                log::debug!("Listen backlogs unavailable: {}", e);
+                return;
+            }
+        };
+
+        for entry in entries
+            .iter_mut()
+            .filter(|entry| entry.state == SocketState::Listen)
+        {
+            let queue = entry.inode.and_then(|inode| queues.get(&inode));
+            if let (Some(queue), Some(metrics)) = (queue, entry.metrics.as_mut()) {
+                metrics.rx_queue = queue.queued;
+                metrics.tx_queue = queue.limit;
+            }
+        }
+    }
+
     pub fn scan_tcp() -> Result<Vec<PortEntry>, Error> {
         // macOS fallback using lsof
         if cfg!(target_os = "macos") {
@@ -114,7 +357,9 @@ impl Scanner {
         let mut entries = Vec::new();
 
         for line in output_str.lines().skip(1) {
-            if let Some(entry) = Self::parse_lsof_line(line, if ipv6 { Protocol::Tcp6 } else { Protocol::Tcp }) {
+            if let Some(entry) =
+                Self::parse_lsof_line(line, if ipv6 { Protocol::Tcp6 } else { Protocol::Tcp })
+            {
                 entries.push(entry);
             }
         }
@@ -124,7 +369,10 @@ impl Scanner {
 
     #[cfg(not(target_os = "macos"))]
     fn scan_with_lsof(_ipv6: bool) -> Result<Vec<PortEntry>, Error> {
-        Err(Error::new(std::io::ErrorKind::Unsupported, "lsof not supported"))
+        Err(Error::new(
+            std::io::ErrorKind::Unsupported,
+            "lsof not supported",
+        ))
     }
 
     #[cfg(target_os = "macos")]
@@ -187,14 +435,15 @@ impl Scanner {
             process_name,
             cmdline: String::new(),
             user,
+            real_user: String::new(),
+            uid: None,
+            euid: None,
+            project: None,
+            firewall: None,
+            metrics: None,
         })
     }
 
-    #[cfg(not(target_os = "macos"))]
-    fn parse_lsof_line(_line: &str, _protocol: Protocol) -> Option<PortEntry> {
-        None
-    }
-
     fn parse_line(line: &str, protocol: Protocol) -> Option<PortEntry> {
         let fields: Vec<&str> = line.split_whitespace().collect();
 
@@ -205,7 +454,9 @@ impl Scanner {
         let local_addr_hex = fields[1];
         let rem_addr_hex = fields[2];
         let state_hex = fields[3];
+        let uid_str = fields[7];
         let inode_str = fields[9];
+        let metrics = Self::parse_metrics(&fields);
 
         let local_port = Self::parse_port(local_addr_hex)?;
         let _remote_port = Self::parse_port(rem_addr_hex);
@@ -221,6 +472,9 @@ impl Scanner {
             None
         };
 
+        // Socket owner UID; refined from /proc/<pid>/status once the owning process is known
+        let uid = uid_str.parse::<u32>().ok();
+
         Some(PortEntry {
             port: local_port,
             protocol,
@@ -232,6 +486,43 @@ impl Scanner {
             process_name: String::new(),
             cmdline: String::new(),
             user: String::new(),
+            real_user: String::new(),
+            uid,
+            euid: uid,
+            project: None,
+            firewall: None,
+            metrics,
+        })
+    }
+
+    // tx_queue:rx_queue tr:tm->when retrnsmt uid timeout
+    fn parse_metrics(fields: &[&str]) -> Option<SocketMetrics> {
+        let hex = |text: &str| u32::from_str_radix(text, 16).ok();
+
+        let (tx_queue, rx_queue) = fields[4].split_once(':')?;
+        let (timer, expires) = fields[5].split_once(':')?;
+        let timer = match hex(timer)? {
+            1 => TcpTimer::Retransmit,
+            2 => TcpTimer::KeepAlive,
+            3 => TcpTimer::TimeWait,
+            4 => TcpTimer::ZeroWindowProbe,
+            _ => TcpTimer::None,
+        };
+        // Printed in clock ticks, USER_HZ = 100 on every Linux architecture
+        let expires_ticks = u64::from_str_radix(expires, 16).ok()?;
+
+        Some(SocketMetrics {
+            tx_queue: hex(tx_queue)?,
+            rx_queue: hex(rx_queue)?,
+            timer,
+            timer_expires_ms: if timer == TcpTimer::None {
+                0
+            } else {
+                expires_ticks * 10
+            },
+            retransmits: hex(fields[6])?,
+            probes: fields[8].parse().ok()?,
+            uid: fields[7].parse().ok()?,
         })
     }
 
@@ -276,31 +567,169 @@ impl Scanner {
             return String::new();
         }
 
-        let ip_part = parts[0];
-        let port_part = parts[1];
+        let ip = match Self::parse_ip_hex(parts[0]) {
+            Some(ip) => ip,
+            None => return String::new(),
+        };
 
-        let ip = Self::parse_ip_hex(ip_part);
+        match u16::from_str_radix(parts[1], 16) {
+            Ok(port) => std::net::SocketAddr::new(ip, port).to_string(),
+            Err(_) => ip.to_string(),
+        }
+    }
 
-        if let Some(port) = Self::parse_port(port_part) {
-            format!("{}:{}", ip, port)
-        } else {
-            ip
+    // /proc/net/tcp{,6} prints addresses as 32-bit words in host (little-endian) order
+    fn parse_ip_hex(hex: &str) -> Option<std::net::IpAddr> {
+        let mut bytes = Vec::with_capacity(16);
+        for chunk in hex.as_bytes().chunks(8) {
+            let word = u32::from_str_radix(std::str::from_utf8(chunk).ok()?, 16).ok()?;
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+
+        match bytes.len() {
+            4 => Some(std::net::IpAddr::from([
+                bytes[0], bytes[1], bytes[2], bytes[3],
+            ])),
+            16 => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(&bytes);
+                let ip = std::net::Ipv6Addr::from(octets);
+                // Show v4-mapped addresses (::ffff:a.b.c.d) the way ss does
+                Some(match ip.to_ipv4_mapped() {
+                    Some(v4) => std::net::IpAddr::V4(v4),
+                    None => std::net::IpAddr::V6(ip),
+                })
+            }
+            _ => None,
         }
     }
+}
 
-    fn parse_ip_hex(hex: &str) -> String {
-        if hex.is_empty() || hex.len() < 8 {
-            return String::new();
+// A listener on every IPv4 address with no known process; tests adjust the
+// fields they care about
+#[cfg(test)]
+impl PortEntry {
+    pub(crate) fn listener(port: u16, process: &str) -> Self {
+        PortEntry {
+            port,
+            protocol: Protocol::Tcp,
+            state: SocketState::Listen,
+            local_addr: format!("0.0.0.0:{}", port),
+            remote_addr: "0.0.0.0:0".to_string(),
+            inode: Some(u32::from(port)),
+            pid: None,
+            process_name: process.to_string(),
+            cmdline: process.to_string(),
+            user: "dev".to_string(),
+            real_user: "dev".to_string(),
+            uid: Some(1000),
+            euid: Some(1000),
+            project: None,
+            firewall: None,
+            metrics: None,
         }
+    }
+}
 
-        let bytes: Vec<u8> = hex
-            .as_bytes()
-            .chunks(2)
-            .rev()
-            .take(4)
-            .map(|chunk| u8::from_str_radix(std::str::from_utf8(chunk).unwrap(), 16).unwrap_or(0))
-            .collect();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setuid_when_the_effective_uid_differs() {
+        let entry = PortEntry::listener(8080, "node");
+        assert!(!entry.is_setuid());
+        assert!(PortEntry {
+            euid: Some(0),
+            ..entry.clone()
+        }
+        .is_setuid());
+        // The socket owner alone says nothing about setuid
+        assert!(!PortEntry {
+            uid: None,
+            euid: Some(0),
+            ..entry
+        }
+        .is_setuid());
+    }
+
+    #[test]
+    fn backlog_is_full_past_the_limit() {
+        let backlog = |queued| Backlog {
+            queued,
+            limit: 128,
+            percent: 0.0,
+        };
+        assert!(!backlog(0).is_full());
+        assert!(!backlog(128).is_full());
+        assert!(backlog(129).is_full());
+    }
+
+    #[test]
+    fn metrics_from_proc_net_tcp_lines() {
+        let metrics = |line: &str| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            Scanner::parse_metrics(&fields)
+        };
+        let expected = |tx_queue, rx_queue, timer, timer_expires_ms, retransmits, probes, uid| {
+            Some(SocketMetrics {
+                tx_queue,
+                rx_queue,
+                timer,
+                timer_expires_ms,
+                retransmits,
+                probes,
+                uid,
+            })
+        };
+
+        let cases = [
+            // A listener: rx_queue is the accept queue, tx_queue the backlog
+            (
+                "   0: 0100007F:1F90 00000000:0000 0A 00000080:00000003 00:00000000 00000000  1000        0 41811 1 0000000000000000 100 0 0 10 0",
+                expected(128, 3, TcpTimer::None, 0, 0, 0, 1000),
+            ),
+            // Unacknowledged data with the retransmit timer due in 20 ticks
+            (
+                "   1: 0500000A:9C40 0900000A:01BB 01 00000A3C:00000000 01:00000014 00000003  1000        0 41812 2 0000000000000000 20 4 30 10 -1",
+                expected(2620, 0, TcpTimer::Retransmit, 200, 3, 0, 1000),
+            ),
+            (
+                "   2: 0500000A:9C41 0900000A:1538 01 00000000:000001F4 02:0000AFC8 00000000  1000        0 41813 1 0000000000000000 20 4 30 10 -1",
+                expected(0, 500, TcpTimer::KeepAlive, 450_000, 0, 0, 1000),
+            ),
+            (
+                "   3: 0500000A:9C42 0900000A:01BB 06 00000000:00000000 03:00000BB8 00000000     0        0 0 3 0000000000000000",
+                expected(0, 0, TcpTimer::TimeWait, 30_000, 0, 0, 0),
+            ),
+            // The peer's window is closed and five probes went unanswered
+            (
+                "   4: 0500000A:9C43 0900000A:01BB 01 0001F400:00000000 04:000000C8 00000000  1000        5 41814 2 0000000000000000 20 4 30 10 -1",
+                expected(128_000, 0, TcpTimer::ZeroWindowProbe, 2000, 0, 5, 1000),
+            ),
+            // Leftover ticks without a timer running mean nothing
+            (
+                "   5: 0500000A:9C44 0900000A:01BB 01 00000000:00000000 00:00000123 00000000  1000        0 41815 1 0000000000000000 20 4 30 10 -1",
+                expected(0, 0, TcpTimer::None, 0, 0, 0, 1000),
+            ),
+        ];
+
+        for (line, expected) in cases {
+            assert_eq!(metrics(line), expected, "{}", line);
+        }
 
-        format!("{}.{}.{}.{}", bytes[0], bytes[1], bytes[2], bytes[3])
+        // Malformed queue or timer columns
+        assert_eq!(
+            metrics("   6: 0100007F:1F90 00000000:0000 0A 00000080 00:00000000 00000000  1000        0 41816"),
+            None
+        );
+        assert_eq!(
+            metrics("   7: 0100007F:1F90 00000000:0000 0A 0000008G:00000000 00:00000000 00000000  1000        0 41817"),
+            None
+        );
+        assert_eq!(
+            metrics("   8: 0100007F:1F90 00000000:0000 0A 00000080:00000000 0000000000 00000000  1000        0 41818"),
+            None
+        );
     }
 }
@@ -1,8 +1,7 @@
+use std::collections::HashMap;
 use std::fs;
-use std::io::{BufRead, BufReader};
-use std::path::Path;
 
-use super::PortEntry;
+use super::{PortEntry, Project, UserCache};
 
 pub struct ProcessInfo;
 
@@ -14,51 +13,99 @@ impl ProcessInfo {
                 Self::read_process_info(pid, entry);
             }
         }
+
+        Self::resolve_user_names(entry);
     }
 
-    fn find_pid_by_inode(inode: u32) -> Option<u32> {
-        let proc_path = Path::new("/proc");
-
-        for entry in fs::read_dir(proc_path).ok()? {
-            let entry = entry.ok()?;
-            let pid_str = entry.file_name().to_string_lossy().to_string();
-
-            if let Ok(pid) = pid_str.parse::<u32>() {
-                let pid_path = proc_path.join(&pid_str);
-
-                if pid_path.exists() {
-                    let net_path = pid_path.join("net/tcp");
-                    if let Ok(file) = fs::File::open(&net_path) {
-                        let reader = BufReader::new(file);
-
-                        for line in reader.lines().skip(1) {
-                            if let Ok(line) = line {
-                                let fields: Vec<&str> = line.split_whitespace().collect();
-                                if fields.len() < 2 {
-                                    continue;
-                                }
-
-                                let inode_str = fields[9];
-
-                                if let Some(entry_inode) = inode_str.parse::<u32>().ok() {
-                                    if entry_inode == inode {
-                                        return Some(pid);
-                                    }
-                                }
-                            }
-                        }
-                    }
+    // Resolves owners for a whole scan at once: /proc is walked a single time to map
+    // socket inodes to PIDs. `progress` receives (processes done, total) and can
+    // return false to abandon the scan, in which case this returns false.
+    pub fn map_all<F>(entries: &mut [PortEntry], progress: F) -> bool
+    where
+        F: FnMut(usize, usize) -> bool,
+    {
+        let inodes = match Self::socket_inode_map(progress) {
+            Some(inodes) => inodes,
+            None => return false,
+        };
+
+        for entry in entries.iter_mut() {
+            if let Some(inode) = entry.inode {
+                entry.pid = inodes.get(&inode).copied();
+                if let Some(pid) = entry.pid {
+                    Self::read_process_info(pid, entry);
                 }
             }
+
+            Self::resolve_user_names(entry);
         }
 
-        None
+        true
+    }
+
+    fn find_pid_by_inode(inode: u32) -> Option<u32> {
+        Self::list_pids()
+            .into_iter()
+            .find(|pid| Self::socket_inodes(*pid).contains(&inode))
+    }
+
+    fn socket_inode_map<F>(mut progress: F) -> Option<HashMap<u32, u32>>
+    where
+        F: FnMut(usize, usize) -> bool,
+    {
+        let pids = Self::list_pids();
+        let total = pids.len();
+        let mut inodes = HashMap::new();
+
+        for (done, pid) in pids.into_iter().enumerate() {
+            if !progress(done, total) {
+                return None;
+            }
+
+            for inode in Self::socket_inodes(pid) {
+                inodes.entry(inode).or_insert(pid);
+            }
+        }
+
+        progress(total, total);
+        Some(inodes)
+    }
+
+    fn list_pids() -> Vec<u32> {
+        match fs::read_dir("/proc") {
+            Ok(dir) => dir
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    // Reading another user's fd table needs root; those processes are simply skipped
+    fn socket_inodes(pid: u32) -> Vec<u32> {
+        match fs::read_dir(format!("/proc/{}/fd", pid)) {
+            Ok(fds) => fds
+                .filter_map(|fd| fd.ok())
+                .filter_map(|fd| fs::read_link(fd.path()).ok())
+                .filter_map(|target| Self::parse_socket_link(&target.to_string_lossy()))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    // fd symlinks to sockets look like "socket:[12345]"
+    fn parse_socket_link(target: &str) -> Option<u32> {
+        target
+            .strip_prefix("socket:[")?
+            .strip_suffix(']')?
+            .parse::<u32>()
+            .ok()
     }
 
     fn read_process_info(pid: u32, entry: &mut PortEntry) {
         Self::read_cmdline(pid, entry);
         Self::read_status(pid, entry);
-        Self::read_user(pid, entry);
+        entry.project = Project::for_pid(pid);
     }
 
     fn read_cmdline(pid: u32, entry: &mut PortEntry) {
@@ -74,31 +121,42 @@ impl ProcessInfo {
         let status_path = format!("/proc/{}/status", pid);
 
         if let Ok(contents) = fs::read_to_string(&status_path) {
-            for line in contents.lines() {
-                if let Some((key, value)) = line.split_once(':') {
-                    let key = key.trim();
-                    let value = value.trim();
-
-                    if key == "Name" {
-                        entry.process_name = value.to_string();
-                    } else if key == "Uid" {
-                        if let Some(uid_str) = value.split_whitespace().next() {
-                            if let Ok(uid) = uid_str.parse::<u32>() {
-                                entry.user = uid.to_string();
-                            }
-                        }
+            Self::parse_status(&contents, entry);
+        }
+    }
+
+    fn parse_status(contents: &str, entry: &mut PortEntry) {
+        for line in contents.lines() {
+            if let Some((key, value)) = line.split_once(':') {
+                let key = key.trim();
+                let value = value.trim();
+
+                if key == "Name" {
+                    entry.process_name = value.to_string();
+                } else if key == "Uid" {
+                    // Uid:\t<real>\t<effective>\t<saved>\t<filesystem>
+                    let uids: Vec<u32> = value
+                        .split_whitespace()
+                        .filter_map(|uid| uid.parse::<u32>().ok())
+                        .collect();
+
+                    if uids.len() >= 2 {
+                        entry.uid = Some(uids[0]);
+                        entry.euid = Some(uids[1]);
                     }
                 }
             }
         }
     }
 
-    fn read_user(pid: u32, entry: &mut PortEntry) {
-        if entry.user.is_empty() {
-            let uid = users::uid_t::from(pid);
-            if let Some(name) = users::get_user_by_uid(uid) {
-                entry.user = name.name().to_string_lossy().to_string();
-            }
+    fn resolve_user_names(entry: &mut PortEntry) {
+        // The USER column shows the effective user, as `ps` does
+        if let Some(euid) = entry.euid {
+            entry.user = UserCache::name_for_uid(euid);
+        }
+
+        if let Some(uid) = entry.uid {
+            entry.real_user = UserCache::name_for_uid(uid);
         }
     }
 
@@ -115,18 +173,31 @@ impl ProcessInfo {
             name
         }
     }
+}
 
-    fn parse_port(addr_hex: &str) -> Option<u16> {
-        if addr_hex.is_empty() {
-            return None;
-        }
-
-        let parts: Vec<&str> = addr_hex.split(':').collect();
-        if parts.len() < 2 {
-            return None;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_uids_are_real_then_effective() {
+        let mut entry = PortEntry::listener(8080, "");
+        ProcessInfo::parse_status(
+            "Name:\tsudo\nUmask:\t0022\nState:\tS (sleeping)\nUid:\t1000\t0\t0\t0\nGid:\t1000\t1000\t1000\t1000\n",
+            &mut entry,
+        );
+        assert_eq!(entry.process_name, "sudo");
+        assert_eq!((entry.uid, entry.euid), (Some(1000), Some(0)));
+    }
 
-        let port_hex = parts[1];
-        u16::from_str_radix(port_hex.trim_start_matches('0'), 16).ok()
+    #[test]
+    fn incomplete_uid_line_keeps_the_socket_owner() {
+        let mut entry = PortEntry {
+            uid: Some(33),
+            euid: Some(33),
+            ..PortEntry::listener(8080, "")
+        };
+        ProcessInfo::parse_status("Name:\tnginx\nUid:\t1000\n", &mut entry);
+        assert_eq!((entry.uid, entry.euid), (Some(33), Some(33)));
     }
 }
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+// The checkout a process was started from, found from its working directory.
+// The git toplevel wins, so every package of a monorepo belongs to one project;
+// outside git the nearest package.json, Cargo.toml or pyproject.toml is used.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Project {
+    pub name: String,
+    pub root: String,
+}
+
+impl Project {
+    pub fn for_pid(pid: u32) -> Option<Project> {
+        // Reading another user's cwd needs root, like their fd table
+        let cwd = std::fs::read_link(format!("/proc/{}/cwd", pid)).ok()?;
+        Self::for_dir(&cwd)
+    }
+
+    // Cached per directory; dev servers rarely move and manifests rarely get renamed
+    pub fn for_dir(dir: &Path) -> Option<Project> {
+        static CACHE: OnceLock<Mutex<HashMap<PathBuf, Option<Project>>>> = OnceLock::new();
+
+        let cache = CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+        let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+
+        cache
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| Self::detect(dir))
+            .clone()
+    }
+
+    fn detect(dir: &Path) -> Option<Project> {
+        let mut nearest_manifest = None;
+
+        for ancestor in dir.ancestors() {
+            // A file for worktrees and submodules, a directory otherwise
+            if ancestor.join(".git").exists() {
+                let name = Self::manifest_name(ancestor).or_else(|| Self::dir_name(ancestor))?;
+                return Some(Project {
+                    name,
+                    root: ancestor.to_string_lossy().to_string(),
+                });
+            }
+
+            if nearest_manifest.is_none() {
+                nearest_manifest = Self::manifest_name(ancestor).map(|name| Project {
+                    name,
+                    root: ancestor.to_string_lossy().to_string(),
+                });
+            }
+        }
+
+        nearest_manifest
+    }
+
+    fn manifest_name(dir: &Path) -> Option<String> {
+        Self::package_json_name(dir)
+            .or_else(|| Self::toml_name(&dir.join("Cargo.toml"), &[&["package", "name"]]))
+            .or_else(|| {
+                Self::toml_name(
+                    &dir.join("pyproject.toml"),
+                    &[&["project", "name"], &["tool", "poetry", "name"]],
+                )
+            })
+            .filter(|name| !name.is_empty())
+    }
+
+    fn package_json_name(dir: &Path) -> Option<String> {
+        let contents = std::fs::read_to_string(dir.join("package.json")).ok()?;
+        let manifest: serde_json::Value = serde_json::from_str(&contents).ok()?;
+        manifest.get("name")?.as_str().map(str::to_string)
+    }
+
+    // The first of `keys` (each a path of nested tables) present in the file
+    fn toml_name(path: &Path, keys: &[&[&str]]) -> Option<String> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        let manifest: toml::Table = toml::from_str(&contents).ok()?;
+
+        keys.iter().find_map(|key| {
+            let (last, tables) = key.split_last()?;
+            let mut table = &manifest;
+            for name in tables {
+                table = table.get(*name)?.as_table()?;
+            }
+            table.get(*last)?.as_str().map(str::to_string)
+        })
+    }
+
+    fn dir_name(dir: &Path) -> Option<String> {
+        dir.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An empty directory of its own for each test
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "portkill-project-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(path: &Path, contents: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    fn project(name: &str, root: &Path) -> Option<Project> {
+        Some(Project {
+            name: name.to_string(),
+            root: root.to_string_lossy().to_string(),
+        })
+    }
+
+    #[test]
+    fn git_toplevel_wins_over_nested_manifests() {
+        let root = scratch("monorepo");
+        std::fs::create_dir(root.join(".git")).unwrap();
+        write(&root.join("package.json"), r#"{ "name": "shop" }"#);
+        write(
+            &root.join("packages/web/package.json"),
+            r#"{ "name": "web" }"#,
+        );
+        let src = root.join("packages/web/src");
+        std::fs::create_dir_all(&src).unwrap();
+
+        assert_eq!(Project::detect(&src), project("shop", &root));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn git_toplevel_without_a_manifest_is_named_after_its_directory() {
+        let root = scratch("worktree");
+        // Worktrees and submodules have a .git file
+        write(&root.join(".git"), "gitdir: /src/main/.git/worktrees/x\n");
+        write(&root.join("api/Cargo.toml"), "[package]\nname = \"api\"\n");
+
+        let name = root.file_name().unwrap().to_string_lossy();
+        assert_eq!(Project::detect(&root.join("api")), project(&name, &root));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn nearest_manifest_outside_git() {
+        let root = scratch("plain");
+        write(&root.join("Cargo.toml"), "[package]\nname = \"outer\"\n");
+        write(
+            &root.join("tools/pyproject.toml"),
+            "[tool.poetry]\nname = \"tooling\"\n",
+        );
+        write(
+            &root.join("scripts/pyproject.toml"),
+            "[project]\nname = \"scripts\"\n",
+        );
+        // Unreadable package.json falls through to the next manifest
+        write(&root.join("web/package.json"), "{ \"name\": ");
+        std::fs::create_dir_all(root.join("tools/bin")).unwrap();
+
+        assert_eq!(
+            Project::detect(&root.join("tools/bin")),
+            project("tooling", &root.join("tools"))
+        );
+        assert_eq!(
+            Project::detect(&root.join("scripts")),
+            project("scripts", &root.join("scripts"))
+        );
+        assert_eq!(Project::detect(&root.join("web")), project("outer", &root));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::os::fd::AsRawFd;
+
+use nix::sys::socket::{
+    recv, send, socket, AddressFamily, MsgFlags, SockFlag, SockProtocol, SockType,
+};
+
+// Listen backlogs over NETLINK_SOCK_DIAG, the interface `ss` uses. For
+// listeners /proc/net/tcp only has the accept queue length; the backlog
+// limit passed to listen() is reported here as idiag_wqueue.
+pub struct SockDiag;
+
+// The accept queue of one listening socket
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AcceptQueue {
+    pub queued: u32,
+    pub limit: u32,
+}
+
+const SOCK_DIAG_BY_FAMILY: u16 = 20;
+const NLM_F_REQUEST: u16 = 0x1;
+const NLM_F_DUMP: u16 = 0x300;
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+const NLMSG_HEADER_LEN: usize = 16;
+const IPPROTO_TCP: u8 = 6;
+const TCP_LISTEN: u32 = 10;
+
+// inet_diag_msg: family, state, timer, retrans, a 48-byte socket id, then
+// expires, rqueue, wqueue, uid and inode as native-endian u32
+const DIAG_MSG_LEN: usize = 72;
+const RQUEUE_OFFSET: usize = 56;
+const WQUEUE_OFFSET: usize = 60;
+const INODE_OFFSET: usize = 68;
+
+impl SockDiag {
+    // Accept queues of every TCP and TCP6 listener, by socket inode
+    pub fn accept_queues() -> Result<HashMap<u32, AcceptQueue>, String> {
+        let fd = socket(
+            AddressFamily::Netlink,
+            SockType::Datagram,
+            SockFlag::SOCK_CLOEXEC,
+            SockProtocol::NetlinkSockDiag,
+        )
+        .map_err(|e| format!("sock_diag socket: {}", e))?;
+
+        let mut queues = HashMap::new();
+        for family in [
+            libc_family(AddressFamily::Inet),
+            libc_family(AddressFamily::Inet6),
+        ] {
+            send(fd.as_raw_fd(), &Self::request(family), MsgFlags::empty())
+                .map_err(|e| format!("sock_diag request: {}", e))?;
+            Self::read_dump(fd.as_raw_fd(), &mut queues)?;
+        }
+
+        Ok(queues)
+    }
+
+    // nlmsghdr followed by inet_diag_req_v2 asking for LISTEN sockets only
+    fn request(family: u8) -> Vec<u8> {
+        let len = NLMSG_HEADER_LEN + 56;
+        let mut request = Vec::with_capacity(len);
+        request.extend_from_slice(&(len as u32).to_ne_bytes());
+        request.extend_from_slice(&SOCK_DIAG_BY_FAMILY.to_ne_bytes());
+        request.extend_from_slice(&(NLM_F_REQUEST | NLM_F_DUMP).to_ne_bytes());
+        request.extend_from_slice(&0u32.to_ne_bytes()); // seq
+        request.extend_from_slice(&0u32.to_ne_bytes()); // pid, the kernel's
+        request.extend_from_slice(&[family, IPPROTO_TCP, 0, 0]);
+        request.extend_from_slice(&(1u32 << TCP_LISTEN).to_ne_bytes());
+        request.resize(len, 0); // any socket id
+        request
+    }
+
+    fn read_dump(fd: i32, queues: &mut HashMap<u32, AcceptQueue>) -> Result<(), String> {
+        let mut buffer = vec![0u8; 32 * 1024];
+
+        loop {
+            let read = recv(fd, &mut buffer, MsgFlags::empty())
+                .map_err(|e| format!("sock_diag reply: {}", e))?;
+            let mut messages = &buffer[..read];
+
+            while messages.len() >= NLMSG_HEADER_LEN {
+                let len = u32_at(messages, 0) as usize;
+                let kind = u16::from_ne_bytes([messages[4], messages[5]]);
+                if len < NLMSG_HEADER_LEN || len > messages.len() {
+                    return Err("sock_diag reply is truncated".to_string());
+                }
+
+                match kind {
+                    NLMSG_DONE => return Ok(()),
+                    NLMSG_ERROR => {
+                        if len < NLMSG_HEADER_LEN + 4 {
+                            return Err("sock_diag error reply is truncated".to_string());
+                        }
+                        let errno = i32::from_ne_bytes(
+                            messages[NLMSG_HEADER_LEN..NLMSG_HEADER_LEN + 4]
+                                .try_into()
+                                .unwrap_or_default(),
+                        );
+                        return Err(format!(
+                            "sock_diag: {}",
+                            std::io::Error::from_raw_os_error(-errno)
+                        ));
+                    }
+                    _ => {
+                        let payload = &messages[NLMSG_HEADER_LEN..len];
+                        if payload.len() >= DIAG_MSG_LEN {
+                            queues.insert(
+                                u32_at(payload, INODE_OFFSET),
+                                AcceptQueue {
+                                    queued: u32_at(payload, RQUEUE_OFFSET),
+                                    limit: u32_at(payload, WQUEUE_OFFSET),
+                                },
+                            );
+                        }
+                    }
+                }
+
+                // Messages are padded to 4 bytes
+                let next = (len + 3) & !3;
+                messages = &messages[next.min(messages.len())..];
+            }
+        }
+    }
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap_or_default())
+}
+
+fn libc_family(family: AddressFamily) -> u8 {
+    family as i32 as u8
+}
@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+pub struct UserCache;
+
+impl UserCache {
+    pub fn name_for_uid(uid: u32) -> String {
+        static CACHE: OnceLock<Mutex<HashMap<u32, String>>> = OnceLock::new();
+
+        let cache = CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+        let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+
+        cache
+            .entry(uid)
+            .or_insert_with(|| Self::lookup(uid))
+            .clone()
+    }
+
+    fn lookup(uid: u32) -> String {
+        // Fall back to the numeric UID for accounts missing from the passwd database
+        // (e.g. container users or deleted accounts)
+        match users::get_user_by_uid(uid) {
+            Some(user) => user.name().to_string_lossy().to_string(),
+            None => uid.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_known_users() {
+        assert_eq!(UserCache::name_for_uid(0), "root");
+    }
+
+    #[test]
+    fn unknown_uid_falls_back_to_the_number() {
+        assert_eq!(UserCache::name_for_uid(3_999_999_999), "3999999999");
+    }
+}
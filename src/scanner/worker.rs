@@ -0,0 +1,244 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use super::{PortEntry, Scanner};
+use crate::firewall::Firewall;
+use crate::rpc::Client;
+
+pub enum ScanEvent {
+    Progress { done: usize, total: usize },
+    Finished(Result<Vec<PortEntry>, String>),
+    Cancelled,
+}
+
+// Runs scans on a background thread so callers (the GUI) never block on /proc.
+// At most one scan is in flight; requests made meanwhile are coalesced into a
+// single follow-up scan. A running daemon's latest scan is used instead when
+// there is one.
+pub struct ScanWorker {
+    requests: Sender<()>,
+    events: Receiver<ScanEvent>,
+    cancel: Arc<AtomicBool>,
+    in_flight: bool,
+    pending: bool,
+}
+
+impl ScanWorker {
+    pub fn spawn() -> Self {
+        let mut daemon: Option<Client> = None;
+        Self::with_scan(move |progress| {
+            if daemon.is_none() {
+                daemon = Client::connect();
+            }
+            let entries = match &mut daemon {
+                Some(client) => match client.list() {
+                    Ok(entries) => Some(entries),
+                    Err(e) => {
+                        log::info!("Scanning locally: {}", e);
+                        daemon = None;
+                        None
+                    }
+                },
+                None => None,
+            };
+            let entries = match entries {
+                Some(entries) => Some(entries),
+                None => Scanner::scan_all_with_progress(progress).map_err(|e| e.to_string())?,
+            };
+
+            // The daemon doesn't read the firewall, so annotate either way
+            Ok(entries.map(|mut entries| {
+                Firewall::current().annotate(&mut entries);
+                entries
+            }))
+        })
+    }
+
+    // Runs `scan` for each request. It reports progress through the callback
+    // it is given, which returns false once the scan is cancelled; `scan`
+    // should then give up and return Ok(None).
+    fn with_scan<S>(mut scan: S) -> Self
+    where
+        S: FnMut(&mut dyn FnMut(usize, usize) -> bool) -> Result<Option<Vec<PortEntry>>, String>
+            + Send
+            + 'static,
+    {
+        let (request_tx, request_rx) = mpsc::channel::<()>();
+        let (event_tx, event_rx) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let worker_cancel = Arc::clone(&cancel);
+
+        thread::Builder::new()
+            .name("portkill-scan".to_string())
+            .spawn(move || {
+                while request_rx.recv().is_ok() {
+                    let result = scan(&mut |done, total| {
+                        let _ = event_tx.send(ScanEvent::Progress { done, total });
+                        !worker_cancel.load(Ordering::SeqCst)
+                    });
+
+                    let event = match result {
+                        Ok(Some(entries)) => ScanEvent::Finished(Ok(entries)),
+                        Ok(None) => ScanEvent::Cancelled,
+                        Err(e) => ScanEvent::Finished(Err(e)),
+                    };
+
+                    if event_tx.send(event).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("failed to spawn scan thread");
+
+        Self {
+            requests: request_tx,
+            events: event_rx,
+            cancel,
+            in_flight: false,
+            pending: false,
+        }
+    }
+
+    pub fn request(&mut self) {
+        if self.in_flight {
+            self.pending = true;
+        } else {
+            self.start();
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        self.pending = false;
+        if self.in_flight {
+            self.cancel.store(true, Ordering::SeqCst);
+        }
+    }
+
+    pub fn is_scanning(&self) -> bool {
+        self.in_flight
+    }
+
+    // Drains events produced since the last call; starts a coalesced follow-up
+    // scan once the current one has finished
+    pub fn poll(&mut self) -> Vec<ScanEvent> {
+        let events: Vec<ScanEvent> = self.events.try_iter().collect();
+
+        if events
+            .iter()
+            .any(|event| matches!(event, ScanEvent::Finished(_) | ScanEvent::Cancelled))
+        {
+            self.in_flight = false;
+        }
+
+        if !self.in_flight && self.pending {
+            self.pending = false;
+            self.start();
+        }
+
+        events
+    }
+
+    fn start(&mut self) {
+        // Only reset between scans, so a cancel can never be lost to the worker
+        self.cancel.store(false, Ordering::SeqCst);
+        self.in_flight = self.requests.send(()).is_ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::{Duration, Instant};
+
+    // Polls until a scan finishes or is cancelled; returns the events seen
+    fn wait(worker: &mut ScanWorker) -> Vec<ScanEvent> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut events = Vec::new();
+        loop {
+            let batch = worker.poll();
+            let ended = batch
+                .iter()
+                .any(|event| matches!(event, ScanEvent::Finished(_) | ScanEvent::Cancelled));
+            events.extend(batch);
+            if ended {
+                return events;
+            }
+            assert!(Instant::now() < deadline, "scan never ended");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn requests_during_a_scan_are_coalesced() {
+        let scans = Arc::new(AtomicUsize::new(0));
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let counter = Arc::clone(&scans);
+        let mut worker = ScanWorker::with_scan(move |_| {
+            release_rx.recv().unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(Some(vec![PortEntry::listener(8080, "node")]))
+        });
+
+        worker.request();
+        worker.request();
+        worker.request();
+        assert!(worker.is_scanning());
+
+        release_tx.send(()).unwrap();
+        release_tx.send(()).unwrap();
+        let events = wait(&mut worker);
+        assert!(matches!(
+            events.last(),
+            Some(ScanEvent::Finished(Ok(entries))) if entries.len() == 1
+        ));
+        // The two requests made meanwhile became a single follow-up scan
+        assert!(worker.is_scanning());
+        wait(&mut worker);
+        assert!(!worker.is_scanning());
+        assert_eq!(scans.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn cancel_stops_the_scan_in_flight() {
+        let (started_tx, started_rx) = mpsc::channel();
+        let mut calls = 0;
+        let mut worker = ScanWorker::with_scan(move |progress| {
+            calls += 1;
+            if calls > 1 {
+                assert!(progress(0, 1), "a cancel carried over to the next scan");
+                return Ok(Some(Vec::new()));
+            }
+
+            started_tx.send(()).unwrap();
+            let mut done = 0;
+            while progress(done, usize::MAX) {
+                done += 1;
+                thread::sleep(Duration::from_millis(1));
+            }
+            Ok(None)
+        });
+
+        worker.request();
+        worker.request();
+        started_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        worker.cancel();
+
+        let events = wait(&mut worker);
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, ScanEvent::Progress { .. })));
+        assert!(matches!(events.last(), Some(ScanEvent::Cancelled)));
+        // The follow-up requested meanwhile is dropped as well
+        assert!(!worker.is_scanning());
+
+        worker.request();
+        let events = wait(&mut worker);
+        assert!(matches!(
+            events.last(),
+            Some(ScanEvent::Finished(Ok(entries))) if entries.is_empty()
+        ));
+    }
+}
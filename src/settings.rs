@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+use portkill::config::Config;
+
+use crate::SortColumn;
+
+// GUI state kept across launches in eframe's storage (the window size is
+// saved by eframe itself). Missing fields fall back to their defaults so
+// older saved settings keep loading.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub filter_text: String,
+    pub state_filter: StateFilter,
+    pub auto_refresh: bool,
+    pub refresh_interval_secs: u64,
+    pub group_by: GroupBy,
+    pub sort_column: SortColumn,
+    pub sort_ascending: bool,
+    pub presets: Vec<FilterPreset>,
+    pub show_stats: bool,
+    pub resolve_names: bool,
+    pub metric_columns: Vec<SortColumn>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateFilter {
+    Listen,
+    Established,
+    All,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    None,
+    User,
+    Project,
+    // Listening sockets with the connections they accepted
+    Listener,
+}
+
+// A named filter expression shown as a chip in the filter bar
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FilterPreset {
+    pub name: String,
+    pub query: String,
+}
+
+impl Settings {
+    pub const STORAGE_KEY: &'static str = "portkill_settings";
+    pub const MIN_REFRESH_INTERVAL_SECS: u64 = 1;
+    pub const MAX_REFRESH_INTERVAL_SECS: u64 = 3600;
+
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        let mut settings: Self = storage
+            .and_then(|storage| eframe::get_value(storage, Self::STORAGE_KEY))
+            .unwrap_or_default();
+
+        settings.refresh_interval_secs = settings.refresh_interval_secs.clamp(
+            Self::MIN_REFRESH_INTERVAL_SECS,
+            Self::MAX_REFRESH_INTERVAL_SECS,
+        );
+        settings
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, Self::STORAGE_KEY, self);
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            filter_text: Config::global().default_filter.clone().unwrap_or_default(),
+            state_filter: StateFilter::Listen,
+            auto_refresh: false,
+            refresh_interval_secs: 5,
+            group_by: GroupBy::None,
+            sort_column: SortColumn::Port,
+            sort_ascending: true,
+            presets: Vec::new(),
+            show_stats: false,
+            resolve_names: false,
+            metric_columns: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::ports::{ephemeral_port_range, PortRange};
+use crate::scanner::{Backlog, PortEntry, SocketState};
+
+// Linux default when /proc/sys/net/ipv4/ip_local_port_range can't be read
+const DEFAULT_EPHEMERAL_RANGE: PortRange = PortRange {
+    start: 32768,
+    end: 60999,
+};
+
+// Aggregate view of a scan: sockets per state, remote endpoint and process,
+// and how much of the ephemeral port range outgoing connections use up.
+#[derive(Clone, Debug, Serialize)]
+pub struct SocketStats {
+    pub total: usize,
+    // Each list is sorted by count, highest first
+    pub by_state: Vec<Count>,
+    pub by_remote: Vec<Count>,
+    pub by_process: Vec<Count>,
+    pub ephemeral: EphemeralUsage,
+    // Listeners with connections waiting to be accepted, fullest first
+    pub backlogs: Vec<ListenerBacklog>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Count {
+    pub name: String,
+    pub count: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct EphemeralUsage {
+    pub range: String,
+    pub capacity: usize,
+    // Distinct local ports in the range held by non-listening sockets
+    pub ports_in_use: usize,
+    pub time_wait: usize,
+    // connect() only needs a local port not already used towards the same
+    // remote endpoint, so the busiest one is what runs out under load tests
+    pub busiest_remote: Option<Count>,
+    // Share of the range used towards the busiest remote endpoint
+    pub percent: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ListenerBacklog {
+    pub port: u16,
+    pub local_addr: String,
+    pub process: String,
+    pub backlog: Backlog,
+}
+
+impl ListenerBacklog {
+    pub fn warning(&self) -> String {
+        let backlog = &self.backlog;
+        let consequence = if backlog.is_full() {
+            "new connections are dropped"
+        } else {
+            "the application isn't accepting fast enough"
+        };
+        format!(
+            "Accept queue of {} on {} is {:.0}% full ({} of {}); {}",
+            self.process,
+            self.local_addr,
+            backlog.percent,
+            backlog.queued,
+            backlog.limit,
+            consequence
+        )
+    }
+}
+
+impl SocketStats {
+    pub fn collect(entries: &[PortEntry]) -> Self {
+        let range = ephemeral_port_range().unwrap_or(DEFAULT_EPHEMERAL_RANGE);
+        Self::collect_with_range(entries, range)
+    }
+
+    pub fn collect_with_range(entries: &[PortEntry], range: PortRange) -> Self {
+        let mut by_state = HashMap::new();
+        let mut by_remote = HashMap::new();
+        let mut by_process = HashMap::new();
+        let mut ports_in_use = HashSet::new();
+        let mut per_remote: HashMap<&str, usize> = HashMap::new();
+        let mut time_wait = 0;
+        let mut backlogs = Vec::new();
+
+        for entry in entries {
+            *by_state.entry(entry.state.to_string()).or_insert(0) += 1;
+
+            let process = match entry.pid {
+                Some(pid) => format!("{} ({})", entry.process_name, pid),
+                // TIME_WAIT sockets belong to no process anymore
+                None => "-".to_string(),
+            };
+            *by_process.entry(process.clone()).or_insert(0) += 1;
+
+            if entry.state == SocketState::Listen {
+                if let Some(backlog) = entry.backlog().filter(|backlog| backlog.queued > 0) {
+                    backlogs.push(ListenerBacklog {
+                        port: entry.port,
+                        local_addr: entry.local_addr.clone(),
+                        process,
+                        backlog,
+                    });
+                }
+                continue;
+            }
+            *by_remote.entry(entry.remote_addr.clone()).or_insert(0) += 1;
+
+            if range.contains(entry.port) {
+                ports_in_use.insert(entry.port);
+                *per_remote.entry(entry.remote_addr.as_str()).or_insert(0) += 1;
+                if entry.state == SocketState::TimeWait {
+                    time_wait += 1;
+                }
+            }
+        }
+
+        let capacity = (range.end - range.start) as usize + 1;
+        let busiest_remote = per_remote
+            .into_iter()
+            .max_by(|(a_name, a), (b_name, b)| a.cmp(b).then_with(|| b_name.cmp(a_name)))
+            .map(|(name, count)| Count {
+                name: name.to_string(),
+                count,
+            });
+        let busiest = busiest_remote.as_ref().map_or(0, |remote| remote.count);
+        backlogs.sort_by(|a, b| {
+            b.backlog
+                .percent
+                .total_cmp(&a.backlog.percent)
+                .then_with(|| a.port.cmp(&b.port))
+        });
+
+        Self {
+            total: entries.len(),
+            by_state: Self::sorted(by_state),
+            by_remote: Self::sorted(by_remote),
+            by_process: Self::sorted(by_process),
+            ephemeral: EphemeralUsage {
+                range: range.to_string(),
+                capacity,
+                ports_in_use: ports_in_use.len(),
+                time_wait,
+                busiest_remote,
+                percent: busiest as f64 * 100.0 / capacity as f64,
+            },
+            backlogs,
+        }
+    }
+
+    // A message once ephemeral port usage reaches `threshold_percent`
+    pub fn warning(&self, threshold_percent: f64) -> Option<String> {
+        let ephemeral = &self.ephemeral;
+        if ephemeral.percent < threshold_percent {
+            return None;
+        }
+
+        let remote = ephemeral.busiest_remote.as_ref()?;
+
+        Some(format!(
+            "Ephemeral ports {:.0}% used towards {} ({} of {} in {}, {} in TIME_WAIT)",
+            ephemeral.percent,
+            remote.name,
+            remote.count,
+            ephemeral.capacity,
+            ephemeral.range,
+            ephemeral.time_wait
+        ))
+    }
+
+    // One message per listener whose accept queue reaches `threshold_percent`
+    pub fn backlog_warnings(&self, threshold_percent: f64) -> Vec<String> {
+        self.backlogs
+            .iter()
+            .filter(|listener| listener.backlog.percent >= threshold_percent)
+            .map(ListenerBacklog::warning)
+            .collect()
+    }
+
+    fn sorted(counts: HashMap<String, usize>) -> Vec<Count> {
+        let mut counts: Vec<Count> = counts
+            .into_iter()
+            .map(|(name, count)| Count { name, count })
+            .collect();
+        counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::SocketMetrics;
+
+    const RANGE: PortRange = PortRange {
+        start: 40000,
+        end: 40099,
+    };
+
+    fn connection(local_port: u16, remote: &str, state: SocketState) -> PortEntry {
+        PortEntry {
+            state,
+            local_addr: format!("127.0.0.1:{}", local_port),
+            remote_addr: remote.to_string(),
+            pid: Some(200),
+            ..PortEntry::listener(local_port, "curl")
+        }
+    }
+
+    fn listener_with_queue(port: u16, queued: u32, limit: u32) -> PortEntry {
+        PortEntry {
+            metrics: Some(SocketMetrics {
+                rx_queue: queued,
+                tx_queue: limit,
+                ..SocketMetrics::default()
+            }),
+            pid: Some(100),
+            ..PortEntry::listener(port, "node")
+        }
+    }
+
+    fn count(name: &str, count: usize) -> Count {
+        Count {
+            name: name.to_string(),
+            count,
+        }
+    }
+
+    #[test]
+    fn counts_by_state_remote_and_process() {
+        let entries = vec![
+            PortEntry {
+                pid: Some(100),
+                ..PortEntry::listener(3000, "node")
+            },
+            connection(40000, "10.0.0.1:5432", SocketState::Established),
+            connection(40001, "10.0.0.1:5432", SocketState::Established),
+            PortEntry {
+                pid: None,
+                ..connection(40002, "10.0.0.2:443", SocketState::TimeWait)
+            },
+        ];
+
+        let stats = SocketStats::collect_with_range(&entries, RANGE);
+
+        assert_eq!(stats.total, 4);
+        assert_eq!(
+            stats.by_state,
+            vec![
+                count("ESTABLISHED", 2),
+                count("LISTEN", 1),
+                count("TIME_WAIT", 1)
+            ]
+        );
+        // Listeners have no remote end
+        assert_eq!(
+            stats.by_remote,
+            vec![count("10.0.0.1:5432", 2), count("10.0.0.2:443", 1)]
+        );
+        assert_eq!(
+            stats.by_process,
+            vec![
+                count("curl (200)", 2),
+                count("-", 1),
+                count("node (100)", 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn ephemeral_usage_is_measured_against_the_busiest_remote() {
+        let mut entries: Vec<PortEntry> = (40000..40030)
+            .map(|port| connection(port, "10.0.0.1:5432", SocketState::Established))
+            .collect();
+        // The same local ports towards another endpoint don't add to the busiest
+        entries.extend(
+            (40000..40010).map(|port| connection(port, "10.0.0.2:443", SocketState::TimeWait)),
+        );
+        // Outside the range
+        entries.push(connection(8080, "10.0.0.1:5432", SocketState::Established));
+
+        let stats = SocketStats::collect_with_range(&entries, RANGE);
+        let ephemeral = &stats.ephemeral;
+
+        assert_eq!(ephemeral.range, "40000-40099");
+        assert_eq!(ephemeral.capacity, 100);
+        assert_eq!(ephemeral.ports_in_use, 30);
+        assert_eq!(ephemeral.time_wait, 10);
+        assert_eq!(ephemeral.busiest_remote, Some(count("10.0.0.1:5432", 30)));
+        assert_eq!(ephemeral.percent, 30.0);
+
+        assert_eq!(stats.warning(50.0), None);
+        assert_eq!(
+            stats.warning(30.0).as_deref(),
+            Some("Ephemeral ports 30% used towards 10.0.0.1:5432 (30 of 100 in 40000-40099, 10 in TIME_WAIT)")
+        );
+    }
+
+    #[test]
+    fn no_ephemeral_warning_without_connections() {
+        let entries = vec![PortEntry::listener(3000, "node")];
+
+        let stats = SocketStats::collect_with_range(&entries, RANGE);
+
+        assert_eq!(stats.ephemeral.busiest_remote, None);
+        assert_eq!(stats.warning(0.0), None);
+    }
+
+    #[test]
+    fn backlog_warnings_at_the_threshold() {
+        let entries = vec![
+            listener_with_queue(3000, 0, 128),
+            listener_with_queue(4000, 64, 128),
+            listener_with_queue(5000, 129, 128),
+            listener_with_queue(6000, 100, 128),
+        ];
+
+        let stats = SocketStats::collect_with_range(&entries, RANGE);
+
+        // Empty queues aren't listed; the rest come fullest first
+        let ports: Vec<u16> = stats
+            .backlogs
+            .iter()
+            .map(|listener| listener.port)
+            .collect();
+        assert_eq!(ports, vec![5000, 6000, 4000]);
+
+        assert_eq!(
+            stats.backlog_warnings(75.0),
+            vec![
+                "Accept queue of node (100) on 0.0.0.0:5000 is 100% full (129 of 128); new connections are dropped",
+                "Accept queue of node (100) on 0.0.0.0:6000 is 78% full (100 of 128); the application isn't accepting fast enough",
+            ]
+        );
+        // The threshold itself counts
+        assert_eq!(stats.backlog_warnings(50.0).len(), 3);
+    }
+}
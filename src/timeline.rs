@@ -0,0 +1,345 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use eframe::egui;
+
+use portkill::config::Config;
+use portkill::history::{History, Interval};
+use portkill::ports::PortRange;
+
+// Window with a bar per listening period, one row per port and address,
+// read from the history file
+pub struct Timeline {
+    pub open: bool,
+    port_text: String,
+    range: TimeRange,
+    history: Option<Result<History, String>>,
+    loaded: Option<Instant>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TimeRange {
+    Hour,
+    SixHours,
+    Day,
+    Week,
+}
+
+impl TimeRange {
+    const ALL: [TimeRange; 4] = [
+        TimeRange::Hour,
+        TimeRange::SixHours,
+        TimeRange::Day,
+        TimeRange::Week,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            TimeRange::Hour => "Last hour",
+            TimeRange::SixHours => "Last 6 hours",
+            TimeRange::Day => "Last 24 hours",
+            TimeRange::Week => "Last 7 days",
+        }
+    }
+
+    fn duration(self) -> chrono::Duration {
+        match self {
+            TimeRange::Hour => chrono::Duration::hours(1),
+            TimeRange::SixHours => chrono::Duration::hours(6),
+            TimeRange::Day => chrono::Duration::days(1),
+            TimeRange::Week => chrono::Duration::days(7),
+        }
+    }
+}
+
+// The file only grows by a few lines per scan; no need to reread it every frame
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+const ROW_HEIGHT: f32 = 22.0;
+const LABEL_WIDTH: f32 = 190.0;
+const MAX_EVENTS: usize = 200;
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            port_text: String::new(),
+            range: TimeRange::Day,
+            history: None,
+            loaded: None,
+        }
+    }
+
+    fn reload(&mut self) {
+        self.history = Some(match History::path() {
+            Some(path) => History::load(&path),
+            None => Err("cannot locate the history file; set [history] path".to_string()),
+        });
+        self.loaded = Some(Instant::now());
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        if !self.open {
+            return;
+        }
+        if self
+            .loaded
+            .is_none_or(|loaded| loaded.elapsed() >= RELOAD_INTERVAL)
+        {
+            self.reload();
+        }
+
+        let mut open = self.open;
+        egui::Window::new("History")
+            .open(&mut open)
+            .resizable(true)
+            .default_size([760.0, 480.0])
+            .show(ctx, |ui| self.contents(ui));
+        self.open = open;
+
+        // Keep the "now" end of the chart moving
+        ctx.request_repaint_after(RELOAD_INTERVAL);
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui) {
+        let muted = egui::Color32::from_rgb(148, 163, 184);
+
+        ui.horizontal(|ui| {
+            ui.label("Port:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.port_text)
+                    .hint_text("all, 8080 or 8000-8099")
+                    .desired_width(130.0),
+            );
+            egui::ComboBox::from_id_salt("history_range")
+                .selected_text(self.range.label())
+                .show_ui(ui, |ui| {
+                    for range in TimeRange::ALL {
+                        ui.selectable_value(&mut self.range, range, range.label());
+                    }
+                });
+            if ui.button("Reload").clicked() {
+                self.reload();
+            }
+        });
+
+        let ports = match self.port_text.trim() {
+            "" => None,
+            text => match PortRange::parse(text) {
+                Ok(range) => Some(range),
+                Err(e) => {
+                    ui.label(egui::RichText::new(e).color(egui::Color32::from_rgb(248, 113, 113)));
+                    return;
+                }
+            },
+        };
+
+        let history = match &self.history {
+            Some(Ok(history)) => history,
+            Some(Err(e)) => {
+                ui.label(egui::RichText::new(e).color(egui::Color32::from_rgb(248, 113, 113)));
+                return;
+            }
+            None => return,
+        };
+        if history.records.is_empty() {
+            let hint = if Config::global().history_record {
+                "Nothing recorded yet; listeners are recorded after each scan."
+            } else {
+                "Nothing recorded. Set [history] record = true in the config, \
+                 or run `portkill history record`."
+            };
+            ui.add_space(8.0);
+            ui.label(egui::RichText::new(hint).color(muted));
+            return;
+        }
+
+        let now = Local::now();
+        let since = now - self.range.duration();
+        let matches =
+            |interval: &Interval| ports.is_none_or(|range| range.contains(interval.entry.port));
+
+        // One row per port and address, in port order
+        let mut rows: BTreeMap<(u16, String), Vec<Interval>> = BTreeMap::new();
+        for interval in history.intervals() {
+            if interval.overlaps(since, now) && matches(&interval) {
+                rows.entry((interval.entry.port, interval.entry.local_addr.clone()))
+                    .or_default()
+                    .push(interval);
+            }
+        }
+
+        ui.add_space(8.0);
+        if rows.is_empty() {
+            ui.label(egui::RichText::new("Nothing listened in this range").color(muted));
+        } else {
+            egui::ScrollArea::vertical()
+                .id_salt("history_rows")
+                .max_height(ui.available_height() * 0.6)
+                .show(ui, |ui| {
+                    for ((port, address), intervals) in &rows {
+                        Self::row(ui, *port, address, intervals, since, now);
+                    }
+                });
+            Self::axis(ui, since, now);
+        }
+
+        ui.add_space(8.0);
+        ui.separator();
+        ui.label(egui::RichText::new("Events").strong());
+        egui::ScrollArea::vertical()
+            .id_salt("history_events")
+            .show(ui, |ui| {
+                let events: Vec<_> = history
+                    .events_between(since, now)
+                    .filter(|record| ports.is_none_or(|range| range.contains(record.entry.port)))
+                    .collect();
+                for record in events.iter().rev().take(MAX_EVENTS) {
+                    let owner = match record.entry.pid {
+                        Some(pid) => format!(
+                            "{} (PID {}, {})",
+                            record.entry.process_name, pid, record.entry.user
+                        ),
+                        None => "-".to_string(),
+                    };
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "{}  {:<6} {:<22} {}",
+                            record.time.format("%m-%d %H:%M:%S"),
+                            record.event.to_string().to_uppercase(),
+                            record.entry.local_addr,
+                            owner
+                        ))
+                        .monospace()
+                        .size(12.0),
+                    );
+                }
+            });
+    }
+
+    fn row(
+        ui: &mut egui::Ui,
+        port: u16,
+        address: &str,
+        intervals: &[Interval],
+        since: DateTime<Local>,
+        now: DateTime<Local>,
+    ) {
+        let (rect, response) = ui.allocate_exact_size(
+            egui::vec2(ui.available_width(), ROW_HEIGHT),
+            egui::Sense::hover(),
+        );
+        let painter = ui.painter_at(rect);
+        painter.text(
+            rect.left_center() + egui::vec2(4.0, 0.0),
+            egui::Align2::LEFT_CENTER,
+            address,
+            egui::FontId::monospace(12.0),
+            egui::Color32::from_rgb(203, 213, 225),
+        );
+
+        let chart = egui::Rect::from_min_max(
+            egui::pos2(rect.left() + LABEL_WIDTH, rect.top() + 3.0),
+            egui::pos2(rect.right(), rect.bottom() - 3.0),
+        );
+        let x = |time: DateTime<Local>| Self::x_for(chart, time, since, now);
+
+        for interval in intervals {
+            let bar = egui::Rect::from_min_max(
+                egui::pos2(x(interval.start.max(since)), chart.top()),
+                egui::pos2(
+                    x(interval.end.unwrap_or(now).min(now)).max(x(interval.start.max(since)) + 2.0),
+                    chart.bottom(),
+                ),
+            );
+            painter.rect_filled(bar, 3.0, Self::color_for(&interval.entry.process_name));
+            if bar.width() > 60.0 {
+                painter.text(
+                    bar.left_center() + egui::vec2(4.0, 0.0),
+                    egui::Align2::LEFT_CENTER,
+                    &interval.entry.process_name,
+                    egui::FontId::proportional(11.0),
+                    egui::Color32::WHITE,
+                );
+            }
+
+            if response.hover_pos().is_some_and(|pos| bar.contains(pos)) {
+                let end = match interval.end {
+                    Some(end) => end.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    None => "now".to_string(),
+                };
+                let owner = match interval.entry.pid {
+                    Some(pid) => format!(
+                        "{} (PID {}, {})",
+                        interval.entry.process_name, pid, interval.entry.user
+                    ),
+                    None => "unknown process".to_string(),
+                };
+                response.clone().on_hover_text(format!(
+                    "Port {}: {}\n{} - {}\n{}",
+                    port,
+                    owner,
+                    interval.start.format("%Y-%m-%d %H:%M:%S"),
+                    end,
+                    interval.entry.cmdline
+                ));
+            }
+        }
+    }
+
+    // Start, middle and end times under the bars
+    fn axis(ui: &mut egui::Ui, since: DateTime<Local>, now: DateTime<Local>) {
+        let (rect, _) =
+            ui.allocate_exact_size(egui::vec2(ui.available_width(), 18.0), egui::Sense::hover());
+        let chart =
+            egui::Rect::from_min_max(egui::pos2(rect.left() + LABEL_WIDTH, rect.top()), rect.max);
+        let painter = ui.painter_at(rect);
+        let format = if now - since > chrono::Duration::days(1) {
+            "%m-%d %H:%M"
+        } else {
+            "%H:%M"
+        };
+
+        let middle = since + (now - since) / 2;
+        for (time, align) in [
+            (since, egui::Align2::LEFT_TOP),
+            (middle, egui::Align2::CENTER_TOP),
+            (now, egui::Align2::RIGHT_TOP),
+        ] {
+            painter.text(
+                egui::pos2(Self::x_for(chart, time, since, now), chart.top()),
+                align,
+                time.format(format).to_string(),
+                egui::FontId::proportional(11.0),
+                egui::Color32::from_rgb(148, 163, 184),
+            );
+        }
+    }
+
+    fn x_for(
+        chart: egui::Rect,
+        time: DateTime<Local>,
+        since: DateTime<Local>,
+        now: DateTime<Local>,
+    ) -> f32 {
+        let span = (now - since).num_milliseconds().max(1) as f32;
+        let offset = (time - since).num_milliseconds() as f32;
+        chart.left() + chart.width() * (offset / span).clamp(0.0, 1.0)
+    }
+
+    // The same process keeps its color across rows and reloads
+    fn color_for(process_name: &str) -> egui::Color32 {
+        const PALETTE: [egui::Color32; 6] = [
+            egui::Color32::from_rgb(37, 99, 235),
+            egui::Color32::from_rgb(22, 163, 74),
+            egui::Color32::from_rgb(147, 51, 234),
+            egui::Color32::from_rgb(202, 138, 4),
+            egui::Color32::from_rgb(8, 145, 178),
+            egui::Color32::from_rgb(219, 39, 119),
+        ];
+        let hash = process_name.bytes().fold(0usize, |hash, byte| {
+            hash.wrapping_mul(31).wrapping_add(byte as usize)
+        });
+        PALETTE[hash % PALETTE.len()]
+    }
+}